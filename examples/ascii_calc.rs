@@ -25,15 +25,12 @@ mod ascii_calc {
     use std::io::{Write, stdin, stdout};
     use std::error::Error;
 
-    use rbop::node::function::Function;
-    use rbop::node::structured::EvaluationSettings;
     use termion::event::Key;
     use termion::input::TermRead;
     use termion::raw::IntoRawMode;
 
-    use rbop::{Token, UnstructuredNode, UnstructuredNodeList};
-    use rbop::node::unstructured::{UnstructuredNodeRoot, Upgradable};
-    use rbop::{renderers::AsciiRenderer, nav::NavPath, render::Renderer};
+    use rbop::prelude::*;
+    use rbop::redraw::RedrawTracker;
 
     pub fn main() -> Result<(), Box<dyn Error>> {
         // Add a nice panic handler - this example is great for testing new rbop functionality, so
@@ -54,72 +51,61 @@ mod ascii_calc {
         // Now set up all the pieces of an rbop state!
         //
         // There are two things which you will always need to use rbop:
-        //   - An instance of `UnstructuredNodeRoot`. This is a representation of the user's input,
-        //     which builds up a calculation to display and evaluate.
+        //   - A representation of the user's input, which builds up a calculation to display and
+        //     evaluate. Here we're using a `SimpleCalculator`, which bundles an editable
+        //     `UnstructuredNodeRoot` with a cursor (a `NavPath`) and the settings used to evaluate
+        //     it, and knows how to turn key presses into edits.
         //   - An instance of something implementing the `Renderer` trait. A renderer implementation
         //     can provide the information necessary for rbop to convert a tree of nodes into a list
         //     of simple glyphs, and then draw these glyphs to some kind of graphics surface.
         //
-        // If your node tree needs to be editable (i.e. you're not just using rbop to render
-        // pre-existing expressions), you will also need an instance of `NavPath`. This describes
-        // where the cursor is in the associated unstructured node tree.
-        //
         // Some use-cases may also need a `Viewport`, which describes the bounds of the graphics
         // surface to allow rbop to implement scrolling to keep the cursor on screen, and clipping
         // glyphs which are not visible in the viewport. For this example, we are not using a
-        // viewport, so rbop will assume an infinitely-sized area. (We could pass a viewport based
-        // on the size of the terminal, but this becomes a little tricky since the terminal could be
-        // resized on-the-fly. Viewports are more aimed at embedded use-cases with small, fixed-size
-        // displays.)
-        let mut root = UnstructuredNodeRoot { root: UnstructuredNodeList { items: vec![] } };
+        // viewport, so rbop will assume an infinitely-sized area. (We could set one on the
+        // calculator's editor based on the size of the terminal, but this becomes a little tricky
+        // since the terminal could be resized on-the-fly. Viewports are more aimed at embedded
+        // use-cases with small, fixed-size displays.)
+        let mut calc = SimpleCalculator::new();
         let mut renderer = AsciiRenderer::default();
-        let mut nav_path = NavPath::new(vec![0]);
+
+        // Remembers what was last drawn, so that we only redraw when something has changed
+        let mut tracker = RedrawTracker::new();
     
         // This is an infinite loop which iterates when a key is pressed
         for k in stdin.keys() {
-            // Match the pressed key. Most of the these keys, but not all, will insert a new node,
-            // so to avoid duplicating a `root.insert` call, we return Option<UnstructuredNode>. If
-            // `Some(node)`, we insert `node` afterwards; if `None`, that key doesn't need to insert
-            // anything.
-            let node_to_insert = match k? {
+            // Convert the pressed key into a key which the calculator understands, and press it.
+            // The renderer is needed because moving the cursor depends on the layout of the
+            // expression. Unrecognised characters are ignored by the calculator.
+            let key = match k? {
                 Key::Char('q') => break,
-                Key::Char(d) if d.is_digit(10) =>
-                    Some(UnstructuredNode::Token(Token::Digit(d.to_digit(10).unwrap() as u8))),
-    
-                Key::Char('+') => Some(UnstructuredNode::Token(Token::Add)),
-                Key::Char('-') => Some(UnstructuredNode::Token(Token::Subtract)),
-                Key::Char('*') => Some(UnstructuredNode::Token(Token::Multiply)),
-                Key::Char('/') => Some(UnstructuredNode::Fraction(
-                    UnstructuredNodeList::new(),
-                    UnstructuredNodeList::new(),
-                )),
-    
-                Key::Char('s') => Some(UnstructuredNode::Sqrt(
-                    UnstructuredNodeList::new()
-                )),
-                Key::Char('^') => Some(UnstructuredNode::Power(
-                    UnstructuredNodeList::new()
-                )),
-                Key::Char('t') => Some(UnstructuredNode::new_function_call(Function::Sine)),
-                Key::Char('g') => Some(UnstructuredNode::new_function_call(Function::GreatestCommonDenominator)),
-    
-                Key::Char('x') => Some(UnstructuredNode::Token(Token::Variable('x'))),
-                Key::Char('y') => Some(UnstructuredNode::Token(Token::Variable('y'))),
+                Key::Char(c) => CalculatorKey::Char(c),
  
-                Key::Left => { root.move_left(&mut nav_path, &mut renderer, None); None }
-                Key::Right => { root.move_right(&mut nav_path, &mut renderer, None); None }
-                Key::Down => { root.move_down(&mut nav_path, &mut renderer, None); None },
-                Key::Up => { root.move_up(&mut nav_path, &mut renderer, None); None },
+                Key::Left => CalculatorKey::Left,
+                Key::Right => CalculatorKey::Right,
+                Key::Down => CalculatorKey::Down,
+                Key::Up => CalculatorKey::Up,
     
-                Key::Backspace => { root.delete(&mut nav_path, &mut renderer, None); None },
-                _ => None,
+                Key::Backspace => CalculatorKey::Backspace,
+                _ => continue,
             };
+            calc.press(key, &mut renderer);
 
-            // If the pressed key needs to insert a node, insert it
-            if let Some(new_node) = node_to_insert {
-                root.insert(&mut nav_path, &mut renderer, None, new_node);
+            // Ask the renderer to draw the current node tree!
+            //
+            // This uses the renderer's `draw_all_by_layout` method, which is essentially a
+            // "do-it-all-in-one" method call, which wraps up rbop's important tasks of:
+            //   - Initialising the graphics surface
+            //   - Drawing glyphs to the graphics surface
+            //
+            // The calculator computes the layout of glyphs from the nodes first, and the tracker
+            // compares it with the last one which was drawn. If the key press didn't change
+            // anything (for example, moving left when the cursor is already at the start), there's
+            // no need to redraw the terminal at all.
+            if !calc.draw_if_changed(&mut renderer, &mut tracker) {
+                continue;
             }
-    
+
             // Move the cursor back up to the top right
             write!(stdout,
                 "{}{}",
@@ -127,15 +113,6 @@ mod ascii_calc {
                 termion::clear::All)
                  .unwrap();
     
-            // Ask the renderer to draw the current node tree!
-            //
-            // The `draw_all` method is essentially a "do-it-all-in-one" method call, which wraps
-            // up rbop's important tasks of:
-            //   - Computing a layout of glyphs from the nodes
-            //   - Initialising the graphics surface
-            //   - Drawing glyphs to the graphics surface
-            renderer.draw_all(&root, Some(&mut nav_path.to_navigator()), None);
-    
             // `AsciiRenderer` does not draw straight to the screen, it draws to a buffer of lines
             // of text - so print these to the console
             for line in renderer.lines.iter() {
@@ -144,24 +121,20 @@ mod ascii_calc {
     
             write!(stdout, "\r\n===================================\r\n")?;
     
-            // Next we'd like to evaluate this expression and print a result. Unstructured nodes do
-            // not express any precedence information, so first we need to *upgrade* it to a
-            // structured node tree. If the input tree contains parse errors, this will return None.
-            match root.upgrade() {
-                Ok(upgraded) => {
-                    // If the upgrade succeeded, we now have a valid structured node tree. We can
-                    // try to evaluate this now, which might fail if there are maths errors or
-                    // similar.
-                    match upgraded.evaluate(&EvaluationSettings::default()) {
-                        Ok(result) => write!(stdout, "{:?}", result)?,
-                        Err(err) => write!(stdout, "Evaluation error: {}", err)?,
-                    }
-                    
-                    // Also print the node tree
-                    write!(stdout, "\r\n\r\n{:?}", upgraded)?;
-                },
-                Err(err) => write!(stdout, "Parse error: {}", err)?,
-            };
+            // Next we'd like to evaluate this expression and print a result. The calculator does
+            // this in two steps. Unstructured nodes do not express any precedence information, so
+            // first it *upgrades* the tree to a structured node tree, which fails if the input
+            // contains parse errors. Then it evaluates the structured tree, which might fail if
+            // there are maths errors or similar.
+            match calc.evaluate() {
+                Ok(result) => write!(stdout, "{:?}", result)?,
+                Err(err) => write!(stdout, "Error: {}", err)?,
+            }
+
+            // Also print the node tree, if it's valid
+            if let Ok(upgraded) = calc.editor.root.upgrade() {
+                write!(stdout, "\r\n\r\n{:?}", upgraded)?;
+            }
     
             // Ensure everything is printed
             stdout.flush()?;
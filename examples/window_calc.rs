@@ -22,7 +22,7 @@ fn main() {
 #[cfg(feature = "examples")]
 mod window_calc {
     use std::rc::Rc;
-    use rbop::{Token, UnstructuredNode, UnstructuredNodeList, node::{unstructured::{UnstructuredNodeRoot, Upgradable}, structured::EvaluationSettings}, render::{Renderer, SizedGlyph, ViewportGlyph}};
+    use rbop::{calculator::{SimpleCalculator, CalculatorKey}, redraw::RedrawTracker, render::{Dimension, Renderer, SizedGlyph, ViewportGlyph}};
     use speedy2d::{self, Graphics2D, Window, color::Color, font::{Font, FormattedTextBlock, TextLayout, TextOptions}, window::{VirtualKeyCode, WindowHandler, WindowHelper}};
 
     // This is the struct we'll implement `Renderer` on! The fields will be very
@@ -48,8 +48,8 @@ mod window_calc {
         fn text_size(&mut self, text: &str, size_reduction_level: u32) -> rbop::render::Area {
             let layout = self.text_layout(text, size_reduction_level);
             rbop::render::Area {
-                width: layout.width() as u64,
-                height: layout.height() as u64,
+                width: layout.width() as Dimension,
+                height: layout.height() as Dimension,
             }
         }
 
@@ -96,19 +96,42 @@ mod window_calc {
                 rbop::render::Glyph::Subtract => self.text_size("-", size_reduction_level),
                 rbop::render::Glyph::Multiply => self.text_size("*", size_reduction_level),
                 rbop::render::Glyph::Divide => self.text_size("/", size_reduction_level),
+                rbop::render::Glyph::Equals => self.text_size("=", size_reduction_level),
+                rbop::render::Glyph::Caret => self.text_size("^", size_reduction_level),
+                rbop::render::Glyph::Boolean { name, .. } => self.text_size(name, size_reduction_level),
+                rbop::render::Glyph::EngineeringSuffix { suffix } =>
+                    self.text_size(&suffix.symbol().to_string(), size_reduction_level),
 
                 rbop::render::Glyph::Fraction { inner_width } => rbop::render::Area {
                     width: inner_width,
                     height: 3,
                 },
 
-                rbop::render::Glyph::Cursor { height } => rbop::render::Area {
+                rbop::render::Glyph::RadicalTick { inner_height } => rbop::render::Area {
+                    width: 12,
+                    height: inner_height,
+                },
+                rbop::render::Glyph::RadicalOverline { inner_width } => rbop::render::Area {
+                    width: inner_width,
+                    height: 3,
+                },
+
+                rbop::render::Glyph::Cursor { height, .. } => rbop::render::Area {
                     // Lie about the cursor width! This means that rbop doesn't make space for the
                     // cursor, so the cursor moving won't cause elements to shift a little bit
                     width: 0,
                     height,
                 },
                 rbop::render::Glyph::Placeholder => self.text_size("X", size_reduction_level),
+                rbop::render::Glyph::WavyUnderline { width } => rbop::render::Area { width, height: 4 },
+                rbop::render::Glyph::Strikethrough { width } => rbop::render::Area { width, height: 2 },
+
+                // Leave a gap either side of words
+                rbop::render::Glyph::Text { text } => self.text_size(&format!(" {} ", text), size_reduction_level),
+                rbop::render::Glyph::LeftBrace { inner_height } => rbop::render::Area {
+                    width: 12,
+                    height: inner_height,
+                },
 
                 // TODO: not everything's implemented
                 rbop::render::Glyph::LeftParenthesis { .. } => todo!(),
@@ -116,11 +139,16 @@ mod window_calc {
                 rbop::render::Glyph::Sqrt { .. } => todo!(),
                 rbop::render::Glyph::Point => todo!(),
                 rbop::render::Glyph::Variable { .. } => todo!(),
+                rbop::render::Glyph::Pi => todo!(),
                 rbop::render::Glyph::FunctionName { .. } => todo!(),
                 rbop::render::Glyph::Comma => todo!(),
             }
         }
 
+        // Radicals are drawn with lines, so let rbop split them into a tick and an overline rather
+        // than drawing the whole symbol ourselves
+        fn decompose_radicals(&self) -> bool { true }
+
         // After the layout has been computed, this `draw` method will be called for every glyph.
         // The implementation of this method should draw the passed glyph to the given point.
         fn draw(&mut self, viewport_glyph: rbop::render::ViewportGlyph) {
@@ -146,6 +174,11 @@ mod window_calc {
                 rbop::render::Glyph::Subtract => self.text_draw("-", point, size_reduction_level),
                 rbop::render::Glyph::Multiply => self.text_draw("*", point, size_reduction_level),
                 rbop::render::Glyph::Divide => self.text_draw("/", point, size_reduction_level),
+                rbop::render::Glyph::Equals => self.text_draw("=", point, size_reduction_level),
+                rbop::render::Glyph::Caret => self.text_draw("^", point, size_reduction_level),
+                rbop::render::Glyph::Boolean { name, .. } => self.text_draw(name, point, size_reduction_level),
+                rbop::render::Glyph::EngineeringSuffix { suffix } =>
+                    self.text_draw(&suffix.symbol().to_string(), point, size_reduction_level),
 
                 rbop::render::Glyph::Fraction { inner_width } => 
                     self.graphics.as_mut().unwrap().draw_line(
@@ -155,7 +188,22 @@ mod window_calc {
                         Color::BLACK
                     ),
 
-                rbop::render::Glyph::Cursor { height } =>
+                rbop::render::Glyph::RadicalTick { inner_height } => {
+                    // A short stroke down to the bottom, then a long stroke up to the overline
+                    let (x, y, height) = (point.x as f32, point.y as f32, inner_height as f32);
+                    let graphics = self.graphics.as_mut().unwrap();
+                    graphics.draw_line((x, y + height * 0.6), (x + 5.0, y + height), 2.0, Color::BLACK);
+                    graphics.draw_line((x + 5.0, y + height), (x + 12.0, y + 1.5), 2.0, Color::BLACK);
+                },
+                rbop::render::Glyph::RadicalOverline { inner_width } =>
+                    self.graphics.as_mut().unwrap().draw_line(
+                        (point.x as f32, point.y as f32 + 1.5),
+                        (point.x as f32 + inner_width as f32, point.y as f32 + 1.5),
+                        3.0,
+                        Color::BLACK
+                    ),
+
+                rbop::render::Glyph::Cursor { height, .. } =>
                     self.graphics.as_mut().unwrap().draw_line(
                         (point.x as f32, point.y as f32),
                         (point.x as f32, point.y as f32 + height as f32),
@@ -163,6 +211,37 @@ mod window_calc {
                         Color::BLACK
                     ),
                 rbop::render::Glyph::Placeholder => self.text_draw("?", point, size_reduction_level),
+                rbop::render::Glyph::WavyUnderline { width } => {
+                    // Zig-zag between the top and bottom of the glyph
+                    let graphics = self.graphics.as_mut().unwrap();
+                    let (x, y) = (point.x as f32, point.y as f32);
+                    for i in 0..(width / 4) {
+                        let start = x + i as f32 * 4.0;
+                        let (y1, y2) = if i % 2 == 0 { (y, y + 4.0) } else { (y + 4.0, y) };
+                        graphics.draw_line((start, y1), (start + 4.0, y2), 1.0, Color::RED);
+                    }
+                },
+                rbop::render::Glyph::Strikethrough { width } =>
+                    self.graphics.as_mut().unwrap().draw_line(
+                        (point.x as f32, point.y as f32 + 1.0),
+                        (point.x as f32 + width as f32, point.y as f32 + 1.0),
+                        2.0,
+                        Color::RED
+                    ),
+
+                rbop::render::Glyph::Text { text } =>
+                    self.text_draw(&format!(" {} ", text), point, size_reduction_level),
+                rbop::render::Glyph::LeftBrace { inner_height } => {
+                    // A vertical stroke with a point halfway down, and short serifs at each end
+                    let (x, y, height) = (point.x as f32, point.y as f32, inner_height as f32);
+                    let graphics = self.graphics.as_mut().unwrap();
+                    graphics.draw_line((x + 10.0, y), (x + 6.0, y + 2.0), 2.0, Color::BLACK);
+                    graphics.draw_line((x + 6.0, y + 2.0), (x + 6.0, y + height / 2.0 - 2.0), 2.0, Color::BLACK);
+                    graphics.draw_line((x + 6.0, y + height / 2.0 - 2.0), (x + 2.0, y + height / 2.0), 2.0, Color::BLACK);
+                    graphics.draw_line((x + 2.0, y + height / 2.0), (x + 6.0, y + height / 2.0 + 2.0), 2.0, Color::BLACK);
+                    graphics.draw_line((x + 6.0, y + height / 2.0 + 2.0), (x + 6.0, y + height - 2.0), 2.0, Color::BLACK);
+                    graphics.draw_line((x + 6.0, y + height - 2.0), (x + 10.0, y + height), 2.0, Color::BLACK);
+                },
 
                 // TODO: not everything's implemented
                 rbop::render::Glyph::LeftParenthesis { .. } => todo!(),
@@ -170,19 +249,19 @@ mod window_calc {
                 rbop::render::Glyph::Sqrt { .. } => todo!(),    
                 rbop::render::Glyph::Point => todo!(),
                 rbop::render::Glyph::Variable { .. } => todo!(),
+                rbop::render::Glyph::Pi => todo!(),
                 rbop::render::Glyph::FunctionName { .. } => todo!(),
                 rbop::render::Glyph::Comma => todo!(),
             }
         }
     }
 
-    /// The Speedy2D window handler implementation. This also contains the required pieces of the
-    /// rbop context, except the renderer - renderer instances are created on-the-fly, trading off
-    /// performance for "borrow checker sanity" :P
+    /// The Speedy2D window handler implementation. This also contains the calculator, which holds
+    /// the required pieces of the rbop context, except the renderer - renderer instances are
+    /// created on-the-fly, trading off performance for "borrow checker sanity" :P
     struct WindowCalc {
-        root: UnstructuredNodeRoot,
-        nav_path: rbop::nav::NavPath,
-        needs_draw: bool,
+        calc: SimpleCalculator,
+        tracker: RedrawTracker,
     }
 
     impl WindowCalc {
@@ -213,32 +292,28 @@ mod window_calc {
 
     impl WindowHandler for WindowCalc {
         fn on_draw(&mut self, helper: &mut WindowHelper, graphics: &mut Graphics2D) {
-            // Only draw the screen if there was an rbop input since the last draw
-            if self.needs_draw {
-                graphics.clear_screen(Color::from_rgb(1.0, 1.0, 1.0));
-
-                // A bit funky, but makes the borrow checker happy
-                // Means that we drop create_renderer's mutable borrow before using `draw_text` again
-                let result_text = {
-                    let mut renderer = self.create_renderer(Some(graphics));
-                    renderer.draw_all(&self.root, Some(&mut self.nav_path.to_navigator()), None);
-
-                    let result = self.root.upgrade().map(|x| x.evaluate(&EvaluationSettings::default()));
-                    renderer.text_layout(&match result {
-                        Ok(Ok(number)) => format!("{:?}", number),
-
-                        Ok(Err(error)) => error.to_string(),   
-                        Err(error) => error.to_string(),
-                    }, 0)
-                };
+            // Only draw the screen if the expression or cursor has changed since the last draw.
+            // The tracker remembers a hash of what was last drawn, so key presses which don't
+            // change anything (like moving left at the start of the expression) don't redraw.
+            //
+            // A bit funky, but makes the borrow checker happy
+            // Means that we drop create_renderer's mutable borrow before using `draw_text` again
+            let result_text = {
+                let mut renderer = self.create_renderer(Some(graphics));
+                let drawn = self.calc.draw_if_changed(&mut renderer, &mut self.tracker);
+
+                drawn.then(|| renderer.text_layout(&match self.calc.evaluate() {
+                    Ok(number) => format!("{:?}", number),
+                    Err(error) => error.to_string(),
+                }, 0))
+            };
 
+            if let Some(result_text) = result_text {
                 graphics.draw_text(
                     (20.0, 400.0),
                     Color::BLACK,
                     &result_text,
                 );
-
-                self.needs_draw = false;
             }
 
             helper.request_redraw();
@@ -249,62 +324,44 @@ mod window_calc {
             // used for its `size` method
             let mut renderer = self.create_renderer(None);
 
-            // Handle the input key
-            let node_to_insert = match virtual_key_code.unwrap() {
-                VirtualKeyCode::Key0 => Some(UnstructuredNode::Token(Token::Digit(0))),
-                VirtualKeyCode::Key1 => Some(UnstructuredNode::Token(Token::Digit(1))),
-                VirtualKeyCode::Key2 => Some(UnstructuredNode::Token(Token::Digit(2))),
-                VirtualKeyCode::Key3 => Some(UnstructuredNode::Token(Token::Digit(3))),
-                VirtualKeyCode::Key4 => Some(UnstructuredNode::Token(Token::Digit(4))),
-                VirtualKeyCode::Key5 => Some(UnstructuredNode::Token(Token::Digit(5))),
-                VirtualKeyCode::Key6 => Some(UnstructuredNode::Token(Token::Digit(6))),
-                VirtualKeyCode::Key7 => Some(UnstructuredNode::Token(Token::Digit(7))),
-                VirtualKeyCode::Key8 => Some(UnstructuredNode::Token(Token::Digit(8))),
-                VirtualKeyCode::Key9 => Some(UnstructuredNode::Token(Token::Digit(9))),
-
-                VirtualKeyCode::Plus => Some(UnstructuredNode::Token(Token::Add)),
-                VirtualKeyCode::Minus => Some(UnstructuredNode::Token(Token::Subtract)),
-                VirtualKeyCode::Asterisk => Some(UnstructuredNode::Token(Token::Multiply)),
-                VirtualKeyCode::Slash => Some(UnstructuredNode::Fraction(
-                    UnstructuredNodeList::new(),
-                    UnstructuredNodeList::new(),
-                )),
-
-                VirtualKeyCode::S => Some(UnstructuredNode::Sqrt(
-                    UnstructuredNodeList::new(),
-                )),
-                VirtualKeyCode::P => Some(UnstructuredNode::Power(
-                    UnstructuredNodeList::new(),
-                )),
-
-                VirtualKeyCode::Left => { self.root.move_left(&mut self.nav_path, &mut renderer, None); None }
-                VirtualKeyCode::Right => { self.root.move_right(&mut self.nav_path, &mut renderer, None); None }
-                VirtualKeyCode::Down => { self.root.move_down(&mut self.nav_path, &mut renderer, None); None }
-                VirtualKeyCode::Up => { self.root.move_up(&mut self.nav_path, &mut renderer, None); None }
-
-                VirtualKeyCode::Backspace => { self.root.delete(&mut self.nav_path, &mut renderer, None); None }
+            // Convert the input key into a calculator key, and press it
+            let key = match virtual_key_code.unwrap() {
+                VirtualKeyCode::Key0 => CalculatorKey::Char('0'),
+                VirtualKeyCode::Key1 => CalculatorKey::Char('1'),
+                VirtualKeyCode::Key2 => CalculatorKey::Char('2'),
+                VirtualKeyCode::Key3 => CalculatorKey::Char('3'),
+                VirtualKeyCode::Key4 => CalculatorKey::Char('4'),
+                VirtualKeyCode::Key5 => CalculatorKey::Char('5'),
+                VirtualKeyCode::Key6 => CalculatorKey::Char('6'),
+                VirtualKeyCode::Key7 => CalculatorKey::Char('7'),
+                VirtualKeyCode::Key8 => CalculatorKey::Char('8'),
+                VirtualKeyCode::Key9 => CalculatorKey::Char('9'),
+
+                VirtualKeyCode::Plus => CalculatorKey::Char('+'),
+                VirtualKeyCode::Minus => CalculatorKey::Char('-'),
+                VirtualKeyCode::Asterisk => CalculatorKey::Char('*'),
+                VirtualKeyCode::Slash => CalculatorKey::Char('/'),
+
+                VirtualKeyCode::S => CalculatorKey::Char('s'),
+                VirtualKeyCode::P => CalculatorKey::Char('^'),
+
+                VirtualKeyCode::Left => CalculatorKey::Left,
+                VirtualKeyCode::Right => CalculatorKey::Right,
+                VirtualKeyCode::Down => CalculatorKey::Down,
+                VirtualKeyCode::Up => CalculatorKey::Up,
+
+                VirtualKeyCode::Backspace => CalculatorKey::Backspace,
 
                 _ => return,
             };
-
-            if let Some(node) = node_to_insert {
-                self.root.insert(
-                    &mut self.nav_path,
-                    &mut renderer,
-                    None,
-                    node,
-                );
-            }
-
-            self.needs_draw = true;
+            self.calc.press(key, &mut renderer);
         }
     }
 
     pub fn main() {
         WindowCalc::new_window().run_loop(WindowCalc {
-            root: UnstructuredNodeRoot::new(),
-            nav_path: rbop::nav::NavPath::new(vec![0]),
-            needs_draw: true,
+            calc: SimpleCalculator::new(),
+            tracker: RedrawTracker::new(),
         })
     }
 }
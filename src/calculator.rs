@@ -0,0 +1,134 @@
+//! A headless calculator, which is the recommended starting point for building an application on
+//! top of rbop.
+//!
+//! [SimpleCalculator] wraps an [Editor], translating [CalculatorKey] presses into edit operations,
+//! and evaluates the expression being edited. It doesn't depend on any particular input or output
+//! system - applications only need to convert their own key events into [CalculatorKey]s, and
+//! provide a [Renderer] to draw with.
+//!
+//! Applications which need more control can use the [Editor] (or the underlying
+//! [UnstructuredNodeRoot](crate::UnstructuredNodeRoot)) directly instead.
+
+use crate::{Number, Token, UnstructuredNode, UnstructuredNodeList, editor::{Editor, EditOperation}, error::EvaluationError, node::{function::Function, structured::EvaluationSettings, unstructured::Upgradable}, render::{LayoutBlock, Renderer}};
+
+#[cfg(feature = "redraw")]
+use crate::{redraw::RedrawTracker, render::LayoutComputationProperties};
+
+/// A key which can be pressed on a [SimpleCalculator].
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CalculatorKey {
+    /// A character key. The following characters are recognised:
+    ///
+    ///   - Digits, `.`, `+`, `-` and `*` insert the corresponding token.
+    ///   - `/` inserts a fraction.
+    ///   - `^` inserts a power.
+    ///   - `s` inserts a square root.
+    ///   - `r` converts the square root containing the cursor into a root of any degree, or inserts
+    ///     a new root if there isn't one, like the "x√" key on a physical calculator.
+    ///   - `t` inserts a call to [Function::Sine], and `g` a call to
+    ///     [Function::GreatestCommonDenominator].
+    ///   - `p` inserts pi.
+    ///   - `x` and `y` insert variables.
+    Char(char),
+
+    Left,
+    Right,
+    Up,
+    Down,
+
+    /// Delete the item before the cursor.
+    Backspace,
+
+    /// Clear the entire expression.
+    Clear,
+}
+
+/// A calculator with an editable expression. See the
+/// [module-level documentation](crate::calculator) for more information.
+#[derive(Debug, Clone, Default)]
+pub struct SimpleCalculator {
+    /// The editor holding the expression and cursor.
+    pub editor: Editor,
+
+    /// The settings used by [evaluate](SimpleCalculator::evaluate).
+    pub settings: EvaluationSettings,
+}
+
+impl SimpleCalculator {
+    /// Creates a new calculator with an empty expression.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles a key press. Returns false if the key isn't recognised, or if it was rejected by the
+    /// editor's [validation](crate::editor::Editor#structfield.validation) or
+    /// [limits](crate::editor::Editor#structfield.limits), in which case nothing is changed.
+    pub fn press(&mut self, key: CalculatorKey, renderer: &mut impl Renderer) -> bool {
+        let operation = match key {
+            CalculatorKey::Left => EditOperation::MoveLeft,
+            CalculatorKey::Right => EditOperation::MoveRight,
+            CalculatorKey::Up => EditOperation::MoveUp,
+            CalculatorKey::Down => EditOperation::MoveDown,
+            CalculatorKey::Backspace => EditOperation::Delete,
+            CalculatorKey::Clear => EditOperation::Clear,
+
+            CalculatorKey::Char('r') => {
+                // Converting a square root always moves the cursor into the new degree slot, so if
+                // the cursor hasn't moved, there was no square root to convert
+                let path = self.editor.path.clone();
+                self.editor.apply(EditOperation::SqrtToRoot, renderer);
+                if self.editor.path != path {
+                    return true
+                }
+                EditOperation::Insert(UnstructuredNode::new_root())
+            },
+
+            CalculatorKey::Char(c) => match Self::char_node(c) {
+                Some(node) => EditOperation::Insert(node),
+                None => return false,
+            },
+        };
+
+        self.editor.apply(operation, renderer)
+    }
+
+    /// Returns the node inserted by a character key, other than `r`.
+    fn char_node(c: char) -> Option<UnstructuredNode> {
+        Some(match c {
+            '/' => UnstructuredNode::Fraction(UnstructuredNodeList::new(), UnstructuredNodeList::new()),
+            '^' => UnstructuredNode::Power(UnstructuredNodeList::new()),
+            's' => UnstructuredNode::Sqrt(UnstructuredNodeList::new()),
+            't' => UnstructuredNode::new_function_call(Function::Sine),
+            'g' => UnstructuredNode::new_function_call(Function::GreatestCommonDenominator),
+            'p' => UnstructuredNode::Token(Token::Pi),
+            'x' | 'y' => UnstructuredNode::Token(Token::Variable(c)),
+            _ => UnstructuredNode::Token(Token::from_char(c)?),
+        })
+    }
+
+    /// Upgrades and evaluates the current expression.
+    pub fn evaluate(&self) -> Result<Number, EvaluationError> {
+        Ok(self.editor.root.upgrade()?.evaluate(&self.settings)?)
+    }
+
+    /// Draws the current expression with a cursor, using the editor's viewport if it has one.
+    pub fn draw(&mut self, renderer: &mut impl Renderer) -> LayoutBlock {
+        renderer.draw_all(
+            &self.editor.root,
+            Some(&mut self.editor.path.to_navigator()),
+            self.editor.viewport.as_ref(),
+        )
+    }
+
+    /// Draws the current expression like [draw](SimpleCalculator::draw), but only if it looks
+    /// different to when the tracker last drew it. Returns true if it was drawn.
+    #[cfg(feature = "redraw")]
+    pub fn draw_if_changed(&mut self, renderer: &mut impl Renderer, tracker: &mut RedrawTracker) -> bool {
+        let layout = renderer.layout(
+            &self.editor.root,
+            Some(&mut self.editor.path.to_navigator()),
+            LayoutComputationProperties::default(),
+        );
+        tracker.draw_if_changed(renderer, &layout, self.editor.viewport.as_ref())
+    }
+}
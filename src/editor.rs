@@ -0,0 +1,370 @@
+//! An editor facade, which bundles an unstructured node tree together with its cursor and viewport
+//! and applies [EditOperation]s to them.
+//!
+//! The editor can also record the operations applied to it as an [EditMacro], which can be
+//! serialized and later replayed onto another editor. This can be used to implement user-defined
+//! input macros, or to write deterministic tests of complex editing sessions.
+//!
+//! Insertions can optionally be checked with [InputValidation], to catch mistakes like two binary
+//! operators in a row while the user is typing, rather than when the expression is upgraded. The
+//! size of the expression can also be bounded with [EditorLimits], which protects devices with
+//! little memory from unbounded input.
+
+use core::ops::Range;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Token, UnstructuredNode, UnstructuredNodeList, UnstructuredNodeRoot, nav::{NavPath, FractionNavigationOrder}, node::unstructured::Navigable, render::{Renderer, Viewport}, serialize::{Serializable, ByteRead}, error::{DeserializeError, EditError}};
+
+/// An operation which can be applied to an [Editor].
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum EditOperation {
+    /// Insert a node at the cursor.
+    Insert(UnstructuredNode),
+
+    /// Move the cursor left.
+    MoveLeft,
+
+    /// Move the cursor right.
+    MoveRight,
+
+    /// Move the cursor up.
+    MoveUp,
+
+    /// Move the cursor down.
+    MoveDown,
+
+    /// Delete the item before the cursor.
+    Delete,
+
+    /// Clear the entire tree.
+    Clear,
+
+    /// Convert the square root containing the cursor into a root of any degree.
+    SqrtToRoot,
+
+    /// Insert an empty [PowerOfTen](UnstructuredNode::PowerOfTen), and move the cursor into its
+    /// exponent.
+    PowerOfTen,
+}
+
+/// How an [Editor] handles insertions which would make the expression invalid.
+///
+/// An insertion is invalid if it places a binary operator (`+`, `*` or `/`) at the start of an
+/// expression or slot, or directly next to another operator. Subtraction is always allowed, since
+/// it can also be a unary minus, as in `2*-3`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum InputValidation {
+    /// All insertions are allowed.
+    #[default]
+    Off,
+
+    /// Invalid insertions are ignored.
+    Reject,
+
+    /// Invalid insertions are corrected where possible, by replacing the neighbouring operators
+    /// with the new one, like most hardware calculators. For example, typing `*` after `2+` gives
+    /// `2*`. Insertions which can't be corrected, such as a leading `*`, are ignored.
+    Correct,
+}
+
+/// The result of validating an insertion.
+enum Insertion {
+    Accept,
+    Reject,
+
+    /// Replace this range of the list with the inserted node.
+    Replace(Range<usize>),
+}
+
+impl InputValidation {
+    fn check(&self, list: &UnstructuredNodeList, index: usize, node: &UnstructuredNode) -> Insertion {
+        let is_operator = |n: &UnstructuredNode| matches!(n, UnstructuredNode::Token(Token::Add | Token::Subtract | Token::Multiply | Token::Divide));
+        let is_binary_operator = |n: &UnstructuredNode| is_operator(n) && *n != UnstructuredNode::Token(Token::Subtract);
+
+        if *self == InputValidation::Off || !is_binary_operator(node) {
+            return Insertion::Accept
+        }
+
+        // Find any run of operators directly before the cursor
+        let mut start = index;
+        while start > 0 && is_operator(&list.items[start - 1]) {
+            start -= 1;
+        }
+
+        if start == 0 {
+            // Nothing for the operator to apply to
+            Insertion::Reject
+        } else if start < index {
+            if *self == InputValidation::Correct { Insertion::Replace(start..index) } else { Insertion::Reject }
+        } else if list.items.get(index).map(is_binary_operator).unwrap_or(false) {
+            if *self == InputValidation::Correct { Insertion::Replace(index..index + 1) } else { Insertion::Reject }
+        } else {
+            Insertion::Accept
+        }
+    }
+}
+
+/// Limits on the size of the expression in an [Editor]. Operations which would exceed a limit are
+/// rejected with an [EditError]. Each limit is disabled if None.
+///
+/// Limits are only checked when nodes are added, so an expression which already exceeds them, such
+/// as one assigned to [Editor::root] directly, can still be edited to make it smaller.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct EditorLimits {
+    /// The maximum number of nodes in the expression, including nested nodes. When
+    /// [auto_multiply](Editor::auto_multiply) is enabled, a digit inserted after a structure needs
+    /// room for two nodes, since a multiplication may also be inserted.
+    pub max_nodes: Option<usize>,
+
+    /// The maximum number of nodes which can be added by one insertion, which mainly applies to
+    /// pasting.
+    pub max_insert_nodes: Option<usize>,
+
+    /// The maximum number of slots which any node can be nested inside. For example, a power
+    /// within a fraction gives a depth of 2.
+    pub max_depth: Option<usize>,
+}
+
+impl EditorLimits {
+    /// Checks whether inserting nodes at the cursor would exceed these limits. `depth` is the
+    /// nesting depth of the inserted nodes, and `automatic` is the number of other nodes which the
+    /// editor may insert alongside them.
+    fn check(&self, root: &UnstructuredNodeRoot, path: &NavPath, nodes: usize, automatic: usize, depth: usize) -> Result<(), EditError> {
+        if self.max_insert_nodes.map(|max| nodes > max).unwrap_or(false) {
+            return Err(EditError::InsertTooLarge)
+        }
+        if self.max_nodes.map(|max| root.root.node_count() + nodes + automatic > max).unwrap_or(false) {
+            return Err(EditError::TooManyNodes)
+        }
+
+        // Each slot the cursor is inside adds two entries to its path
+        if self.max_depth.map(|max| path.len() / 2 + depth > max).unwrap_or(false) {
+            return Err(EditError::TooDeep)
+        }
+
+        Ok(())
+    }
+}
+
+/// A recorded sequence of [EditOperation]s.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct EditMacro {
+    pub operations: Vec<EditOperation>,
+}
+
+impl EditMacro {
+    /// Applies each operation in this macro to an editor, in order.
+    pub fn replay(&self, editor: &mut Editor, renderer: &mut impl Renderer) {
+        for operation in &self.operations {
+            editor.apply(operation.clone(), renderer);
+        }
+    }
+}
+
+/// An unstructured node tree being edited, with a cursor and optional viewport. See the
+/// [module-level documentation](crate::editor) for more information.
+#[derive(Debug, Clone)]
+pub struct Editor {
+    pub root: UnstructuredNodeRoot,
+    pub path: NavPath,
+    pub viewport: Option<Viewport>,
+
+    /// The order in which the cursor moves through fractions when moving left and right.
+    pub navigation_order: FractionNavigationOrder,
+
+    /// How insertions which would make the expression invalid are handled.
+    pub validation: InputValidation,
+
+    /// If true, typing a digit directly after a structure such as a parenthesis, fraction or power
+    /// inserts a multiplication sign first. For example, typing `(1+2)3` gives `(1+2)*3`. This
+    /// multiplication would otherwise be implicit, which can be hard to read - particularly after
+    /// a fraction, where it looks like a mixed number.
+    pub auto_multiply: bool,
+
+    /// Limits on the size of the expression.
+    pub limits: EditorLimits,
+
+    recording: Option<Vec<EditOperation>>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            root: UnstructuredNodeRoot::new(),
+            path: NavPath::new(vec![0]),
+            viewport: None,
+            navigation_order: FractionNavigationOrder::default(),
+            validation: InputValidation::default(),
+            auto_multiply: false,
+            limits: EditorLimits::default(),
+            recording: None,
+        }
+    }
+}
+
+impl Editor {
+    /// Creates a new editor with an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies an operation to the tree and cursor. If a macro is being recorded, the operation is
+    /// added to it.
+    ///
+    /// Returns false if the operation was rejected by the editor's
+    /// [validation](#structfield.validation) or [limits](#structfield.limits), in which case
+    /// nothing is changed or recorded. Use [try_apply](Editor::try_apply) to find out why.
+    /// Recorded macros should be replayed onto an editor with the same validation and limits.
+    pub fn apply(&mut self, operation: EditOperation, renderer: &mut impl Renderer) -> bool {
+        self.try_apply(operation, renderer).is_ok()
+    }
+
+    /// Applies an operation like [apply](Editor::apply), but returns an error describing why the
+    /// operation was rejected.
+    pub fn try_apply(&mut self, operation: EditOperation, renderer: &mut impl Renderer) -> Result<(), EditError> {
+        // Check limits before anything is changed
+        match &operation {
+            EditOperation::Insert(node) => {
+                let automatic = self.auto_multiply && matches!(node, UnstructuredNode::Token(Token::Digit(_)));
+                self.limits.check(&self.root, &self.path, node.node_count(), automatic as usize, node.nesting_depth())?
+            },
+            EditOperation::PowerOfTen => self.limits.check(&self.root, &self.path, 1, 0, 1)?,
+            _ => (),
+        }
+
+        let (root, path, viewport) = (&mut self.root, &mut self.path, self.viewport.as_mut());
+        match &operation {
+            EditOperation::Insert(node) => {
+                let (list, mut index) = root.root.navigate(&mut path.to_navigator());
+                if self.auto_multiply && matches!(node, UnstructuredNode::Token(Token::Digit(_)))
+                    && index > 0 && Self::is_closing_structure(&list.items[index - 1])
+                {
+                    list.items.insert(index, UnstructuredNode::Token(Token::Multiply));
+                    path.offset(1);
+                    index += 1;
+                }
+
+                match self.validation.check(list, index, node) {
+                    Insertion::Accept => root.insert(path, renderer, viewport, node.clone()),
+                    Insertion::Reject => return Err(EditError::InvalidInput),
+                    Insertion::Replace(range) => {
+                        // Leave the cursor after the inserted node
+                        let start = range.start;
+                        list.items.splice(range, [node.clone()]);
+                        path.offset(start as isize + 1 - index as isize);
+                        root.ensure_cursor_visible(path, renderer, viewport);
+                    },
+                }
+            },
+            EditOperation::MoveLeft => root.move_left_with_order(path, self.navigation_order, renderer, viewport),
+            EditOperation::MoveRight => root.move_right_with_order(path, self.navigation_order, renderer, viewport),
+            EditOperation::MoveUp => { root.move_up(path, renderer, viewport); },
+            EditOperation::MoveDown => { root.move_down(path, renderer, viewport); },
+            EditOperation::Delete => root.delete(path, renderer, viewport),
+            EditOperation::Clear => root.clear(path, renderer, viewport),
+            EditOperation::SqrtToRoot => { root.sqrt_to_root(path, renderer, viewport); },
+            EditOperation::PowerOfTen => root.insert_power_of_ten(path, renderer, viewport),
+        }
+
+        if let Some(recording) = &mut self.recording {
+            recording.push(operation);
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the given node is drawn with a closing edge, such as a parenthesis, which a
+    /// following digit could be implicitly multiplied with.
+    fn is_closing_structure(node: &UnstructuredNode) -> bool {
+        !matches!(node, UnstructuredNode::Token(_) | UnstructuredNode::Styled(_, _))
+    }
+
+    /// Finds where digit grouping separators should be displayed in the number containing the
+    /// cursor. See [UnstructuredNodeRoot::digit_grouping].
+    pub fn digit_grouping(&self, group_size: usize) -> Vec<NavPath> {
+        self.root.digit_grouping(&self.path, group_size)
+    }
+
+    /// Starts recording operations into a new macro, discarding any macro currently being
+    /// recorded.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(vec![]);
+    }
+
+    /// Returns true if operations are currently being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Stops recording operations, and returns the recorded macro, or None if nothing was being
+    /// recorded.
+    pub fn stop_recording(&mut self) -> Option<EditMacro> {
+        self.recording.take().map(|operations| EditMacro { operations })
+    }
+}
+
+impl Serializable for EditOperation {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            EditOperation::Insert(node) => {
+                bytes.push(1);
+                node.serialize_into(bytes);
+            },
+            EditOperation::MoveLeft => bytes.push(2),
+            EditOperation::MoveRight => bytes.push(3),
+            EditOperation::MoveUp => bytes.push(4),
+            EditOperation::MoveDown => bytes.push(5),
+            EditOperation::Delete => bytes.push(6),
+            EditOperation::Clear => bytes.push(7),
+            EditOperation::SqrtToRoot => bytes.push(8),
+            EditOperation::PowerOfTen => bytes.push(9),
+        }
+    }
+
+    fn serialized_size_hint(&self) -> usize {
+        match self {
+            EditOperation::Insert(node) => 1 + node.serialized_size_hint(),
+            _ => 1,
+        }
+    }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        Ok(match reader.read_u8()? {
+            1 => EditOperation::Insert(UnstructuredNode::deserialize_from(reader)?),
+            2 => EditOperation::MoveLeft,
+            3 => EditOperation::MoveRight,
+            4 => EditOperation::MoveUp,
+            5 => EditOperation::MoveDown,
+            6 => EditOperation::Delete,
+            7 => EditOperation::Clear,
+            8 => EditOperation::SqrtToRoot,
+            9 => EditOperation::PowerOfTen,
+
+            tag => return Err(DeserializeError::InvalidTag(tag)),
+        })
+    }
+}
+
+impl Serializable for EditMacro {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.operations.len().serialize_into(bytes);
+        for operation in &self.operations {
+            operation.serialize_into(bytes);
+        }
+    }
+
+    fn serialized_size_hint(&self) -> usize {
+        self.operations.len().serialized_size_hint()
+            + self.operations.iter().map(|o| o.serialized_size_hint()).sum::<usize>()
+    }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        let len = usize::deserialize_from(reader)?;
+        let mut operations = vec![];
+        for _ in 0..len {
+            operations.push(EditOperation::deserialize_from(reader)?);
+        }
+        Ok(EditMacro { operations })
+    }
+}
@@ -1,8 +1,8 @@
 //! Error types for various operations.
 
-use alloc::{fmt, vec, vec::Vec};
+use alloc::{fmt, vec::Vec};
 
-use crate::serialize::Serializable;
+use crate::{Number, StructuredNode, node::function::Function, serialize::{Serializable, ByteRead}};
 
 /// A trait implemented on any rbop error.
 pub trait Error : alloc::fmt::Display + alloc::fmt::Debug {}
@@ -44,25 +44,27 @@ impl fmt::Display for NodeError {
 impl Error for NodeError {}
 
 impl Serializable for NodeError {
-    fn serialize(&self) -> Vec<u8> {
-        vec![match self {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self {
             NodeError::UnexpectedTokensAtEnd => 1,
             NodeError::PowerMissingBase => 2,
             NodeError::ExpectedUnit => 3,
             NodeError::CannotUpgradeToken => 4,
             NodeError::Overflow => 5,
-        }]
+        })
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        Some(match bytes.next()? {
+    fn serialized_size_hint(&self) -> usize { 1 }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        Ok(match reader.read_u8()? {
             1 => NodeError::UnexpectedTokensAtEnd,
             2 => NodeError::PowerMissingBase,
             3 => NodeError::ExpectedUnit,
             4 => NodeError::CannotUpgradeToken,
             5 => NodeError::Overflow,
 
-            _ => return None,
+            tag => return Err(DeserializeError::InvalidTag(tag)),
         })
     }
 }
@@ -86,6 +88,21 @@ pub enum MathsError {
 
     /// Raising to a power would give an imaginary result, which rbop cannot represent.
     Imaginary,
+
+    /// The result is an indeterminate form, such as 0⁰ or 0/0, which has been configured not to
+    /// have a value.
+    Indeterminate,
+
+    /// The result is undefined, such as 0⁰ or 0/0 when configured to be undefined. This is the
+    /// expected outcome rather than a failure, so applications may want to present it as a result.
+    Undefined,
+
+    /// A function was called with an argument outside of its domain, for example the arcsine of 2
+    /// or the natural logarithm of a negative number.
+    DomainError {
+        function: Function,
+        argument: Number,
+    },
 }
 
 impl fmt::Display for MathsError {
@@ -96,31 +113,227 @@ impl fmt::Display for MathsError {
             MathsError::MissingVariable => "cannot evaluate variable",
             MathsError::Overflow => "numeric overflow",
             MathsError::Imaginary => "imaginary",
+            MathsError::Indeterminate => "indeterminate",
+            MathsError::Undefined => "undefined",
+            MathsError::DomainError { function, argument } =>
+                return write!(f, "domain error: {}({})", function.render_name(), argument.to_decimal()),
         })
     }
 }
 impl Error for MathsError {}
 
 impl Serializable for MathsError {
-    fn serialize(&self) -> Vec<u8> {
-        vec![match self {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self {
             MathsError::DivisionByZero => 1,
             MathsError::InvalidSqrt => 2,
             MathsError::MissingVariable => 3,
             MathsError::Overflow => 4,
             MathsError::Imaginary => 5,
-        }]
+            MathsError::Indeterminate => 6,
+            MathsError::DomainError { .. } => 7,
+            MathsError::Undefined => 8,
+        });
+
+        if let MathsError::DomainError { function, argument } = self {
+            function.serialize_into(bytes);
+            argument.serialize_into(bytes);
+        }
+    }
+
+    fn serialized_size_hint(&self) -> usize {
+        match self {
+            MathsError::DomainError { function, argument } =>
+                1 + function.serialized_size_hint() + argument.serialized_size_hint(),
+            _ => 1,
+        }
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        Some(match bytes.next()? {
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        Ok(match reader.read_u8()? {
             1 => MathsError::DivisionByZero,
             2 => MathsError::InvalidSqrt,
             3 => MathsError::MissingVariable,
             4 => MathsError::Overflow,
             5 => MathsError::Imaginary,
+            6 => MathsError::Indeterminate,
+            7 => MathsError::DomainError {
+                function: Function::deserialize_from(reader)?,
+                argument: Number::deserialize_from(reader)?,
+            },
+            8 => MathsError::Undefined,
+
+            tag => return Err(DeserializeError::InvalidTag(tag)),
+        })
+    }
+}
+
+/// A [MathsError], along with information about the operation which caused it. This is returned by
+/// [StructuredNode::evaluate_detailed].
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MathsErrorDetail {
+    /// The error which occurred.
+    pub error: MathsError,
+
+    /// The node which could not be evaluated.
+    pub node: StructuredNode,
+
+    /// The evaluated values of the node's operands, in order. For example, for a division by zero,
+    /// this would be the dividend and the divisor (which is 0).
+    pub operands: Vec<Number>,
+
+    /// The indexes to follow from the root of the evaluated tree to reach [node](#structfield.node),
+    /// using the order given by [StructuredNode::children]. For example, \[1, 0\] means the first
+    /// child of the second child of the root.
+    pub path: Vec<usize>,
+}
+
+impl fmt::Display for MathsErrorDetail {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if !self.operands.is_empty() {
+            write!(f, " (operands: ")?;
+            for (i, operand) in self.operands.iter().enumerate() {
+                if i > 0 { write!(f, ", ")?; }
+                write!(f, "{}", operand.to_decimal())?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+impl Error for MathsErrorDetail {}
+
+/// An error which occurs while evaluating an unstructured node tree, which must be upgraded before
+/// it can be evaluated.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum EvaluationError {
+    /// The node tree could not be upgraded.
+    Node(NodeError),
+
+    /// The upgraded node tree could not be evaluated.
+    Maths(MathsError),
+
+    /// The part of the node tree to evaluate, such as a selection, could not be found.
+    Path(PathError),
+}
+
+impl From<NodeError> for EvaluationError {
+    fn from(e: NodeError) -> Self { EvaluationError::Node(e) }
+}
+
+impl From<MathsError> for EvaluationError {
+    fn from(e: MathsError) -> Self { EvaluationError::Maths(e) }
+}
+
+impl From<PathError> for EvaluationError {
+    fn from(e: PathError) -> Self { EvaluationError::Path(e) }
+}
+
+impl fmt::Display for EvaluationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvaluationError::Node(e) => write!(f, "{}", e),
+            EvaluationError::Maths(e) => write!(f, "{}", e),
+            EvaluationError::Path(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl Error for EvaluationError {}
 
-            _ => return None,
+/// An error which occurs while deserializing an item.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum DeserializeError {
+    /// The input ended before the item was complete.
+    UnexpectedEof,
+
+    /// A byte which should identify the kind of item (or part of an item) which follows was not
+    /// recognised. The unrecognised byte is included.
+    InvalidTag(u8),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeserializeError::UnexpectedEof => write!(f, "unexpected end of data"),
+            DeserializeError::InvalidTag(tag) => write!(f, "invalid tag {}", tag),
+        }
+    }
+}
+impl Error for DeserializeError {}
+
+/// An error which occurs when a navigation path passed to a structural editing method, such as
+/// [insert_at](crate::UnstructuredNodeRoot::insert_at), is not valid for the node tree.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum PathError {
+    /// The path does not lead to a position within a node list.
+    InvalidPath,
+
+    /// The path leads to a valid position, but there is no node after that position to act on.
+    NoNodeAtPath,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            PathError::InvalidPath => "invalid path",
+            PathError::NoNodeAtPath => "no node at path",
+        })
+    }
+}
+impl Error for PathError {}
+
+/// An error which occurs when an [Editor](crate::editor::Editor) rejects an operation. Nothing is
+/// changed when this happens.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum EditError {
+    /// The insertion would make the expression invalid, as determined by the editor's
+    /// [InputValidation](crate::editor::InputValidation).
+    InvalidInput,
+
+    /// The expression would contain more nodes than the editor's
+    /// [limits](crate::editor::EditorLimits::max_nodes) allow.
+    TooManyNodes,
+
+    /// The inserted node contains more nodes than can be inserted at once, according to the
+    /// editor's [limits](crate::editor::EditorLimits::max_insert_nodes).
+    InsertTooLarge,
+
+    /// The expression would be nested more deeply than the editor's
+    /// [limits](crate::editor::EditorLimits::max_depth) allow.
+    TooDeep,
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            EditError::InvalidInput => "invalid input",
+            EditError::TooManyNodes => "expression too long",
+            EditError::InsertTooLarge => "insertion too long",
+            EditError::TooDeep => "expression too deeply nested",
+        })
+    }
+}
+impl Error for EditError {}
+
+/// An error which occurs when a layout computed by [try_layout](crate::render::Renderer::try_layout)
+/// would exceed its [LayoutLimits](crate::render::LayoutLimits).
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum LayoutError {
+    /// The layout would contain more glyphs than allowed. This is checked before any layout is
+    /// computed, so no memory is allocated for the glyphs.
+    TooManyGlyphs,
+
+    /// The layout is wider or taller than allowed.
+    TooLarge,
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            LayoutError::TooManyGlyphs => "expression too long",
+            LayoutError::TooLarge => "expression too large",
         })
     }
 }
+impl Error for LayoutError {}
@@ -4,7 +4,14 @@
 //! that other evaluable kinds of nodes could be added later, for example compiled nodes which 
 //! aren't editable but are much faster to evaluate.
 
-use crate::{Number, error::MathsError};
+//!
+//! This module also provides [IncrementalEvaluation], which evaluates a structured node a few
+//! steps at a time. This is useful on single-threaded devices, where a long evaluation would
+//! otherwise stop the user interface from responding until it completes.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Number, StructuredNode, error::MathsError, node::structured::EvaluationSettings};
 
 /// Something which can be evaluated into a number, optionally with variables substituted into it.
 pub trait Evaluable {
@@ -23,3 +30,98 @@ pub trait Evaluable {
     /// Substitutes the named variable with a given value, and returns a new evaluable expression.
     fn substitute(self, variable: char, value: Number) -> Self::Substituted;
 }
+
+/// A node which is part-way through being evaluated by an [IncrementalEvaluation], along with the
+/// values of the children which have been evaluated so far.
+#[derive(Debug, Clone)]
+struct EvaluationFrame<'a> {
+    node: &'a StructuredNode,
+    operands: Vec<Number>,
+}
+
+/// An evaluation of a [StructuredNode] which can be paused and resumed, performing a limited number
+/// of steps each time it is resumed.
+///
+/// Each step either moves into a child of a node, or evaluates a node whose children have all
+/// been evaluated. An individual operation, such as a single call to an expensive function, cannot
+/// be split across steps.
+///
+/// The result is the same as [StructuredNode::evaluate], including the short-circuiting of logical
+/// functions and the lazy evaluation of conditionals.
+#[derive(Debug, Clone)]
+pub struct IncrementalEvaluation<'a> {
+    settings: &'a EvaluationSettings,
+    stack: Vec<EvaluationFrame<'a>>,
+    result: Option<Result<Number, MathsError>>,
+}
+
+impl<'a> IncrementalEvaluation<'a> {
+    /// Prepares to evaluate a node. No evaluation takes place until [step](Self::step) is called.
+    pub fn new(node: &'a StructuredNode, settings: &'a EvaluationSettings) -> Self {
+        IncrementalEvaluation {
+            settings,
+            stack: vec![EvaluationFrame { node, operands: vec![] }],
+            result: None,
+        }
+    }
+
+    /// Returns true if the evaluation has completed, either successfully or with an error.
+    pub fn is_finished(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Performs at most `max_steps` steps of the evaluation. Returns the result if the evaluation
+    /// has completed, or None if more steps are needed.
+    ///
+    /// Once the evaluation has completed, further calls return the same result without doing any
+    /// more work.
+    pub fn step(&mut self, max_steps: usize) -> Option<Result<Number, MathsError>> {
+        for _ in 0..max_steps {
+            if self.result.is_some() {
+                break
+            }
+            self.step_once();
+        }
+
+        self.result.clone()
+    }
+
+    /// Performs steps until the evaluation completes, and returns its result.
+    pub fn finish(&mut self) -> Result<Number, MathsError> {
+        self.step(usize::MAX).unwrap()
+    }
+
+    fn step_once(&mut self) {
+        let frame = self.stack.last().unwrap();
+
+        // Move into the next child to evaluate, if there are any left
+        if let Some((_, child)) = frame.node.next_operand(&frame.operands) {
+            self.stack.push(EvaluationFrame { node: child, operands: vec![] });
+            return
+        }
+
+        // All of the children have been evaluated, so evaluate this node
+        let frame = self.stack.pop().unwrap();
+        let mut value = match frame.node.evaluate_with_operands(&frame.operands, self.settings) {
+            Ok(value) => value,
+            Err(error) => {
+                self.stack.clear();
+                self.result = Some(Err(error));
+                return
+            },
+        };
+
+        // Pass the value to the parent node, which might be able to use it to short-circuit
+        while let Some(parent) = self.stack.last_mut() {
+            if let Some(result) = parent.node.short_circuit(&value) {
+                self.stack.pop();
+                value = result;
+                continue
+            }
+
+            parent.operands.push(value);
+            return
+        }
+        self.result = Some(Ok(value));
+    }
+}
@@ -21,6 +21,14 @@ pub mod decimal_ext;
 pub mod number;
 pub mod serialize;
 pub mod evaluate;
+pub mod worksheet;
+pub mod template;
+pub mod editor;
+pub mod calculator;
+pub mod prelude;
+
+#[cfg(feature = "redraw")]
+pub mod redraw;
 
 #[cfg(test)]
 mod tests;
@@ -6,9 +6,11 @@
 //! type, this is currently only on unstructured nodes, implementing using the
 //! [Navigable](crate::node::unstructured::Navigable) trait.
 
+use core::fmt;
+
 use alloc::{vec, vec::Vec};
 
-use crate::{UnstructuredNodeList, render::{Layoutable, Renderer, LayoutComputationProperties}};
+use crate::{UnstructuredNodeList, render::{Dimension, Layoutable, Renderer, LayoutComputationProperties, SignedDimension}};
 
 /// Describes the movements which must be taken down a node tree to reach the position of the 
 /// cursor.
@@ -75,15 +77,31 @@ use crate::{UnstructuredNodeList, render::{Layoutable, Renderer, LayoutComputati
 /// 12+---
 ///    45
 /// ```
-#[derive(PartialEq, Eq, Debug, Clone)]
+///
+/// The [Debug] representation lists the indices separated by commas, like `3,0,3,1,1`.
+#[derive(PartialEq, Eq, Clone)]
 pub struct NavPath {
     path: Vec<usize>,
 }
 
+impl fmt::Debug for NavPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, index) in self.path.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", index)?;
+        }
+        Ok(())
+    }
+}
+
 impl NavPath {
     pub fn new(path: Vec<usize>) -> Self { Self { path } }
 
-    pub fn to_navigator(&mut self) -> NavPathNavigator {
+    /// Creates a navigator for stepping through this path. This only borrows the path immutably,
+    /// so one path can be used to lay out the same tree from several threads at once.
+    pub fn to_navigator(&self) -> NavPathNavigator<'_> {
         NavPathNavigator {
             path: self,
             index: 0 
@@ -107,6 +125,11 @@ impl NavPath {
         self.path.push(index);
     }
 
+    /// Adds index to the start of this path, making it relative to an enclosing node or slot.
+    pub(crate) fn push_front(&mut self, index: usize) {
+        self.path.insert(0, index);
+    }
+
     /// Adds n to the final entry of this path. This will not navigate deep into node structures,
     /// you should use `Node`'s `move_x` methods for this.
     pub fn offset(&mut self, n: isize) {
@@ -127,6 +150,33 @@ impl core::ops::Index<usize> for NavPath {
     }
 }
 
+/// Describes a contiguous range of items within one [UnstructuredNodeList] of a node tree, for
+/// example to evaluate part of an expression independently of the rest.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum NavSelection {
+    /// A number of items, starting at the position described by the path. The path is interpreted
+    /// in the same way as a cursor position, so the selection begins with the item after the cursor.
+    Range(NavPath, usize),
+
+    /// Every item in the list containing the position described by the path. For example, if the
+    /// path is inside the top of a fraction, the entire top of the fraction is selected.
+    Container(NavPath),
+}
+
+impl NavSelection {
+    /// Returns a path to the position in the selection's list where the selection begins.
+    pub fn start(&self) -> NavPath {
+        match self {
+            NavSelection::Range(path, _) => path.clone(),
+            NavSelection::Container(path) => {
+                let mut path = path.clone();
+                *path.path.last_mut().unwrap() = 0;
+                path
+            }
+        }
+    }
+}
+
 /// Provides utilities for stepping through a path, one index at a time.
 pub struct NavPathNavigator<'a> {
     path: &'a NavPath,
@@ -162,6 +212,22 @@ impl<'a> NavPathNavigator<'a> {
     }
 }
 
+/// The order in which the cursor moves through the slots of a fraction when moving left and right.
+/// Calculators differ in this, so it is configurable.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum FractionNavigationOrder {
+    /// Moving right out of either the numerator or denominator leaves the fraction, and moving left
+    /// into a fraction enters the end of the numerator. The denominator is only reachable by moving
+    /// down.
+    #[default]
+    Direct,
+
+    /// The numerator and denominator are visited in reading order: moving right out of the
+    /// numerator enters the start of the denominator, and moving left into a fraction enters the
+    /// end of the denominator.
+    ReadingOrder,
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum MoveVerticalDirection {
     Up,
@@ -203,11 +269,11 @@ pub fn match_vertical_cursor_points(
         .collect::<Vec<_>>();
 
     // Work out complete widths
-    let from_total_width: u64 = from_layouts
+    let from_total_width: Dimension = from_layouts
         .iter()
         .map(|x| x.area.width)
         .sum();
-    let to_total_width: u64 = to_layouts
+    let to_total_width: Dimension = to_layouts
         .iter()
         .map(|x| x.area.width)
         .sum();
@@ -242,8 +308,8 @@ pub fn match_vertical_cursor_points(
         let mut closest_to_idx_found = 0;
 
         for (i, to_point) in to_boundary_points.iter().enumerate() {
-            let this_distance = (*to_point as i64 - from_point as i64).abs();
-            let best_distance = (to_boundary_points[closest_to_idx_found] as i64 - from_point as i64).abs();
+            let this_distance = (*to_point as SignedDimension - from_point as SignedDimension).abs();
+            let best_distance = (to_boundary_points[closest_to_idx_found] as SignedDimension - from_point as SignedDimension).abs();
             if this_distance < best_distance {
                 closest_to_idx_found = i;
             }
@@ -0,0 +1,161 @@
+//! Assumptions about the values of variables, used to decide whether symbolic manipulations are
+//! valid.
+//! 
+//! Many identities only hold for some values. For example, √(x²) = x is only true when x ≥ 0, so
+//! [SimplifiedNode](crate::node::simplified::SimplifiedNode) reduction will only apply it if it is
+//! known that x is non-negative. With no assumptions, reduction leaves such expressions alone.
+
+use alloc::{vec, vec::Vec};
+use num_traits::Zero;
+
+use crate::{Number, error::DeserializeError, serialize::{Serializable, ByteRead}};
+
+use super::simplified::SimplifiedNode;
+
+/// Something which can be assumed about a variable.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Assumption {
+    /// The variable is greater than 0.
+    Positive,
+
+    /// The variable is greater than or equal to 0.
+    NonNegative,
+
+    /// The variable is a whole number.
+    Integer,
+}
+
+impl Assumption {
+    /// Returns true if this assumption means that `other` must also hold.
+    pub fn implies(&self, other: Assumption) -> bool {
+        *self == other || (*self == Assumption::Positive && other == Assumption::NonNegative)
+    }
+
+    /// Returns true if a value satisfies this assumption.
+    pub fn satisfied_by(&self, value: &Number) -> bool {
+        match self {
+            Assumption::Positive => *value > Number::zero(),
+            Assumption::NonNegative => *value >= Number::zero(),
+            Assumption::Integer => value.to_whole().is_some(),
+        }
+    }
+}
+
+/// A set of [Assumption]s about variables.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Assumptions {
+    assumptions: Vec<(char, Assumption)>,
+}
+
+impl Assumptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an assumption about a variable.
+    pub fn assume(&mut self, variable: char, assumption: Assumption) {
+        if !self.assumptions.contains(&(variable, assumption)) {
+            self.assumptions.push((variable, assumption));
+        }
+    }
+
+    /// Removes all assumptions about a variable.
+    pub fn forget(&mut self, variable: char) {
+        self.assumptions.retain(|(v, _)| *v != variable);
+    }
+
+    /// Returns true if an assumption about a variable is known to hold, either because it was
+    /// assumed directly or because it is implied by another assumption.
+    pub fn holds(&self, variable: char, assumption: Assumption) -> bool {
+        self.assumptions.iter().any(|(v, a)| *v == variable && a.implies(assumption))
+    }
+
+    /// Returns true if a candidate value for a variable satisfies all of the assumptions about that
+    /// variable. This can be used to filter out extraneous solutions of an equation.
+    pub fn satisfied_by(&self, variable: char, value: &Number) -> bool {
+        self.assumptions.iter()
+            .filter(|(v, _)| *v == variable)
+            .all(|(_, a)| a.satisfied_by(value))
+    }
+
+    /// Returns true if a node is known to be greater than or equal to 0.
+    pub fn is_non_negative(&self, node: &SimplifiedNode) -> bool {
+        match node {
+            SimplifiedNode::Number(n) => *n >= Number::zero(),
+            SimplifiedNode::Variable(v) => self.holds(*v, Assumption::NonNegative),
+            SimplifiedNode::Add(v) | SimplifiedNode::Multiply(v) => v.iter().all(|n| self.is_non_negative(n)),
+            SimplifiedNode::Power(b, e) => self.is_non_negative(b) || self.is_even_integer(e),
+            SimplifiedNode::FunctionCall(_, _) => false,
+        }
+    }
+
+    /// Returns true if a node is known not to be 0.
+    pub fn is_non_zero(&self, node: &SimplifiedNode) -> bool {
+        match node {
+            SimplifiedNode::Number(n) => !n.is_zero(),
+            SimplifiedNode::Variable(v) => self.holds(*v, Assumption::Positive),
+            SimplifiedNode::Multiply(v) => v.iter().all(|n| self.is_non_zero(n)),
+            SimplifiedNode::Power(b, _) => self.is_non_zero(b),
+            SimplifiedNode::Add(_) | SimplifiedNode::FunctionCall(_, _) => false,
+        }
+    }
+
+    /// Returns true if a node is known to be a whole number.
+    pub fn is_integer(&self, node: &SimplifiedNode) -> bool {
+        match node {
+            SimplifiedNode::Number(n) => n.to_whole().is_some(),
+            SimplifiedNode::Variable(v) => self.holds(*v, Assumption::Integer),
+            SimplifiedNode::Add(v) | SimplifiedNode::Multiply(v) => v.iter().all(|n| self.is_integer(n)),
+            SimplifiedNode::Power(_, _) | SimplifiedNode::FunctionCall(_, _) => false,
+        }
+    }
+
+    fn is_even_integer(&self, node: &SimplifiedNode) -> bool {
+        matches!(node, SimplifiedNode::Number(n) if matches!(n.to_whole(), Some(n) if n % 2 == 0))
+    }
+}
+
+impl Serializable for Assumption {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self {
+            Assumption::Positive => 1,
+            Assumption::NonNegative => 2,
+            Assumption::Integer => 3,
+        })
+    }
+
+    fn serialized_size_hint(&self) -> usize { 1 }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        match reader.read_u8()? {
+            1 => Ok(Assumption::Positive),
+            2 => Ok(Assumption::NonNegative),
+            3 => Ok(Assumption::Integer),
+            tag => Err(DeserializeError::InvalidTag(tag)),
+        }
+    }
+}
+
+impl Serializable for Assumptions {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.assumptions.len().serialize_into(bytes);
+        for (variable, assumption) in &self.assumptions {
+            bytes.push(*variable as u8);
+            assumption.serialize_into(bytes);
+        }
+    }
+
+    fn serialized_size_hint(&self) -> usize {
+        self.assumptions.len().serialized_size_hint() + self.assumptions.len() * 2
+    }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        let len = usize::deserialize_from(reader)?;
+        let mut assumptions = vec![];
+        for _ in 0..len {
+            let variable = reader.read_u8()? as char;
+            assumptions.push((variable, Assumption::deserialize_from(reader)?));
+        }
+        Ok(Assumptions { assumptions })
+    }
+}
@@ -2,14 +2,13 @@ use core::cmp::max;
 
 use alloc::vec;
 
-use crate::{nav::NavPathNavigator, render::{Glyph, LayoutBlock, Layoutable, MergeBaseline, Renderer, LayoutComputationProperties}};
+use crate::{Token, UnstructuredNode, UnstructuredNodeList, nav::NavPathNavigator, render::{Glyph, LayoutBlock, Layoutable, MergeBaseline, Renderer, LayoutComputationProperties}};
 
 use super::function::Function;
 
 pub fn layout_sqrt<T>(inner: &T, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
 where T : Layoutable
 {
-    // Lay out the inner item first
     let mut path = if let Some(p) = path {
         if p.next() == 0 {
             Some(p.step())
@@ -19,10 +18,65 @@ where T : Layoutable
     } else {
         None
     };
-    
-    let inner_layout = inner.layout(renderer, (&mut path).as_mut(), properties);
+
+    layout_radical(inner, renderer, path.as_mut(), properties).in_slot(0)
+}
+
+pub fn layout_root<T>(degree: &T, radicand: &T, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
+where T : Layoutable
+{
+    let (mut degree_path, mut radicand_path) = {
+        if let Some(p) = path {
+            if p.next() == 0 {
+                (Some(p.step()), None)
+            } else if p.next() == 1 {
+                (None, Some(p.step()))
+            } else {
+                panic!()
+            }
+        } else {
+            (None, None)
+        }
+    };
+
+    let radicand_layout = layout_radical(radicand, renderer, radicand_path.as_mut(), properties).in_slot(1);
+    let degree_layout = degree.layout(
+        renderer,
+        degree_path.as_mut(),
+        properties.reduce_size(),
+    ).in_slot(0);
+
+    // Like a power, we can't offset the degree upwards, so instead move the radical down by the
+    // height of the degree. This leaves the degree sitting above the left of the radical symbol
+    radicand_layout
+        .offset(0, degree_layout.area.height)
+        .merge_in_place(&degree_layout, MergeBaseline::SelfAsBaseline)
+}
+
+/// Lays out a radical symbol around an inner item. Used to implement both square roots and roots
+/// of other degrees, which differ only in how the path is stepped into the inner item.
+fn layout_radical<T>(inner: &T, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
+where T : Layoutable
+{
+    // Lay out the inner item first
+    let inner_layout = inner.layout(renderer, path, properties);
     let inner_area = inner_layout.area;
 
+    if renderer.decompose_radicals() {
+        // Put the overline above the inner item, and the tick to the left of both
+        let overline_layout = LayoutBlock::from_glyph(renderer, Glyph::RadicalOverline {
+            inner_width: inner_area.width + renderer.square_root_padding(),
+        }, properties);
+        let tick_layout = LayoutBlock::from_glyph(renderer, Glyph::RadicalTick {
+            inner_height: inner_area.height + overline_layout.area.height,
+        }, properties);
+
+        let inner_layout = inner_layout.offset(tick_layout.area.width, overline_layout.area.height);
+        return tick_layout
+            .merge_in_place(&overline_layout.move_right_of_other(&tick_layout), MergeBaseline::SelfAsBaseline)
+            .merge_in_place(&inner_layout, MergeBaseline::OtherAsBaseline)
+    }
+
     // Get glyph size for the sqrt symbol
     let sqrt_symbol_layout = LayoutBlock::from_glyph(renderer, Glyph::Sqrt {
         inner_area
@@ -61,12 +115,12 @@ where T : Layoutable
         renderer,
         (&mut top_path).as_mut(),
         properties,
-    );
+    ).in_slot(0);
     let bottom_layout = bottom.layout(
         renderer,
         (&mut bottom_path).as_mut(),
         properties,
-    );
+    ).in_slot(1);
 
     // The fraction line should be the widest of the two
     let line_width = max(
@@ -99,20 +153,26 @@ where T : Layoutable
         None
     };
     
-    let inner_layout = inner.layout(renderer, (&mut path).as_mut(), properties);
-    let inner_area = inner_layout.area;
+    let inner_layout = inner.layout(renderer, (&mut path).as_mut(), properties).in_slot(0);
+    parenthesise_layout(inner_layout, renderer, properties)
+}
+
+/// Places parentheses either side of an existing layout.
+fn parenthesise_layout(inner_layout: LayoutBlock, renderer: &mut impl Renderer, properties: LayoutComputationProperties) -> LayoutBlock {
+    let inner_height = inner_layout.area.height;
+    let inner_baseline = inner_layout.baseline;
 
     // Get glyphs for parentheses
     let mut left_paren_layout = LayoutBlock::from_glyph(renderer, Glyph::LeftParenthesis {
-        inner_height: inner_area.height,
+        inner_height, inner_baseline,
     }, properties);
     let mut right_paren_layout = LayoutBlock::from_glyph(renderer, Glyph::RightParenthesis {
-        inner_height: inner_area.height,
+        inner_height, inner_baseline,
     }, properties);
 
     // Match the baselines for these glyphs with the inner baseline
-    left_paren_layout.baseline = inner_layout.baseline;
-    right_paren_layout.baseline = inner_layout.baseline;
+    left_paren_layout.baseline = inner_baseline;
+    right_paren_layout.baseline = inner_baseline;
 
     // Merge the three
     LayoutBlock::layout_horizontal(&[
@@ -138,16 +198,7 @@ where T : Layoutable
             None
         };
         
-        let mut exp_layout = exp.layout(
-            renderer, (&mut path).as_mut(),
-            properties.reduce_size(),
-        );
-
-        // Ask this to be rendered as superscript
-        exp_layout.special.baseline_merge_with_high_precedence = true;
-        exp_layout.special.superscript = true;
-
-        return exp_layout
+        return layout_exponent(exp, renderer, path.as_mut(), properties).in_slot(0)
     }    
 
     // Lay out base and exponent
@@ -158,10 +209,16 @@ where T : Layoutable
         None,
         properties,
     );
+    if properties.is_linear_power() {
+        return LayoutBlock::layout_horizontal(&[
+            base_layout,
+            layout_linear_exponent(exp, renderer, None, properties),
+        ])
+    }
     let exp_layout = exp.layout(
         renderer,
         None,
-        properties.reduce_size(),
+        properties.reduce_size().enter_superscript(),
     );
 
     // We're going to merge with `merge_in_place`, and want this:
@@ -194,9 +251,67 @@ where T : Layoutable
     base_layout.merge_in_place(&exp_layout, MergeBaseline::SelfAsBaseline)
 }
 
-pub fn layout_function_call<T>(func: Function, args: &[T], renderer: &mut impl Renderer, mut path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
+/// Lays out an exponent on its own, either as a superscript which will be merged with the base to
+/// its left by [LayoutBlock::layout_horizontal], or inline if the power is nested too deeply.
+fn layout_exponent<T>(exp: &T, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
 where T : Layoutable
 {
+    if properties.is_linear_power() {
+        return layout_linear_exponent(exp, renderer, path, properties)
+    }
+
+    let mut exp_layout = exp.layout(
+        renderer, path,
+        properties.reduce_size().enter_superscript(),
+    );
+
+    // Ask this to be rendered as superscript
+    exp_layout.special.baseline_merge_with_high_precedence = true;
+    exp_layout.special.superscript = true;
+
+    exp_layout
+}
+
+/// Lays out the exponent of a power inline, after a caret, for powers nested beyond
+/// [max_superscript_depth](LayoutComputationProperties::max_superscript_depth). The exponent is
+/// parenthesised unless it is a single glyph, so that `2^(3^4)` isn't confused with `2^3^4`.
+fn layout_linear_exponent<T>(exp: &T, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
+where T : Layoutable
+{
+    let caret_layout = LayoutBlock::from_glyph(renderer, Glyph::Caret, properties);
+
+    // Deeper exponents keep the same size, and are laid out inline too
+    let exp_properties = properties.enter_superscript();
+    let exp_layout = exp.layout(renderer, path, exp_properties);
+
+    let glyph_count = exp_layout.glyphs.iter()
+        .filter(|(g, _)| !matches!(g.glyph, Glyph::Cursor { .. }))
+        .count();
+    if glyph_count == 1 {
+        return LayoutBlock::layout_horizontal(&[caret_layout, exp_layout])
+    }
+
+    LayoutBlock::layout_horizontal(&[
+        caret_layout,
+        parenthesise_layout(exp_layout, renderer, exp_properties),
+    ])
+}
+
+/// Lays out a function call. `simple_argument` should be true if there is a single argument which
+/// can be written without parentheses, in which case they are left out if
+/// [elide_function_parentheses](LayoutComputationProperties::elide_function_parentheses) is set.
+pub fn layout_function_call<T>(func: Function, args: &[T], simple_argument: bool, renderer: &mut impl Renderer, mut path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
+where T : Layoutable
+{
+    if func.is_written_as_power() {
+        let mut path = if let Some(p) = path {
+            if p.next() == 0 { Some(p.step()) } else { None }
+        } else {
+            None
+        };
+        return layout_power_function(func, &args[0], simple_argument, renderer, path.as_mut(), properties).in_slot(0)
+    }
+
     // Compute layouts for each function argument, interspersing commas
     let mut is_first_arg = true;
     let mut arg_layouts = vec![];
@@ -216,33 +331,101 @@ where T : Layoutable
         }
         is_first_arg = false;
 
-        arg_layouts.push(arg.layout(renderer, (&mut path).as_mut(), properties));
+        arg_layouts.push(arg.layout(renderer, (&mut path).as_mut(), properties).in_slot(i));
     }
 
     // Join argument layouts (and commas)
     let joined_arg_layout = LayoutBlock::layout_horizontal(&arg_layouts);
 
     // Compute layout for function name
-    let func_glyph = Glyph::FunctionName { function: func };
-    let func_layout = LayoutBlock::from_glyph(renderer, func_glyph, properties);
+    let parenthesised = !(simple_argument && properties.elide_function_parentheses);
+    let name = match func.symbol() {
+        Some(symbol) if properties.boolean_symbols => symbol,
+        _ => properties.function_names.name(func),
+    };
+    let func_glyph = Glyph::FunctionName { function: func, name, parenthesised };
+    let mut func_layout = LayoutBlock::from_glyph(renderer, func_glyph, properties);
 
-    // Compute layouts for parentheses
-    let mut left_paren_layout = LayoutBlock::from_glyph(renderer, Glyph::LeftParenthesis {
-        inner_height: joined_arg_layout.area.height,
-    }, properties);
-    let mut right_paren_layout = LayoutBlock::from_glyph(renderer, Glyph::RightParenthesis {
-        inner_height: joined_arg_layout.area.height,
-    }, properties);
+    // Arguments are merged along their baselines, which the name sits on by default. To centre the
+    // name instead, work out where its top should be relative to the top of the arguments, and set
+    // its baseline to match. If that's below the baseline, the name needs moving down instead,
+    // since baselines can't be negative
+    if properties.centre_function_names {
+        let top = joined_arg_layout.area.height.saturating_sub(func_layout.area.height) / 2;
+        let baseline = joined_arg_layout.baseline;
+        func_layout = func_layout.offset(0, top.saturating_sub(baseline));
+        func_layout.baseline = baseline.saturating_sub(top);
+    }
 
-    // Match the baselines for these glyphs with the inner baseline
-    left_paren_layout.baseline = joined_arg_layout.baseline;
-    right_paren_layout.baseline = joined_arg_layout.baseline;
+    if !parenthesised {
+        return LayoutBlock::layout_horizontal(&[func_layout, joined_arg_layout])
+    }
 
-    // Merge everything together
     LayoutBlock::layout_horizontal(&[
         func_layout,
-        left_paren_layout,
-        joined_arg_layout,
-        right_paren_layout,
+        parenthesise_layout(joined_arg_layout, renderer, properties),
     ])
 }
+
+/// Lays out a call to a function which is [written as a power](Function::is_written_as_power),
+/// such as `x⁻¹` for the reciprocal of x. `simple_argument` is as for [layout_function_call].
+fn layout_power_function<T>(func: Function, arg: &T, simple_argument: bool, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock
+where T : Layoutable
+{
+    // The fixed part of the power is built from tokens, so that it can be laid out like any other
+    // exponent or base. It isn't part of the tree, so its nodes aren't recorded
+    let fixed = |tokens: &[Token]| UnstructuredNodeList {
+        items: tokens.iter().map(|t| UnstructuredNode::Token(*t)).collect(),
+    };
+    let without_nodes = |layout: LayoutBlock| LayoutBlock { node_bounds: vec![], ..layout };
+
+    let (base_layout, exp_layout) = match func {
+        Function::Reciprocal => {
+            let mut base_layout = arg.layout(renderer, path, properties);
+            if !simple_argument {
+                base_layout = parenthesise_layout(base_layout, renderer, properties);
+            }
+            let exp = fixed(&[Token::Subtract, Token::Digit(1)]);
+            (base_layout, without_nodes(layout_exponent(&exp, renderer, None, properties)))
+        },
+        Function::PowerOfTen | Function::Exp => {
+            let base = if func == Function::PowerOfTen {
+                fixed(&[Token::Digit(1), Token::Digit(0)])
+            } else {
+                fixed(&[Token::Variable('e')])
+            };
+            (without_nodes(base.layout(renderer, None, properties)), layout_exponent(arg, renderer, path, properties))
+        },
+        _ => unreachable!(),
+    };
+
+    LayoutBlock::layout_horizontal(&[base_layout, exp_layout])
+}
+
+/// Lays out a conditional as a piecewise brace, with the `then` case and its condition on the
+/// first line, and the `otherwise` case on the second.
+pub fn layout_piecewise<T>(condition: &T, then: &T, otherwise: &T, renderer: &mut impl Renderer, properties: LayoutComputationProperties) -> LayoutBlock
+where T : Layoutable
+{
+    let if_text = properties.function_names.name(Function::If);
+    let then_row = LayoutBlock::layout_horizontal(&[
+        then.layout(renderer, None, properties),
+        LayoutBlock::from_glyph(renderer, Glyph::Text { text: if_text }, properties),
+        condition.layout(renderer, None, properties),
+    ]);
+    let otherwise_row = LayoutBlock::layout_horizontal(&[
+        otherwise.layout(renderer, None, properties),
+        LayoutBlock::from_glyph(renderer, Glyph::Text { text: "otherwise" }, properties),
+    ]).move_below_other(&then_row);
+
+    // Stack the rows, and put the baseline between them
+    let mut rows = then_row.merge_in_place(&otherwise_row, MergeBaseline::SelfAsBaseline);
+    rows.baseline = rows.area.height / 2;
+
+    let mut brace_layout = LayoutBlock::from_glyph(renderer, Glyph::LeftBrace {
+        inner_height: rows.area.height,
+    }, properties);
+    brace_layout.baseline = rows.baseline;
+
+    LayoutBlock::layout_horizontal(&[brace_layout, rows])
+}
@@ -56,6 +56,7 @@ impl CompiledNode {
                     Self::new(|_| Err(MathsError::MissingVariable), param_var)
                 }
             },
+            StructuredNode::Pi => Self::new(|_| Ok(Number::pi()), param_var),
             StructuredNode::Sqrt(inner) => {
                 let inner = Self::from_structured(*inner, param_var, evaluation_settings);
                 Self::new(
@@ -66,7 +67,14 @@ impl CompiledNode {
             StructuredNode::Power(base, exp) => {
                 let base = Self::from_structured(*base, param_var, evaluation_settings);
                 let exp = Self::from_structured(*exp, param_var, evaluation_settings);
-                Self::new(move |n| (base.func)(n)?.checked_pow((exp.func)(n)?), param_var)
+                let settings = evaluation_settings.clone();
+                Self::new(move |n| settings.pow((base.func)(n)?, (exp.func)(n)?), param_var)
+            },
+            StructuredNode::Root(degree, radicand) => {
+                let degree = Self::from_structured(*degree, param_var, evaluation_settings);
+                let radicand = Self::from_structured(*radicand, param_var, evaluation_settings);
+                let settings = evaluation_settings.clone();
+                Self::new(move |n| settings.root((degree.func)(n)?, (radicand.func)(n)?), param_var)
             },
             StructuredNode::Add(left, right) => {
                 let left = Self::from_structured(*left, param_var, evaluation_settings);
@@ -86,17 +94,31 @@ impl CompiledNode {
             StructuredNode::Divide(left, right) => {
                 let left = Self::from_structured(*left, param_var, evaluation_settings);
                 let right = Self::from_structured(*right, param_var, evaluation_settings);
-                Self::new(move |n| (left.func)(n)?.checked_div((right.func)(n)?), param_var)
+                let settings = evaluation_settings.clone();
+                Self::new(move |n| settings.div((left.func)(n)?, (right.func)(n)?), param_var)
             }
             StructuredNode::Parentheses(inner) => Self::from_structured(*inner, param_var, evaluation_settings),
             StructuredNode::FunctionCall(func, args) => {
                 let arg_funcs = args.into_iter().map(|arg| Self::from_structured(arg, param_var, evaluation_settings)).collect::<Vec<_>>();
                 let settings_clone = evaluation_settings.clone();
                 Self::new(move |n| {
-                    let evaluated_args = arg_funcs.iter().map(|cn| (cn.func)(n)).collect::<Result<Vec<_>, _>>()?;
+                    let mut evaluated_args = Vec::with_capacity(arg_funcs.len());
+                    for cn in &arg_funcs {
+                        let value = (cn.func)(n)?;
+                        if let Some(result) = func.short_circuit(&value) {
+                            return Ok(result)
+                        }
+                        evaluated_args.push(value);
+                    }
                     func.evaluate(&evaluated_args, &settings_clone)
                 }, param_var)
             }
+            StructuredNode::Conditional(condition, then, otherwise) => {
+                let condition = Self::from_structured(*condition, param_var, evaluation_settings);
+                let then = Self::from_structured(*then, param_var, evaluation_settings);
+                let otherwise = Self::from_structured(*otherwise, param_var, evaluation_settings);
+                Self::new(move |n| if (condition.func)(n)?.to_bool() { (then.func)(n) } else { (otherwise.func)(n) }, param_var)
+            }
         }
     }
 
@@ -1,22 +1,57 @@
 //! Mathematical functions which can be used by all kinds of node tree, and called during
 //! evaluation.
 
-use alloc::{vec::Vec, vec};
+use alloc::vec::Vec;
 use num_integer::Integer;
-use num_traits::{ToPrimitive, FromPrimitive};
+use num_traits::{ToPrimitive, FromPrimitive, Zero};
 use rust_decimal::{MathematicalOps, Decimal};
 
-use crate::{Number, error::MathsError, number::DecimalAccuracy, serialize::Serializable};
+use crate::{Number, error::{MathsError, DeserializeError}, number::DecimalAccuracy, serialize::{Serializable, ByteRead}};
 
 use super::{structured::{EvaluationSettings, AngleUnit}};
 
 /// A mathematical function, for which an invocation may appear in an unstructured or structured
 /// node tree.
-#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
 pub enum Function {
     Sine,
     Cosine,
     GreatestCommonDenominator,
+    ArcSine,
+    ArcCosine,
+    ArcTangent,
+    Tangent,
+    NaturalLogarithm,
+
+    /// Logical AND. Like the other logical functions, this treats any non-zero number as true, and
+    /// returns 1 for true or 0 for false.
+    And,
+    Or,
+    Not,
+    Xor,
+
+    /// A conditional, returning the second argument if the first is true, or the third otherwise.
+    /// This is upgraded to a [StructuredNode::Conditional](crate::StructuredNode::Conditional), so
+    /// that only the chosen argument is evaluated.
+    If,
+
+    /// The reciprocal of the argument, written as a power of -1, like `x⁻¹`.
+    Reciprocal,
+
+    /// Ten raised to the power of the argument, written as `10ˣ`.
+    PowerOfTen,
+
+    /// e raised to the power of the argument, written as `eˣ`.
+    Exp,
+
+    /// The percentage change from the first argument to the second, for example 25 from 40 to 50.
+    /// The change is relative to the size of the first argument, so -40 to -50 is -25.
+    PercentChange,
+
+    /// The ratio of the first argument to the second. As a number, this is the first argument
+    /// divided by the second. Use [StructuredNode::evaluate_ratio](crate::StructuredNode::evaluate_ratio)
+    /// to get the ratio in its simplest form, like 3:2.
+    Ratio,
 }
 
 impl Function {
@@ -27,14 +62,64 @@ impl Function {
             Self::Sine => "sin",
             Self::Cosine => "cos",
             Self::GreatestCommonDenominator => "gcd",
+            Self::ArcSine => "asin",
+            Self::ArcCosine => "acos",
+            Self::ArcTangent => "atan",
+            Self::Tangent => "tan",
+            Self::NaturalLogarithm => "ln",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Not => "not",
+            Self::Xor => "xor",
+            Self::If => "if",
+            Self::Reciprocal => "recip",
+            Self::PowerOfTen => "pow10",
+            Self::Exp => "exp",
+            Self::PercentChange => "%ch",
+            Self::Ratio => "ratio",
         }
     }
 
+    /// The symbol which can be displayed for this function instead of its name, if it has one.
+    /// This is used for logical functions when
+    /// [boolean_symbols](crate::render::LayoutComputationProperties::boolean_symbols) is set.
+    pub fn symbol(&self) -> Option<&'static str> {
+        match self {
+            Self::And => Some("∧"),
+            Self::Or => Some("∨"),
+            Self::Not => Some("¬"),
+            Self::Xor => Some("⊕"),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this function is written as a power, rather than as its name followed by
+    /// its arguments. Its [render_name](Function::render_name) isn't displayed.
+    pub fn is_written_as_power(&self) -> bool {
+        matches!(self, Self::Reciprocal | Self::PowerOfTen | Self::Exp)
+    }
+
     /// The number of arguments for this function.
     pub fn argument_count(&self) -> usize {
         match self {
-            Self::Sine | Self::Cosine => 1,
-            Self::GreatestCommonDenominator => 2,
+            Self::Sine | Self::Cosine | Self::Tangent | Self::ArcSine | Self::ArcCosine | Self::ArcTangent
+                | Self::NaturalLogarithm | Self::Not | Self::Reciprocal | Self::PowerOfTen | Self::Exp => 1,
+            Self::GreatestCommonDenominator | Self::And | Self::Or | Self::Xor | Self::PercentChange
+                | Self::Ratio => 2,
+            Self::If => 3,
+        }
+    }
+
+    /// If the result of this function is already known from the value of one of its arguments,
+    /// returns that result. For example, `and` is false as soon as any argument is false.
+    ///
+    /// Evaluators use this to skip evaluating the remaining arguments, so that an error in an
+    /// argument which doesn't affect the result isn't reported.
+    pub fn short_circuit(&self, argument: &Number) -> Option<Number> {
+        match self {
+            Self::And if !argument.to_bool() => Some(false.into()),
+            Self::Or if argument.to_bool() => Some(true.into()),
+            _ => None,
         }
     }
 
@@ -47,30 +132,113 @@ impl Function {
             panic!("rbop function {:?} expected {} arguments, but got {}", self, self.argument_count(), arguments.len());
         }
 
+        let domain_error = || MathsError::DomainError { function: *self, argument: arguments[0] };
+
         match self {
-            Self::Sine | Self::Cosine => {
-                // rust_decimal only lets us sine or cosine by interpreting the input as radians, so
-                // do a conversion ourselves first if need be
+            Self::Sine | Self::Cosine | Self::Tangent => {
+                // Tangent is undefined at odd multiples of 90°. These can't be represented exactly
+                // once converted to radians, so check for them beforehand
+                if *self == Self::Tangent {
+                    let undefined = match settings.angle_unit {
+                        AngleUnit::Degree => (arguments[0].to_decimal() - Decimal::from(90)) % Decimal::from(180) == Decimal::ZERO,
+                        AngleUnit::Radian => matches!(arguments[0].to_pi_multiple(), Some(Number::Rational(_, 2))),
+                    };
+                    if undefined {
+                        return Err(domain_error())
+                    }
+                }
+
+                // rust_decimal only lets us use trigonometric functions by interpreting the input
+                // as radians, so do a conversion ourselves first if need be
                 let mut target = arguments[0].to_decimal();
                 if settings.angle_unit == AngleUnit::Degree {
                     target *= Decimal::PI / Decimal::from(180)
                 }
 
+                // rust_decimal calculates the sine or cosine of an angle of π/4 or more (once
+                // reduced into the range 0 to π) using the other function of π/2 minus the angle,
+                // which recurses forever for exactly π/4. Nudge the angle towards zero to avoid this
+                let reduced = target.abs().checked_rem(Decimal::TWO_PI).ok_or(MathsError::Overflow)?;
+                if reduced == Decimal::QUARTER_PI || reduced - Decimal::PI == Decimal::QUARTER_PI {
+                    let nudge = Decimal::new(1, 26);
+                    if target.is_sign_negative() { target += nudge } else { target -= nudge }
+                }
+
                 if settings.use_floats && let Some(float) = target.to_f32() {
                     Ok(Number::Decimal(Decimal::from_f32(match self {
                         Self::Sine => libm::sinf(float),
                         Self::Cosine => libm::cosf(float),
+                        Self::Tangent => libm::tanf(float),
                         _ => unreachable!()
-                    }).unwrap(), DecimalAccuracy::Approximation))
+                    }).ok_or(MathsError::Overflow)?, DecimalAccuracy::Approximation))
                 } else {
                     Ok(Number::Decimal(match self {
                         Self::Sine => target.sin(),
                         Self::Cosine => target.cos(),
+                        Self::Tangent => target.checked_tan().ok_or_else(domain_error)?,
                         _ => unreachable!()
                     }, DecimalAccuracy::Approximation))
                 }
             },
 
+            Self::ArcSine | Self::ArcCosine | Self::ArcTangent => {
+                // rust_decimal doesn't implement inverse trigonometric functions, so use floats
+                // regardless of settings
+                let target = arguments[0].to_decimal().to_f64().ok_or(MathsError::Overflow)?;
+                if *self != Self::ArcTangent && !(-1.0..=1.0).contains(&target) {
+                    return Err(domain_error())
+                }
+
+                let mut result = Decimal::from_f64(match self {
+                    Self::ArcSine => libm::asin(target),
+                    Self::ArcCosine => libm::acos(target),
+                    Self::ArcTangent => libm::atan(target),
+                    _ => unreachable!()
+                }).ok_or(MathsError::Overflow)?;
+
+                // Convert result back from radians if need be
+                if settings.angle_unit == AngleUnit::Degree {
+                    result *= Decimal::from(180) / Decimal::PI
+                }
+
+                Ok(Number::Decimal(result, DecimalAccuracy::Approximation))
+            },
+
+            Self::NaturalLogarithm => {
+                let target = arguments[0].to_decimal();
+                if target <= Decimal::ZERO {
+                    return Err(domain_error())
+                }
+
+                let result = match target.to_f32() {
+                    Some(float) if settings.use_floats => Decimal::from_f32(libm::logf(float)),
+                    _ => target.checked_ln(),
+                };
+                Ok(Number::Decimal(result.ok_or(MathsError::Overflow)?, DecimalAccuracy::Approximation))
+            },
+
+            Self::Reciprocal => Number::from(1).checked_div(arguments[0]),
+            Self::PowerOfTen => settings.pow(Number::from(10), arguments[0]),
+
+            Self::Exp => {
+                // e⁰ is the only exact result
+                if arguments[0].is_zero() {
+                    return Ok(Number::from(1))
+                }
+
+                let target = arguments[0].to_decimal();
+                let result = match target.to_f32() {
+                    Some(float) if settings.use_floats => Decimal::from_f32(libm::expf(float)),
+                    _ => target.checked_exp(),
+                };
+                Ok(Number::Decimal(result.ok_or(MathsError::Overflow)?, DecimalAccuracy::Approximation))
+            },
+
+            Self::PercentChange => arguments[1].checked_sub(arguments[0])?
+                .checked_div(arguments[0].abs())?
+                .checked_mul(Number::from(100)),
+            Self::Ratio => settings.div(arguments[0], arguments[1]),
+
             Self::GreatestCommonDenominator => {
                 // This is an integer operation, so convert both numbers to integers - if we can't,
                 // just return 1
@@ -83,26 +251,88 @@ impl Function {
 
                 Ok(int_a.gcd(&int_b).into())
             }
+
+            Self::And => Ok((arguments[0].to_bool() && arguments[1].to_bool()).into()),
+            Self::Or => Ok((arguments[0].to_bool() || arguments[1].to_bool()).into()),
+            Self::Not => Ok((!arguments[0].to_bool()).into()),
+            Self::Xor => Ok((arguments[0].to_bool() != arguments[1].to_bool()).into()),
+            Self::If => Ok(if arguments[0].to_bool() { arguments[1] } else { arguments[2] }),
         }
     }
 }
 
+/// A table of names to display for functions instead of their
+/// [render_name](Function::render_name), for example to use localised names like "sen" for sine.
+/// Functions which aren't in the table use their usual name.
+/// 
+/// This is used by layout through [LayoutComputationProperties](crate::render::LayoutComputationProperties),
+/// and only affects how functions are displayed - not how they are serialized.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct FunctionNameTable {
+    pub names: &'static [(Function, &'static str)],
+}
+
+impl FunctionNameTable {
+    /// A table with no overridden names.
+    pub const DEFAULT: FunctionNameTable = FunctionNameTable { names: &[] };
+
+    /// Returns the name to display for a function.
+    pub fn name(&self, function: Function) -> &'static str {
+        self.names.iter()
+            .find(|(f, _)| *f == function)
+            .map(|(_, name)| *name)
+            .unwrap_or_else(|| function.render_name())
+    }
+}
+
 impl Serializable for Function {
-    fn serialize(&self) -> Vec<u8> {
-        vec![match self {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self {
             Function::Sine => 1,
             Function::Cosine => 2,
             Function::GreatestCommonDenominator => 3,
-        }]
+            Function::ArcSine => 4,
+            Function::ArcCosine => 5,
+            Function::ArcTangent => 6,
+            Function::Tangent => 7,
+            Function::NaturalLogarithm => 8,
+            Function::And => 9,
+            Function::Or => 10,
+            Function::Not => 11,
+            Function::Xor => 12,
+            Function::If => 13,
+            Function::Reciprocal => 14,
+            Function::PowerOfTen => 15,
+            Function::Exp => 16,
+            Function::PercentChange => 17,
+            Function::Ratio => 18,
+        })
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        match bytes.next() {
-            Some(1) => Some(Function::Sine),
-            Some(2) => Some(Function::Cosine),
-            Some(3) => Some(Function::GreatestCommonDenominator),
+    fn serialized_size_hint(&self) -> usize { 1 }
 
-            _ => None,
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        match reader.read_u8()? {
+            1 => Ok(Function::Sine),
+            2 => Ok(Function::Cosine),
+            3 => Ok(Function::GreatestCommonDenominator),
+            4 => Ok(Function::ArcSine),
+            5 => Ok(Function::ArcCosine),
+            6 => Ok(Function::ArcTangent),
+            7 => Ok(Function::Tangent),
+            8 => Ok(Function::NaturalLogarithm),
+            9 => Ok(Function::And),
+            10 => Ok(Function::Or),
+            11 => Ok(Function::Not),
+            12 => Ok(Function::Xor),
+            13 => Ok(Function::If),
+            14 => Ok(Function::Reciprocal),
+            15 => Ok(Function::PowerOfTen),
+            16 => Ok(Function::Exp),
+            17 => Ok(Function::PercentChange),
+            18 => Ok(Function::Ratio),
+
+            tag => Err(DeserializeError::InvalidTag(tag)),
         }
     }
 }
@@ -13,5 +13,6 @@ pub mod structured;
 pub mod simplified;
 pub mod function;
 pub mod compiled;
+pub mod assumptions;
 mod parser;
 mod common;
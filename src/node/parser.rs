@@ -1,10 +1,10 @@
 use alloc::{string::ToString, vec::Vec, boxed::Box};
-use num_traits::Zero;
+use num_traits::{One, Zero};
 use rust_decimal::{Decimal, prelude::{FromPrimitive, ToPrimitive}, MathematicalOps};
 
 use crate::{Number, error::NodeError, number::DecimalAccuracy};
 
-use super::{structured::StructuredNode, unstructured::{Token, UnstructuredNode, Upgradable}};
+use super::{structured::StructuredNode, unstructured::{Token, UnstructuredNode, Upgradable}, function::Function};
 
 /// Converts a list of unstructured nodes into a single structured node. Used to implement
 /// `Upgradable` for `UnstructuredNodeList`.
@@ -191,35 +191,67 @@ impl<'a> Parser<'a> {
                 }
             }
 
-            self.accepts_power(StructuredNode::Number(
-                if is_decimal {
-                    Number::Decimal(number, DecimalAccuracy::Exact)
+            let mut number = if is_decimal {
+                Number::Decimal(number, DecimalAccuracy::Exact)
+            } else {
+                // Handle case where number doesn't fit in i64
+                if let Some(numerator) = number.to_i64() {
+                    Number::Rational(numerator, 1)
                 } else {
-                    // Handle case where number doesn't fit in i64
-                    if let Some(numerator) = number.to_i64() {
-                        Number::Rational(numerator, 1)
-                    } else {
-                        Number::Decimal(number, DecimalAccuracy::Exact)
-                    }
+                    Number::Decimal(number, DecimalAccuracy::Exact)
                 }
-            ))?
+            };
+
+            // An engineering suffix scales the number, so "4.7k" is 4700
+            if let Some(Token::EngineeringSuffix(suffix)) = self.current_token() {
+                self.advance();
+                number = number.checked_mul(suffix.multiplier()).map_err(|_| NodeError::Overflow)?;
+            }
+
+            // So does a power of ten, so "6÷1.5×10³" divides by 1500 rather than by 1.5
+            if let Some(UnstructuredNode::PowerOfTen(exp)) = self.current() {
+                self.advance();
+                self.accepts_power(Self::scale_by_power_of_ten(number, exp.upgrade()?))?
+            } else {
+                self.accepts_power(StructuredNode::Number(number))?
+            }
         } else if let Some(UnstructuredNode::Fraction(a, b)) = self.current() {
             self.advance();
             self.accepts_power(StructuredNode::Divide(Box::new(a.upgrade()?), Box::new(b.upgrade()?)))?
         } else if let Some(UnstructuredNode::Sqrt(n)) = self.current() {
             self.advance();
             self.accepts_power(StructuredNode::Sqrt(Box::new(n.upgrade()?)))?
+        } else if let Some(UnstructuredNode::Root(d, r)) = self.current() {
+            self.advance();
+            self.accepts_power(StructuredNode::Root(Box::new(d.upgrade()?), Box::new(r.upgrade()?)))?
         } else if let Some(UnstructuredNode::Parentheses(inner)) = self.current() {
             self.advance();
             self.accepts_power(StructuredNode::Parentheses(Box::new(inner.upgrade()?)))?
         } else if let Some(UnstructuredNode::Power(_)) = self.current() {
             return Err(NodeError::PowerMissingBase)
+        } else if let Some(UnstructuredNode::PowerOfTen(exp)) = self.current() {
+            self.advance();
+            self.accepts_power(Self::scale_by_power_of_ten(Number::one(), exp.upgrade()?))?
         } else if let Some(Token::Variable(v)) = self.current_token() {
             self.advance();
             self.accepts_power(StructuredNode::Variable(v))?
+        } else if let Some(Token::Pi) = self.current_token() {
+            self.advance();
+            self.accepts_power(StructuredNode::Pi)?
+        } else if let Some(Token::Boolean(b)) = self.current_token() {
+            self.advance();
+            StructuredNode::Number(b.into())
         } else if let Some(UnstructuredNode::FunctionCall(func, args)) = self.current() {
             self.advance();
-            self.accepts_power(StructuredNode::FunctionCall(*func, args.iter().map(|n| n.upgrade()).collect::<Result<Vec<_>, _>>()?))?
+            let mut args = args.iter().map(|n| n.upgrade()).collect::<Result<Vec<_>, _>>()?;
+            if *func == Function::If && args.len() == 3 {
+                let otherwise = args.pop().unwrap();
+                let then = args.pop().unwrap();
+                let condition = args.pop().unwrap();
+                self.accepts_power(StructuredNode::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))?
+            } else {
+                self.accepts_power(StructuredNode::FunctionCall(*func, args))?
+            }
         } else {
             return Err(NodeError::ExpectedUnit)
         };
@@ -240,8 +272,10 @@ impl<'a> Parser<'a> {
             Some(
                 UnstructuredNode::Fraction(_, _)
                 | UnstructuredNode::Sqrt(_)
+                | UnstructuredNode::Root(_, _)
                 | UnstructuredNode::Parentheses(_)
-                | UnstructuredNode::Token(Token::Variable(_) | Token::Digit(_))
+                | UnstructuredNode::PowerOfTen(_)
+                | UnstructuredNode::Token(Token::Variable(_) | Token::Digit(_) | Token::Pi)
             )
         ) {
             result = StructuredNode::Multiply(Box::new(result), Box::new(self.parse_level3()?));
@@ -249,4 +283,25 @@ impl<'a> Parser<'a> {
 
         Ok(result)
     }
+
+    /// Scales a number literal by a power of ten, for a [PowerOfTen](UnstructuredNode::PowerOfTen).
+    /// Whole exponents are folded into the number when the result stays exact, and anything else
+    /// is left as a multiplication for evaluation to deal with.
+    fn scale_by_power_of_ten(number: Number, exponent: StructuredNode) -> StructuredNode {
+        if let StructuredNode::Number(exp @ Number::Rational(_, 1)) = exponent {
+            if let Ok(power @ Number::Rational(_, _)) = Number::Rational(10, 1).checked_pow(exp) {
+                if let Ok(scaled) = number.checked_mul(power) {
+                    return StructuredNode::Number(scaled)
+                }
+            }
+        }
+
+        StructuredNode::Multiply(
+            Box::new(StructuredNode::Number(number)),
+            Box::new(StructuredNode::Power(
+                Box::new(StructuredNode::Number(Number::Rational(10, 1))),
+                Box::new(exponent),
+            )),
+        )
+    }
 }
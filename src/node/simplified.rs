@@ -17,9 +17,9 @@ use alloc::{boxed::Box, vec, vec::Vec};
 use num_traits::{One, Zero};
 use rust_decimal::MathematicalOps;
 
-use crate::{Number, error::MathsError, number::DecimalAccuracy};
+use crate::{Number, error::MathsError, number::{DecimalAccuracy, ZeroPowerZero}};
 
-use super::function::Function;
+use super::{function::Function, assumptions::Assumptions};
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 /// A simplified variant of `StructuredNode`. By "simplified", we mean fewer possible variants which
@@ -139,7 +139,28 @@ impl SimplifiedNode {
     /// Returns a `ReductionResult` encapsulating:
     ///   - Whether any reduction took place
     ///   - If an error occured during reduction
+    /// 
+    /// No assumptions are made about variables, so identities which only hold for some values are
+    /// not applied. Use [reduce_with_assumptions](SimplifiedNode::reduce_with_assumptions) to
+    /// provide assumptions, or [reduce_with_settings](SimplifiedNode::reduce_with_settings) for
+    /// further control.
     pub fn reduce(&mut self) -> ReductionResult {
+        self.reduce_with_settings(&ReductionSettings::default())
+    }
+
+    /// Performs a mathematical reduction on this node tree like [reduce](SimplifiedNode::reduce),
+    /// additionally using a set of assumptions about variables. For example, if x is assumed to be
+    /// non-negative, then √(x²) can be reduced to x.
+    pub fn reduce_with_assumptions(&mut self, assumptions: &Assumptions) -> ReductionResult {
+        self.reduce_with_settings(&ReductionSettings {
+            assumptions: assumptions.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Performs a mathematical reduction on this node tree like [reduce](SimplifiedNode::reduce),
+    /// using the given settings.
+    pub fn reduce_with_settings(&mut self, settings: &ReductionSettings) -> ReductionResult {
         use ReductionStatus::*;
 
         let mut status = NoReduction;
@@ -158,8 +179,8 @@ impl SimplifiedNode {
 
             Self::Power(b, e) => {
                 // Reduce the base and exponent first
-                b.reduce()?;
-                e.reduce()?;
+                b.reduce_with_settings(settings)?;
+                e.reduce_with_settings(settings)?;
 
                 // Is the power a rational number, with a non-one numerator and denominator?
                 //   e.g. 3/2, but not 2 or 1/2
@@ -179,7 +200,7 @@ impl SimplifiedNode {
 
                         // Restart the reduction for this node - this shouldn't recurse infinitely,
                         // due to our != 1 barrier
-                        self.reduce()?;
+                        self.reduce_with_settings(settings)?;
                         return Ok(PerformedReduction)
                     }
                 }
@@ -192,10 +213,18 @@ impl SimplifiedNode {
                         return Ok(PerformedReduction)
                     }
 
-                    // Anything to the power 0 becomes 1
+                    // Anything to the power 0 becomes 1 - but 0^0 might not, so if that's not
+                    // allowed then only do this if we know the base isn't 0
                     if let Some(0) = n.to_whole() {
-                        *self = SimplifiedNode::Number(Number::one());
-                        return Ok(PerformedReduction)
+                        if settings.zero_power_zero == ZeroPowerZero::One || settings.assumptions.is_non_zero(b) {
+                            *self = SimplifiedNode::Number(Number::one());
+                            return Ok(PerformedReduction)
+                        } else if let box SimplifiedNode::Number(_) = b {
+                            return Err(match settings.zero_power_zero {
+                                ZeroPowerZero::Undefined => MathsError::Undefined,
+                                _ => MathsError::Indeterminate,
+                            })
+                        }
                     }
                 }
 
@@ -243,29 +272,38 @@ impl SimplifiedNode {
                         }
                     },
 
-                    box SimplifiedNode::Power(inner_base, inner_exp) => {
-                        // So we're powering a power - (x^a)^b = x^(ab), so we can multiply and
-                        // reduce the two exponents to form a new power node
-                        //
-                        // (The inner base and exponent were already reduced recursively earlier)
-                        
+                    // So we're powering a power - (x^a)^b = x^(ab), so we can multiply and
+                    // reduce the two exponents to form a new power node. This only holds if b is
+                    // an integer or x is non-negative, otherwise we'd reduce √(x²) to x
+                    //
+                    // (The inner base and exponent were already reduced recursively earlier)
+                    box SimplifiedNode::Power(inner_base, inner_exp)
+                    if settings.assumptions.is_integer(e) || settings.assumptions.is_non_negative(inner_base) => {
                         let mut new_exp = SimplifiedNode::Multiply(vec![
                             inner_exp.as_ref().clone(),
                             e.as_ref().clone(),
                         ]);
-                        new_exp.reduce()?; 
+                        new_exp.reduce_with_settings(settings)?; 
+                        let new_exp_whole = matches!(&new_exp, SimplifiedNode::Number(n) if n.to_whole().is_some());
                         
                         *self = SimplifiedNode::Power(
                             inner_base.clone(),
                             Box::new(new_exp),
                         );
+
+                        // If the new exponent is whole, the special cases for whole exponents may
+                        // apply, e.g. √(x²) = x¹ = x. (Other exponents would be split up again!)
+                        if new_exp_whole {
+                            self.reduce_with_settings(settings)?;
+                        }
                         status = PerformedReduction
                     }
 
-                    box SimplifiedNode::Multiply(v) => {
-                        // To raise a series of multiplications to a power, we can apply that power
-                        // to all of the terms of the multiplication instead: (ab)^n = a^n*b^n
-                        //
+                    // To raise a series of multiplications to a power, we can apply that power to
+                    // all of the terms of the multiplication instead: (ab)^n = a^n*b^n. Like
+                    // above, this needs n to be an integer or the terms to be non-negative
+                    box SimplifiedNode::Multiply(v)
+                    if settings.assumptions.is_integer(e) || v.iter().all(|n| settings.assumptions.is_non_negative(n)) => {
                         // Construct a new multiplication node where we do this
                         let mut new_terms = vec![];
 
@@ -277,11 +315,14 @@ impl SimplifiedNode {
                         }
 
                         *self = SimplifiedNode::Multiply(new_terms);
-                        self.reduce()?;
+                        self.reduce_with_settings(settings)?;
 
                         status = PerformedReduction
                     }
                         
+                    // The identities above aren't known to hold, so leave the power alone
+                    box SimplifiedNode::Power(_, _) | box SimplifiedNode::Multiply(_) => (),
+
                     box SimplifiedNode::Add(_) => todo!(),      // TODO: Expand
 
                     box SimplifiedNode::FunctionCall(_, _) => todo!(), // TODO
@@ -293,7 +334,7 @@ impl SimplifiedNode {
                 v.sort();
 
                 // Reduce children
-                Self::reduce_vec(v)?;
+                Self::reduce_vec(v, settings)?;
 
                 // Are there numbers at the start?
                 if let Some(numbers) = Self::collect_numbers_from_start(&v[..]) {
@@ -332,14 +373,18 @@ impl SimplifiedNode {
                     |n, c|
                         Ok(SimplifiedNode::Power(Box::new(n.clone()), Box::new(SimplifiedNode::Number(c))))
                 )? == PerformedReduction {
-                    self.reduce()?;
+                    self.reduce_with_settings(settings)?;
                     return Ok(PerformedReduction)
                 };
 
                 // If there is only one child, reduce to that child
+                // If there are none, they must have all multiplied to 1, which was removed
                 if v.len() == 1 {
                     *self = v[0].clone();
                     status = PerformedReduction;
+                } else if v.is_empty() {
+                    *self = Self::Number(Number::one());
+                    status = PerformedReduction;
                 }
             }
 
@@ -348,7 +393,7 @@ impl SimplifiedNode {
                 v.sort();
 
                 // Reduce children
-                Self::reduce_vec(v)?;
+                Self::reduce_vec(v, settings)?;
 
                 // Are there numbers at the start?
                 if let Some(numbers) = Self::collect_numbers_from_start(&v[..]) {
@@ -380,7 +425,7 @@ impl SimplifiedNode {
                             if let Some(SimplifiedNode::Number(n)) = v.first() => {
                                 // Construct a new multiply out of the non-number nodes
                                 let mut result = SimplifiedNode::Multiply(v[1..].to_vec());
-                                result.reduce()?;
+                                result.reduce_with_settings(settings)?;
                                 Ok((result, *n))
                             },
 
@@ -391,13 +436,16 @@ impl SimplifiedNode {
                             SimplifiedNode::Number(c), n
                         ]))
                 )? == PerformedReduction {
-                    self.reduce()?;
+                    self.reduce_with_settings(settings)?;
                     return Ok(PerformedReduction)
                 };
 
                 // If there is only one child, reduce to that child
+                // If there are none, they must have all added to 0, which was removed
                 if v.len() == 1 {
                     *self = v[0].clone();
+                } else if v.is_empty() {
+                    *self = Self::Number(Number::zero());
                 }
             }
         
@@ -470,11 +518,11 @@ impl SimplifiedNode {
     }
 
     /// Reduces a vec of nodes, and re-sorts the vec if any of the reductions changed a child node.
-    fn reduce_vec(vec: &mut Vec<SimplifiedNode>) -> ReductionResult {
+    fn reduce_vec(vec: &mut Vec<SimplifiedNode>, settings: &ReductionSettings) -> ReductionResult {
         // Reduce all child items, collecting whether any were actually reduced
         let mut any_children_reduced = false;
         for child in vec.iter_mut() {
-            if child.reduce()? == ReductionStatus::PerformedReduction {
+            if child.reduce_with_settings(settings)? == ReductionStatus::PerformedReduction {
                 any_children_reduced = true;
             }
         }
@@ -511,6 +559,17 @@ impl SimplifiedNode {
     }
 }
 
+/// Settings which control how a simplified node tree is reduced.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct ReductionSettings {
+    /// Assumptions about the values of variables.
+    pub assumptions: Assumptions,
+
+    /// How 0⁰ is treated. Unless this is [ZeroPowerZero::One], x⁰ is only reduced to 1 if x is
+    /// known to be non-zero.
+    pub zero_power_zero: ZeroPowerZero,
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum ReductionStatus {
     PerformedReduction,
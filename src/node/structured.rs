@@ -13,6 +13,7 @@
 
 use core::fmt::Display;
 use core::ops::Deref;
+use core::cell::RefCell;
 
 use alloc::boxed::Box;
 use alloc::string::ToString;
@@ -21,9 +22,9 @@ use num_traits::{FromPrimitive, Zero};
 use rust_decimal::{Decimal, MathematicalOps};
 
 use crate::Number;
-use crate::error::MathsError;
+use crate::error::{MathsError, MathsErrorDetail};
 use crate::node::common;
-use crate::number::DecimalAccuracy;
+use crate::number::{DecimalAccuracy, Ratio, ZeroOverZero, ZeroPowerZero};
 use crate::render::{Glyph, LayoutBlock, Layoutable, Renderer, LayoutComputationProperties};
 use crate::nav::NavPathNavigator;
 
@@ -42,9 +43,15 @@ pub enum StructuredNode {
     // A variable, identified by a character.
     Variable(char),
 
+    /// The constant π.
+    Pi,
+
     /// A square root applied to other structured nodes.
     Sqrt(Box<StructuredNode>),
 
+    /// A root of any degree, with both a degree and radicand as structured nodes.
+    Root(Box<StructuredNode>, Box<StructuredNode>),
+
     /// A power, with both a base and exponent as structured nodes.
     Power(Box<StructuredNode>, Box<StructuredNode>),
 
@@ -68,6 +75,12 @@ pub enum StructuredNode {
 
     /// A function call, with a sequence of arguments passed as structured nodes.
     FunctionCall(Function, Vec<StructuredNode>),
+
+    /// A conditional, which evaluates to the second node if the first node is true (non-zero), or
+    /// to the third node otherwise. Only the chosen node is evaluated.
+    ///
+    /// In unstructured trees, this is written as a call to [Function::If].
+    Conditional(Box<StructuredNode>, Box<StructuredNode>, Box<StructuredNode>),
 }
 
 /// A unit in which angles are measured.
@@ -93,7 +106,18 @@ impl Display for AngleUnit {
 }
 
 /// Settings for how structured nodes are evaluated into a number.
+///
+/// New settings may be added in future versions, so outside of rbop these can't be created with
+/// a struct literal. Instead, start from the [default](EvaluationSettings::new) settings and
+/// change them with the builder methods:
+///
+/// ```ignore
+/// let settings = EvaluationSettings::new()
+///     .with_angle_unit(AngleUnit::Radian)
+///     .with_decimal_places(Some(4));
+/// ```
 #[derive(PartialEq, Eq, Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct EvaluationSettings {
     /// The angle unit to use for trigonometric functions.
     pub angle_unit: AngleUnit,
@@ -103,6 +127,102 @@ pub struct EvaluationSettings {
     /// typically use Taylor series expansions). This produces less accurate results, but is much
     /// faster.
     pub use_floats: bool,
+
+    /// If true, [UnstructuredNodeRoot::from_number_with_settings](crate::UnstructuredNodeRoot::from_number_with_settings)
+    /// presents approximate results which are rational multiples of π in terms of π, for example
+    /// π/6 rather than 0.5235987. This is most useful for the results of inverse trigonometric
+    /// functions in radians.
+    pub pi_multiples: bool,
+
+    /// How to evaluate 0⁰.
+    pub zero_power_zero: ZeroPowerZero,
+
+    /// How to evaluate 0/0.
+    pub zero_over_zero: ZeroOverZero,
+
+    /// If true, [UnstructuredNodeRoot::from_number_with_settings](crate::UnstructuredNodeRoot::from_number_with_settings)
+    /// presents results with an engineering suffix where possible, for example 4.7k rather than
+    /// 4700.
+    pub engineering_suffix_results: bool,
+
+    /// If set, [UnstructuredNodeRoot::from_number_with_settings](crate::UnstructuredNodeRoot::from_number_with_settings)
+    /// rounds decimal results to this many decimal places. Rational results are unaffected.
+    pub decimal_places: Option<u32>,
+
+    /// If set, raising a number to a power with an absolute value larger than this fails with
+    /// [MathsError::Overflow]. Large non-integer powers are very slow to compute with
+    /// `rust_decimal`, so this can be used to keep evaluation responsive.
+    pub max_exponent: Option<u32>,
+}
+
+impl EvaluationSettings {
+    /// Creates the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets [angle_unit](EvaluationSettings::angle_unit).
+    pub fn with_angle_unit(self, angle_unit: AngleUnit) -> Self {
+        Self { angle_unit, ..self }
+    }
+
+    /// Sets [use_floats](EvaluationSettings::use_floats).
+    pub fn with_use_floats(self, use_floats: bool) -> Self {
+        Self { use_floats, ..self }
+    }
+
+    /// Sets [pi_multiples](EvaluationSettings::pi_multiples).
+    pub fn with_pi_multiples(self, pi_multiples: bool) -> Self {
+        Self { pi_multiples, ..self }
+    }
+
+    /// Sets [zero_power_zero](EvaluationSettings::zero_power_zero).
+    pub fn with_zero_power_zero(self, zero_power_zero: ZeroPowerZero) -> Self {
+        Self { zero_power_zero, ..self }
+    }
+
+    /// Sets [zero_over_zero](EvaluationSettings::zero_over_zero).
+    pub fn with_zero_over_zero(self, zero_over_zero: ZeroOverZero) -> Self {
+        Self { zero_over_zero, ..self }
+    }
+
+    /// Sets [engineering_suffix_results](EvaluationSettings::engineering_suffix_results).
+    pub fn with_engineering_suffix_results(self, engineering_suffix_results: bool) -> Self {
+        Self { engineering_suffix_results, ..self }
+    }
+
+    /// Sets [decimal_places](EvaluationSettings::decimal_places).
+    pub fn with_decimal_places(self, decimal_places: Option<u32>) -> Self {
+        Self { decimal_places, ..self }
+    }
+
+    /// Sets [max_exponent](EvaluationSettings::max_exponent).
+    pub fn with_max_exponent(self, max_exponent: Option<u32>) -> Self {
+        Self { max_exponent, ..self }
+    }
+
+    /// Raises a number to a power, respecting [zero_power_zero](EvaluationSettings::zero_power_zero)
+    /// and [max_exponent](EvaluationSettings::max_exponent).
+    pub(crate) fn pow(&self, base: Number, power: Number) -> Result<Number, MathsError> {
+        if let Some(max) = self.max_exponent {
+            if power.abs() > Number::from(max as i64) {
+                return Err(MathsError::Overflow)
+            }
+        }
+
+        base.checked_pow_with(power, self.zero_power_zero)
+    }
+
+    /// Divides one number by another, respecting
+    /// [zero_over_zero](EvaluationSettings::zero_over_zero).
+    pub(crate) fn div(&self, dividend: Number, divisor: Number) -> Result<Number, MathsError> {
+        dividend.checked_div_with(divisor, self.zero_over_zero)
+    }
+
+    /// Takes a root of a number, with the same settings as [pow](EvaluationSettings::pow).
+    pub(crate) fn root(&self, degree: Number, radicand: Number) -> Result<Number, MathsError> {
+        self.pow(radicand, Number::Rational(1, 1).checked_div(degree)?)
+    }
 }
 
 impl StructuredNode {
@@ -116,6 +236,17 @@ impl StructuredNode {
         matches!(&self, StructuredNode::Multiply(_, _) | StructuredNode::Divide(_, _))
     }
 
+    /// Returns true if this node is a single non-negative number, variable or π, which could be
+    /// written as a function argument without parentheses.
+    pub fn is_simple_argument(&self) -> bool {
+        match self {
+            StructuredNode::Number(n) =>
+                *n >= Number::zero() && !matches!(n, Number::Rational(_, d) if *d != 1),
+            StructuredNode::Variable(_) | StructuredNode::Pi => true,
+            _ => false,
+        }
+    }
+
     /// Returns a clone of this node wrapped in `Parentheses`.
     pub fn in_parentheses(&self) -> StructuredNode {
         StructuredNode::Parentheses(Box::new(self.clone()))
@@ -160,7 +291,7 @@ impl StructuredNode {
                 StructuredNode::Subtract(l.clone(), Box::new(r))
             }
 
-            StructuredNode::Number(_) | StructuredNode::Sqrt(_) | StructuredNode::Parentheses(_) | StructuredNode::Variable(_) | StructuredNode::Power(_, _) | StructuredNode::FunctionCall(_, _)
+            StructuredNode::Number(_) | StructuredNode::Sqrt(_) | StructuredNode::Root(_, _) | StructuredNode::Parentheses(_) | StructuredNode::Variable(_) | StructuredNode::Pi | StructuredNode::Power(_, _) | StructuredNode::FunctionCall(_, _) | StructuredNode::Conditional(_, _, _)
                 => self.clone(),
         })
     }
@@ -170,22 +301,127 @@ impl StructuredNode {
     /// Using the [Evaluable](crate::evaluate::Evaluable) trait is more desirable than calling this
     /// method directly, but this still exists for backwards-compatibility.
     pub fn evaluate(&self, settings: &EvaluationSettings) -> Result<Number, MathsError> {
+        let mut operands = vec![];
+        while let Some((_, child)) = self.next_operand(&operands) {
+            let value = child.evaluate(settings)?;
+            if let Some(result) = self.short_circuit(&value) {
+                return Ok(result)
+            }
+            operands.push(value);
+        }
+
+        self.evaluate_with_operands(&operands, settings)
+    }
+
+    /// Evaluates this node into a single number like [evaluate](StructuredNode::evaluate), but if
+    /// evaluation fails, returns details about the node which failed and the values of its
+    /// operands. This allows more helpful error messages to be shown, at the expense of some speed.
+    pub fn evaluate_detailed(&self, settings: &EvaluationSettings) -> Result<Number, MathsErrorDetail> {
+        let mut operands = vec![];
+        while let Some((i, child)) = self.next_operand(&operands) {
+            let value = child.evaluate_detailed(settings).map_err(|mut detail| {
+                detail.path.insert(0, i);
+                detail
+            })?;
+            if let Some(result) = self.short_circuit(&value) {
+                return Ok(result)
+            }
+            operands.push(value);
+        }
+
+        self.evaluate_with_operands(&operands, settings).map_err(|error| MathsErrorDetail {
+            error,
+            node: self.clone(),
+            operands,
+            path: vec![],
+        })
+    }
+
+    /// If this node is a call to [Function::Ratio], evaluates its arguments and returns their ratio
+    /// in its simplest form, like 3:2, rather than the single number which
+    /// [evaluate](StructuredNode::evaluate) would give. This allows a ratio to be presented as a
+    /// result.
+    ///
+    /// Returns None if this node isn't a ratio, or if the ratio can't be exact, as described by
+    /// [Number::to_ratio].
+    pub fn evaluate_ratio(&self, settings: &EvaluationSettings) -> Result<Option<Ratio>, MathsError> {
         match self {
-            StructuredNode::Number(n) => Ok((*n).into()),
-            StructuredNode::Variable(_) => Err(MathsError::MissingVariable),
-            StructuredNode::Sqrt(inner) =>
-                inner.evaluate(settings)?.to_decimal().sqrt().map(|x| x.into()).ok_or(MathsError::InvalidSqrt),
-            StructuredNode::Power(b, e) => b.evaluate(settings)?.checked_pow(e.evaluate(settings)?),
-            StructuredNode::Add(a, b) => a.evaluate(settings)?.checked_add(b.evaluate(settings)?),
-            StructuredNode::Subtract(a, b) => a.evaluate(settings)?.checked_sub(b.evaluate(settings)?),
-            StructuredNode::Multiply(a, b) => a.evaluate(settings)?.checked_mul(b.evaluate(settings)?),
-            StructuredNode::Divide(a, b) => a.evaluate(settings)?.checked_div(b.evaluate(settings)?),
-            StructuredNode::Parentheses(inner) => inner.evaluate(settings),
-            StructuredNode::FunctionCall(func, args) => {
-                let args = args.iter().map(|n| n.evaluate(settings)).collect::<Result<Vec<_>, _>>()?;
-                func.evaluate(&args, settings)
+            StructuredNode::FunctionCall(Function::Ratio, args) if args.len() == 2 =>
+                Ok(args[0].evaluate(settings)?.to_ratio(args[1].evaluate(settings)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the next child which needs to be evaluated before this node can be, along with its
+    /// index in [children](StructuredNode::children), given the values of the children evaluated
+    /// so far. Returns None once every operand needed by
+    /// [evaluate_with_operands](StructuredNode::evaluate_with_operands) has been evaluated.
+    ///
+    /// Children are usually evaluated in order, but conditionals only evaluate their condition and
+    /// then the branch it chooses.
+    pub(crate) fn next_operand(&self, operands: &[Number]) -> Option<(usize, &StructuredNode)> {
+        if let StructuredNode::Conditional(condition, then, otherwise) = self {
+            return match operands.len() {
+                0 => Some((0, condition)),
+                1 if operands[0].to_bool() => Some((1, then)),
+                1 => Some((2, otherwise)),
+                _ => None,
             }
         }
+
+        self.children().get(operands.len()).map(|child| (operands.len(), *child))
+    }
+
+    /// Returns the result of this node if it is already known from the value of one of its
+    /// operands, so the rest of them don't need to be evaluated. This is the case for some
+    /// [functions](Function::short_circuit), such as a logical AND with a false argument.
+    pub(crate) fn short_circuit(&self, operand: &Number) -> Option<Number> {
+        match self {
+            StructuredNode::FunctionCall(func, _) => func.short_circuit(operand),
+            _ => None,
+        }
+    }
+
+    /// Evaluates just this node, given the already-evaluated values of the operands chosen by
+    /// [next_operand](StructuredNode::next_operand).
+    pub(crate) fn evaluate_with_operands(&self, operands: &[Number], settings: &EvaluationSettings) -> Result<Number, MathsError> {
+        match self {
+            StructuredNode::Number(n) => Ok(*n),
+            StructuredNode::Variable(_) => Err(MathsError::MissingVariable),
+            StructuredNode::Pi => Ok(Number::pi()),
+            StructuredNode::Sqrt(_) =>
+                operands[0].to_decimal().sqrt().map(|x| x.into()).ok_or(MathsError::InvalidSqrt),
+            StructuredNode::Root(_, _) =>
+                settings.root(operands[0], operands[1]),
+            StructuredNode::Power(_, _) => settings.pow(operands[0], operands[1]),
+            StructuredNode::Add(_, _) => operands[0].checked_add(operands[1]),
+            StructuredNode::Subtract(_, _) => operands[0].checked_sub(operands[1]),
+            StructuredNode::Multiply(_, _) => operands[0].checked_mul(operands[1]),
+            StructuredNode::Divide(_, _) => settings.div(operands[0], operands[1]),
+            StructuredNode::Parentheses(_) => Ok(operands[0]),
+            StructuredNode::FunctionCall(func, _) => func.evaluate(operands, settings),
+
+            // The operands are the condition and the value of the branch it chose
+            StructuredNode::Conditional(_, _, _) => Ok(operands[1]),
+        }
+    }
+
+    /// Returns the direct children of this node, from left to right. For roots, the degree comes
+    /// before the radicand.
+    pub fn children(&self) -> Vec<&StructuredNode> {
+        match self {
+            StructuredNode::Add(l, r)
+            | StructuredNode::Subtract(l, r)
+            | StructuredNode::Multiply(l, r)
+            | StructuredNode::Divide(l, r)
+            | StructuredNode::Power(l, r)
+            | StructuredNode::Root(l, r) => vec![l, r],
+            StructuredNode::Sqrt(inner) | StructuredNode::Parentheses(inner) => vec![inner],
+            StructuredNode::FunctionCall(_, args) => args.iter().collect(),
+            StructuredNode::Conditional(c, t, o) => vec![c, t, o],
+
+            StructuredNode::Number(_) | StructuredNode::Variable(_) | StructuredNode::Pi => vec![],
+        }
     }
 
     /// Walks over all nodes in this tree.
@@ -206,13 +442,22 @@ impl StructuredNode {
                 b.walk(func);
                 e.walk(func);
             }
+            StructuredNode::Root(d, r) => {
+                d.walk(func);
+                r.walk(func);
+            }
             StructuredNode::FunctionCall(_, args) => {
                 for arg in args {
                     arg.walk(func);
                 }
             }
+            StructuredNode::Conditional(c, t, o) => {
+                c.walk(func);
+                t.walk(func);
+                o.walk(func);
+            }
 
-            StructuredNode::Number(_) | StructuredNode::Variable(_) => (),
+            StructuredNode::Number(_) | StructuredNode::Variable(_) | StructuredNode::Pi => (),
         }
     }
 
@@ -234,16 +479,40 @@ impl StructuredNode {
                 b.walk_mut(func);
                 e.walk_mut(func);
             }
+            StructuredNode::Root(d, r) => {
+                d.walk_mut(func);
+                r.walk_mut(func);
+            }
             StructuredNode::FunctionCall(_, args) => {
                 for arg in args {
                     arg.walk_mut(func);
                 }
             }
+            StructuredNode::Conditional(c, t, o) => {
+                c.walk_mut(func);
+                t.walk_mut(func);
+                o.walk_mut(func);
+            }
 
-            StructuredNode::Number(_) | StructuredNode::Variable(_) => (),
+            StructuredNode::Number(_) | StructuredNode::Variable(_) | StructuredNode::Pi => (),
         }
     }    
 
+    /// Returns the names of the variables used in this tree, in the order they first appear, without
+    /// duplicates.
+    pub fn variables(&self) -> Vec<char> {
+        let variables = RefCell::new(vec![]);
+        self.walk(&|n| {
+            if let StructuredNode::Variable(name) = n {
+                let mut variables = variables.borrow_mut();
+                if !variables.contains(name) {
+                    variables.push(*name);
+                }
+            }
+        });
+        variables.into_inner()
+    }
+
     /// Returns a clone of this node tree where all usages of a variable are replaced with another
     /// set of nodes.
     pub fn substitute_variable(&self, var_name: char, subst: &StructuredNode) -> StructuredNode {
@@ -320,6 +589,7 @@ impl Layoutable for StructuredNode {
             },
 
             StructuredNode::Variable(v) => LayoutBlock::from_glyph(renderer, Glyph::Variable { name: *v }, properties),
+            StructuredNode::Pi => LayoutBlock::from_glyph(renderer, Glyph::Pi, properties),
 
             StructuredNode::Add(left, right) => layout_binop(renderer, Glyph::Add, properties, left, right),
             StructuredNode::Subtract(left, right) => layout_binop(renderer, Glyph::Subtract, properties, left, right),
@@ -329,12 +599,63 @@ impl Layoutable for StructuredNode {
                 => common::layout_fraction(top.deref(), bottom.deref(), renderer, path, properties),
             StructuredNode::Sqrt(inner)
                 => common::layout_sqrt(inner.deref(), renderer, path, properties),
+            StructuredNode::Root(degree, radicand)
+                => common::layout_root(degree.deref(), radicand.deref(), renderer, path, properties),
             StructuredNode::Parentheses(inner)
                 => common::layout_parentheses(inner.deref(), renderer, path, properties),
-            StructuredNode::Power(base, exp)
-                => common::layout_power(Some(base.deref()), exp.deref(), renderer, path, properties),
+            StructuredNode::Power(base, exp) => {
+                // A function call with its parentheses left out would look like it's raising its
+                // argument to the power instead, like `sin 30²`
+                let properties = if matches!(base.deref(), StructuredNode::FunctionCall(_, _)) {
+                    LayoutComputationProperties { elide_function_parentheses: false, ..properties }
+                } else {
+                    properties
+                };
+                common::layout_power(Some(base.deref()), exp.deref(), renderer, path, properties)
+            },
             StructuredNode::FunctionCall(func, args)
-                => common::layout_function_call(*func, args, renderer, path, properties),
+                => common::layout_function_call(
+                    *func, args, args.len() == 1 && args[0].is_simple_argument(), renderer, path, properties
+                ),
+            StructuredNode::Conditional(condition, then, otherwise) =>
+                if properties.piecewise_conditionals {
+                    common::layout_piecewise(condition.deref(), then.deref(), otherwise.deref(), renderer, properties)
+                } else {
+                    let args = [condition.deref(), then.deref(), otherwise.deref()];
+                    common::layout_function_call(Function::If, &args, false, renderer, path, properties)
+                },
+        }
+    }
+
+    fn glyph_count_bound(&self) -> usize {
+        match self {
+            // One glyph per character, including any point and sign
+            StructuredNode::Number(Number::Decimal(number, _)) => number.to_string().len(),
+            StructuredNode::Number(Number::Rational(numer, denom)) => {
+                let digits = |n: &i64| n.to_string().len();
+                if *denom == 1 { digits(numer) } else { 1 + digits(numer) + digits(denom) }
+            },
+
+            StructuredNode::Variable(_) | StructuredNode::Pi => 1,
+
+            StructuredNode::Add(left, right) | StructuredNode::Subtract(left, right) | StructuredNode::Multiply(left, right)
+                => 1 + left.glyph_count_bound() + right.glyph_count_bound(),
+
+            StructuredNode::Divide(a, b) => 1 + a.glyph_count_bound() + b.glyph_count_bound(),
+
+            // Radicals may be decomposed into a tick and an overline
+            StructuredNode::Root(a, b) => 2 + a.glyph_count_bound() + b.glyph_count_bound(),
+            StructuredNode::Sqrt(inner) => 2 + inner.glyph_count_bound(),
+            StructuredNode::Parentheses(inner) => 2 + inner.glyph_count_bound(),
+            StructuredNode::Power(base, exp) => 3 + base.glyph_count_bound() + exp.glyph_count_bound(),
+            // Functions written as powers also have a fixed base or exponent of up to two glyphs,
+            // with a caret and parentheses
+            StructuredNode::FunctionCall(func, args)
+                => 3 + args.len() + args.iter().map(|a| a.glyph_count_bound()).sum::<usize>()
+                    + if func.is_written_as_power() { 5 } else { 0 },
+
+            // Either a function call, or a brace and two pieces of text
+            StructuredNode::Conditional(c, t, o) => 5 + c.glyph_count_bound() + t.glyph_count_bound() + o.glyph_count_bound(),
         }
     }
 }
@@ -344,6 +665,7 @@ impl Simplifiable for StructuredNode {
         match self {
             &Self::Number(n) => SimplifiedNode::Number(n),
             &Self::Variable(n) => SimplifiedNode::Variable(n),
+            &Self::Pi => SimplifiedNode::Number(Number::pi()),
 
             &Self::Add(ref l, ref r) => SimplifiedNode::Add(vec![
                 l.simplify(), 
@@ -367,6 +689,10 @@ impl Simplifiable for StructuredNode {
                 Box::new(n.simplify()),
                 Box::new(SimplifiedNode::Number(Number::Rational(1, 2))),
             ),
+            Self::Root(d, r) => SimplifiedNode::Power(
+                Box::new(r.simplify()),
+                Box::new(d.simplify().reciprocal()),
+            ),
             Self::Power(b, e) => SimplifiedNode::Power(
                 Box::new(b.simplify()),
                 Box::new(e.simplify()),
@@ -377,6 +703,11 @@ impl Simplifiable for StructuredNode {
                 args.iter().map(|n| n.simplify()).collect(),
             ),
 
+            Self::Conditional(c, t, o) => SimplifiedNode::FunctionCall(
+                Function::If,
+                vec![c.simplify(), t.simplify(), o.simplify()],
+            ),
+
             Self::Parentheses(n) => n.simplify(),
         }
     }
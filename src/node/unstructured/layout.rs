@@ -2,30 +2,130 @@
 
 use alloc::{vec::Vec, vec};
 
-use crate::{render::{Layoutable, Renderer, LayoutComputationProperties, LayoutBlock, Glyph}, UnstructuredNodeRoot, nav::NavPathNavigator, UnstructuredNode, node::common, UnstructuredNodeList, UnstructuredItem};
+use crate::{render::{Layoutable, Renderer, LayoutComputationProperties, LayoutBlock, Glyph}, UnstructuredNodeRoot, nav::NavPathNavigator, UnstructuredNode, node::common, UnstructuredNodeList, UnstructuredItem, Token};
+
+/// Returns the properties to lay out a node with, given the node after it. A function call whose
+/// parentheses are left out would run into anything after it other than an operator, like `sin 30`
+/// followed by `5`, so its parentheses are kept in that case.
+///
+/// Styled nodes are looked through, so a styled call is treated like any other call, and a styled
+/// operator like any other operator.
+fn elision_properties(node: &UnstructuredNode, next: Option<&UnstructuredNode>, properties: LayoutComputationProperties) -> LayoutComputationProperties {
+    let is_call = matches!(unstyled(node, |items| items.last()), UnstructuredNode::FunctionCall(_, _));
+    let next_is_operator = matches!(
+        next.map(|next| unstyled(next, |items| items.first())),
+        None | Some(UnstructuredNode::Token(Token::Add | Token::Subtract | Token::Multiply | Token::Divide))
+    );
+
+    if is_call && !next_is_operator {
+        LayoutComputationProperties { elide_function_parentheses: false, ..properties }
+    } else {
+        properties
+    }
+}
+
+/// Returns the node wrapped by a styled node, looking through any number of styles. If a styled
+/// node wraps several nodes, `pick` chooses which one to use. Nodes which aren't styled, or styled
+/// nodes which are empty, are returned unchanged.
+fn unstyled(node: &UnstructuredNode, pick: fn(&[UnstructuredNode]) -> Option<&UnstructuredNode>) -> &UnstructuredNode {
+    match node {
+        UnstructuredNode::Styled(_, inner) => match pick(&inner.items) {
+            Some(inner) => unstyled(inner, pick),
+            None => node,
+        },
+        _ => node,
+    }
+}
+
+impl UnstructuredNode {
+    /// Wraps this node in a styled node, so that it is laid out with the given style.
+    ///
+    /// Wrapping a power would stop it being laid out as a superscript, so the contents of its
+    /// exponent are wrapped instead. Returns true if this happened, so the wrapper is inside this
+    /// node rather than around it.
+    pub(crate) fn wrap_styled(&mut self, style: u8) -> bool {
+        if let UnstructuredNode::Power(exp) = self {
+            *exp = UnstructuredNodeList { items: vec![UnstructuredNode::Styled(style, exp.clone())] };
+            true
+        } else {
+            *self = UnstructuredNode::Styled(style, UnstructuredNodeList { items: vec![self.clone()] });
+            false
+        }
+    }
+}
 
 impl Layoutable for UnstructuredNodeRoot {
     fn layout(&self, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock {
         self.root.layout(renderer, path, properties)
     }
+
+    fn glyph_count_bound(&self) -> usize {
+        self.root.glyph_count_bound()
+    }
 }
 
 impl Layoutable for UnstructuredNode {
     fn layout(&self, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> crate::render::LayoutBlock {
         match self {
+            UnstructuredNode::Token(Token::Boolean(value))
+                => LayoutBlock::from_glyph(renderer, Glyph::Boolean {
+                    value: *value,
+                    name: Glyph::boolean_name(*value, properties.boolean_symbols),
+                }, properties),
             UnstructuredNode::Token(token)
                 => LayoutBlock::from_glyph(renderer, (*token).into(), properties),
 
             UnstructuredNode::Sqrt(inner)
                 => common::layout_sqrt(inner, renderer, path, properties),
+            UnstructuredNode::Root(degree, radicand)
+                => common::layout_root(degree, radicand, renderer, path, properties),
             UnstructuredNode::Fraction(top, bottom)
                 => common::layout_fraction(top, bottom, renderer, path, properties),
             UnstructuredNode::Parentheses(inner)
                 => common::layout_parentheses(inner, renderer, path, properties),
             UnstructuredNode::Power(exp)
                 => common::layout_power(None, exp, renderer, path, properties),
+            UnstructuredNode::PowerOfTen(exp) => LayoutBlock::layout_horizontal(&[
+                LayoutBlock::from_glyph(renderer, Glyph::Multiply, properties),
+                LayoutBlock::from_glyph(renderer, Glyph::Digit { number: 1 }, properties),
+                LayoutBlock::from_glyph(renderer, Glyph::Digit { number: 0 }, properties),
+                common::layout_power(None, exp, renderer, path, properties),
+            ]),
+            UnstructuredNode::Styled(style, inner) => {
+                let mut path = if let Some(p) = path {
+                    if p.next() == 0 { Some(p.step()) } else { None }
+                } else {
+                    None
+                };
+                inner.layout(renderer, path.as_mut(), LayoutComputationProperties { style: *style, ..properties }).in_slot(0)
+            },
+            UnstructuredNode::FunctionCall(func, args)
+                => common::layout_function_call(
+                    *func, args, args.len() == 1 && args[0].is_simple_argument(), renderer, path, properties
+                ),
+        }
+    }
+
+    fn glyph_count_bound(&self) -> usize {
+        match self {
+            UnstructuredNode::Token(_) => 1,
+
+            // Radicals may be decomposed into a tick and an overline
+            UnstructuredNode::Sqrt(inner) => 2 + inner.glyph_count_bound(),
+            UnstructuredNode::Root(degree, radicand) => 2 + degree.glyph_count_bound() + radicand.glyph_count_bound(),
+            UnstructuredNode::Fraction(top, bottom) => 1 + top.glyph_count_bound() + bottom.glyph_count_bound(),
+            UnstructuredNode::Parentheses(inner) => 2 + inner.glyph_count_bound(),
+            UnstructuredNode::Styled(_, exp) => exp.glyph_count_bound(),
+
+            // A caret and parentheses, if laid out inline
+            UnstructuredNode::Power(exp) => 3 + exp.glyph_count_bound(),
+            UnstructuredNode::PowerOfTen(exp) => 6 + exp.glyph_count_bound(),
+
+            // Name, parentheses and commas, plus the arguments. Functions written as powers also
+            // have a fixed base or exponent of up to two glyphs, with a caret and parentheses
             UnstructuredNode::FunctionCall(func, args)
-                => common::layout_function_call(*func, args, renderer, path, properties),
+                => 3 + args.len() + args.iter().map(|a| a.glyph_count_bound()).sum::<usize>()
+                    + if func.is_written_as_power() { 5 } else { 0 },
         }
     }
 }
@@ -70,8 +170,8 @@ impl Layoutable for UnstructuredNodeList {
             .map(|(i, node)| node.layout(
                 renderer,
                 (&mut paths[i]).as_mut(),
-                properties,
-            ))
+                elision_properties(node, children.get(i + 1), properties),
+            ).record_node(i))
             .collect::<Vec<_>>();
 
         // If the cursor is here, insert it
@@ -101,9 +201,19 @@ impl Layoutable for UnstructuredNodeList {
             let cursor_height = cursor_match_layout.area.height;
             let cursor_baseline = cursor_match_layout.baseline;
 
+            // Cursor styles which cover the next item need its width, or the width of a digit if
+            // the cursor is at the end of the list
+            let next_width = match layouts.get(idx) {
+                Some(layout) => layout.area.width,
+                None => renderer.size(Glyph::Digit { number: 0 }, properties.size_reduction_level).width,
+            };
+
             // Hackily match the baseline
             let mut cursor_layout = LayoutBlock::from_glyph(renderer, Glyph::Cursor {
                 height: cursor_height,
+                next_width,
+                style: properties.cursor_style,
+                visible: true,
             }, properties);
             cursor_layout.baseline = cursor_baseline;
 
@@ -119,6 +229,11 @@ impl Layoutable for UnstructuredNodeList {
         LayoutBlock::layout_horizontal(&layouts[..])
 
     }
+
+    fn glyph_count_bound(&self) -> usize {
+        // An empty list is drawn with a placeholder
+        self.items.iter().map(|n| n.glyph_count_bound()).sum::<usize>().max(1)
+    }
 }
 
 impl<'a> Layoutable for UnstructuredItem<'a> {
@@ -128,4 +243,11 @@ impl<'a> Layoutable for UnstructuredItem<'a> {
             UnstructuredItem::List(children) => children.layout(renderer, path, properties),
         }
     }
+
+    fn glyph_count_bound(&self) -> usize {
+        match self {
+            UnstructuredItem::Node(node) => node.glyph_count_bound(),
+            UnstructuredItem::List(children) => children.glyph_count_bound(),
+        }
+    }
 }
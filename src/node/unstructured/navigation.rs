@@ -2,8 +2,11 @@
 //! manipulating a [NavPath].
 
 use alloc::{vec::Vec, vec};
+use num_integer::Integer;
 
-use crate::{nav::{NavPathNavigator, NavPath, MoveVerticalDirection, self, MoveResult}, UnstructuredNodeList, UnstructuredItem, UnstructuredNode, UnstructuredNodeRoot, render::{Renderer, Viewport, ViewportVisibility, CalculatedPoint}};
+use crate::{nav::{NavPathNavigator, NavPath, MoveVerticalDirection, self, MoveResult, NavSelection, FractionNavigationOrder}, UnstructuredNodeList, UnstructuredItem, UnstructuredNode, UnstructuredNodeRoot, Token, render::{Renderer, Viewport, ViewportVisibility, CalculatedPoint}, Number, error::{EvaluationError, PathError}, node::structured::EvaluationSettings};
+
+use super::Upgradable;
 
 /// A trait implemented on items which can contain a cursor (currently only
 /// [unstructured](crate::node::unstructured) nodes.)
@@ -30,7 +33,7 @@ impl Navigable for UnstructuredNode {
     fn navigate_trace<F>(&mut self, path: &mut NavPathNavigator, mut trace: F) -> (&mut UnstructuredNodeList, usize) 
         where F : FnMut(UnstructuredItem)
     {
-        trace(UnstructuredItem::Node(&self.clone()));
+        trace(UnstructuredItem::Node(self));
 
         if path.here() {
             panic!("navigation path must end on unstructured node");
@@ -47,6 +50,15 @@ impl Navigable for UnstructuredNode {
 
                 inner.navigate_trace(step_path, trace)
             },
+            UnstructuredNode::Root(degree, radicand) => {
+                if next_index == 0 {
+                    degree.navigate_trace(step_path, trace)
+                } else if next_index == 1 {
+                    radicand.navigate_trace(step_path, trace)
+                } else {
+                    panic!("index out of range for root navigation")
+                }
+            },
             UnstructuredNode::Parentheses(inner) => {
                 if next_index != 0 {
                     panic!("index out of range for parens navigation")
@@ -54,6 +66,13 @@ impl Navigable for UnstructuredNode {
 
                 inner.navigate_trace(step_path, trace)
             },
+            UnstructuredNode::Styled(_, inner) => {
+                if next_index != 0 {
+                    panic!("index out of range for styled navigation")
+                }
+
+                inner.navigate_trace(step_path, trace)
+            },
             UnstructuredNode::Fraction(top, bottom) => {
                 if next_index == 0 {
                     top.navigate_trace(step_path, trace)
@@ -70,6 +89,13 @@ impl Navigable for UnstructuredNode {
 
                 exp.navigate_trace(step_path, trace)
             }
+            UnstructuredNode::PowerOfTen(exp) => {
+                if next_index != 0 {
+                    panic!("index out of range for power of ten navigation")
+                }
+
+                exp.navigate_trace(step_path, trace)
+            }
             UnstructuredNode::FunctionCall(_, args) => {
                 if next_index >= args.len() {
                     panic!("index out of range for function call navigation")
@@ -86,7 +112,7 @@ impl Navigable for UnstructuredNodeList {
     fn navigate_trace<F>(&mut self, path: &mut NavPathNavigator, mut trace: F) -> (&mut UnstructuredNodeList, usize) 
         where F : FnMut(UnstructuredItem)
     {
-        trace(UnstructuredItem::List(&self.clone()));
+        trace(UnstructuredItem::List(self));
 
         if path.here() {
             return (self, path.next());
@@ -127,6 +153,12 @@ impl UnstructuredNodeRoot {
 
     /// Modifies the given navigation path to move the cursor right.
     pub fn move_right(&mut self, path: &mut NavPath, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>) {
+        self.move_right_with_order(path, FractionNavigationOrder::default(), renderer, viewport)
+    }
+
+    /// Modifies the given navigation path to move the cursor right, moving through fractions in the
+    /// given order.
+    pub fn move_right_with_order(&mut self, path: &mut NavPath, order: FractionNavigationOrder, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>) {
         // Fetch the node which we're navigating within
         let (current_node, index) = self.root.navigate(&mut path.to_navigator());
         let children = &current_node.items;
@@ -135,7 +167,7 @@ impl UnstructuredNodeRoot {
         if index == children.len() {
             // Is there another node above this one?
             if !path.root() {
-                // Are we inside a function call?
+                // Are we inside a node with sequential slots, like a function call?
                 // To check, clone the path, step out to the structural node, and navigate to it
                 // Sure, there can be sub-nodes (like we need to consider for fractions), but we'll only
                 // ever want to hop between arguments if we're outside those, so this relatively naive
@@ -143,10 +175,10 @@ impl UnstructuredNodeRoot {
                 let mut outer_path = path.clone();
                 outer_path.pop(2);
                 let (outer_node, index) = self.root.navigate(&mut outer_path.to_navigator());
-                if let UnstructuredNode::FunctionCall(_, args) = &outer_node.items[index] {
+                if let Some(slots) = Self::horizontal_slots(&outer_node.items[index], order) {
                     // Can we move right into another argument?
                     let current_arg_index = path[path.len() - 2];
-                    if current_arg_index < args.len() - 1 {
+                    if current_arg_index < slots.len() - 1 {
                         // Yes, we can! Move right into the beginning of the next argument
                         path.pop(2);
                         path.push(current_arg_index + 1);
@@ -169,7 +201,7 @@ impl UnstructuredNodeRoot {
 
             match right_child {
                 // Structured nodes
-                UnstructuredNode::Sqrt(_) | UnstructuredNode::Fraction(_, _) | UnstructuredNode::Parentheses(_) | UnstructuredNode::Power(_) | UnstructuredNode::FunctionCall(_, _) => {
+                UnstructuredNode::Sqrt(_) | UnstructuredNode::Root(_, _) | UnstructuredNode::Fraction(_, _) | UnstructuredNode::Parentheses(_) | UnstructuredNode::Power(_) | UnstructuredNode::PowerOfTen(_) | UnstructuredNode::FunctionCall(_, _) | UnstructuredNode::Styled(_, _) => {
                     // Navigate into its first/only slot, and start at the first item of the
                     // unstructured
                     path.push(0);
@@ -186,6 +218,12 @@ impl UnstructuredNodeRoot {
 
     /// Modifies the given navigation path to move the cursor left.
     pub fn move_left(&mut self, path: &mut NavPath, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>) {
+        self.move_left_with_order(path, FractionNavigationOrder::default(), renderer, viewport)
+    }
+
+    /// Modifies the given navigation path to move the cursor left, moving through fractions in the
+    /// given order.
+    pub fn move_left_with_order(&mut self, path: &mut NavPath, order: FractionNavigationOrder, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>) {
         // Fetch the node which we're navigating within
         let (current_node, index) = self.root.navigate(&mut path.to_navigator());
         let children = &current_node.items;
@@ -199,14 +237,14 @@ impl UnstructuredNodeRoot {
                 let mut outer_path = path.clone();
                 outer_path.pop(2);
                 let (outer_node, index) = self.root.navigate(&mut outer_path.to_navigator());
-                if let UnstructuredNode::FunctionCall(_, args) = &outer_node.items[index] {
+                if let Some(slots) = Self::horizontal_slots(&outer_node.items[index], order) {
                     // Can we move right into another argument?
                     let current_arg_index = path[path.len() - 2];
                     if current_arg_index > 0 {
                         // Yes, we can! Move right into the end of the next argument
                         path.pop(2);
                         path.push(current_arg_index - 1);
-                        path.push(args[current_arg_index - 1].items.len());
+                        path.push(slots[current_arg_index - 1].items.len());
                         return
                     }
                 }
@@ -224,8 +262,14 @@ impl UnstructuredNodeRoot {
             let left_child = &children[index - 1];
 
             match left_child {
+                // In reading order, a fraction is entered at the end of its denominator
+                UnstructuredNode::Fraction(_, bottom) if order == FractionNavigationOrder::ReadingOrder => {
+                    path.push(1);
+                    path.push(bottom.items.len());
+                },
+
                 // Structured nodes
-                UnstructuredNode::Sqrt(n) | UnstructuredNode::Fraction(n, _) | UnstructuredNode::Parentheses(n) | UnstructuredNode::Power(n) => {
+                UnstructuredNode::Sqrt(n) | UnstructuredNode::Fraction(n, _) | UnstructuredNode::Parentheses(n) | UnstructuredNode::Power(n) | UnstructuredNode::PowerOfTen(n) | UnstructuredNode::Styled(_, n) => {
                     // Navigate into its first/only slot, and start at the last item of the
                     // unstructured
                     path.push(0);
@@ -239,6 +283,12 @@ impl UnstructuredNodeRoot {
                     path.push(args.last().expect("no args in call").items.len());
                 }
 
+                UnstructuredNode::Root(_, radicand) => {
+                    // Move into the end of the radicand
+                    path.push(1);
+                    path.push(radicand.items.len());
+                }
+
                 // Anything else, nothing special needed
                 UnstructuredNode::Token(_) => (),
             }
@@ -247,6 +297,17 @@ impl UnstructuredNodeRoot {
         self.ensure_cursor_visible(path, renderer, viewport);
     }
 
+    /// Returns the slots of a node which the cursor should move between one after the other when
+    /// moving left and right, like [UnstructuredNode::sequential_slots], but also including the
+    /// slots of fractions if the navigation order requires it.
+    fn horizontal_slots(node: &UnstructuredNode, order: FractionNavigationOrder) -> Option<Vec<&UnstructuredNodeList>> {
+        match node {
+            UnstructuredNode::Fraction(top, bottom) if order == FractionNavigationOrder::ReadingOrder
+                => Some(vec![top, bottom]),
+            _ => node.sequential_slots(),
+        }
+    }
+
     fn move_vertically(
         &mut self,
         path: &mut NavPath,
@@ -314,20 +375,162 @@ impl UnstructuredNodeRoot {
     pub fn insert(&mut self, path: &mut NavPath, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>, new_node: UnstructuredNode) {
         let (current_node, index) = self.root.navigate(&mut path.to_navigator());
 
-        current_node.items.insert(index, new_node.clone());
-
-        match new_node {
-            UnstructuredNode::Sqrt(_) | UnstructuredNode::Fraction(_, _) | UnstructuredNode::Parentheses(_) | UnstructuredNode::Power(_) | UnstructuredNode::FunctionCall(_, _) => {
-                // Move into the new node
-                path.push(0);
-                path.push(0);
-            },
+        let is_token = matches!(new_node, UnstructuredNode::Token(_));
+        current_node.items.insert(index, new_node);
 
+        if is_token {
             // Just move past it
-            UnstructuredNode::Token(_) => path.offset(1),
+            path.offset(1);
+        } else {
+            // Move into the new node
+            path.push(0);
+            path.push(0);
+        }
+
+        self.ensure_cursor_visible(path, renderer, viewport);
+    }
+
+    /// If the cursor is inside a square root, converts the innermost square root containing the
+    /// cursor into a [Root](UnstructuredNode::Root) with an empty degree, keeping the radicand, and
+    /// moves the cursor into the degree slot. This matches the behaviour of the "x√" key on many
+    /// physical calculators.
+    ///
+    /// Returns true if a square root was converted, or false if the cursor was not inside one (in
+    /// which case nothing is changed).
+    pub fn sqrt_to_root(&mut self, path: &mut NavPath, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>) -> bool {
+        let sqrt_nav_position = self.nav_nodes_outwards(path)
+            .into_iter()
+            .find(|(_, _, node)| matches!(node, UnstructuredNode::Sqrt(_)));
+        let ri = if let Some((_, ri, _)) = sqrt_nav_position { ri } else { return false };
+
+        // Pop up to and including the square root's slot, so the path points at the square root
+        path.pop(ri + 1);
+        let (current_node, index) = self.root.navigate(&mut path.to_navigator());
+        if let UnstructuredNode::Sqrt(radicand) = &mut current_node.items[index] {
+            let radicand = core::mem::take(radicand);
+            current_node.items[index] = UnstructuredNode::Root(UnstructuredNodeList::new(), radicand);
+        } else {
+            unreachable!()
         }
 
+        // Move into the new degree slot
+        path.push(0);
+        path.push(0);
+
         self.ensure_cursor_visible(path, renderer, viewport);
+        true
+    }
+
+    /// Inserts an empty [PowerOfTen](UnstructuredNode::PowerOfTen) at the cursor, and moves the
+    /// cursor into its exponent. This matches the behaviour of the "×10ˣ" key on many physical
+    /// calculators, allowing numbers like 1.5×10⁻³ to be entered with a single keypress.
+    pub fn insert_power_of_ten(&mut self, path: &mut NavPath, renderer: &mut impl Renderer, viewport: Option<&mut Viewport>) {
+        self.insert(path, renderer, viewport, UnstructuredNode::PowerOfTen(UnstructuredNodeList::new()));
+    }
+
+    /// Finds where digit grouping separators should be displayed in the number containing the
+    /// cursor, so that a number being typed can be shown like `1 234 567`. The integer part of the
+    /// number is split into groups of `group_size` digits, counting from the decimal point.
+    ///
+    /// Each returned path is interpreted in the same way as a cursor position, so a separator
+    /// should be displayed just before the item after each one. The tree itself isn't changed, so
+    /// the separators are purely for display.
+    ///
+    /// Returns an empty list if the cursor isn't next to a digit or decimal point, if the number is
+    /// too short to need any separators, or if the path isn't valid for this tree.
+    pub fn digit_grouping(&self, path: &NavPath, group_size: usize) -> Vec<NavPath> {
+        let (list, index) = match self.try_navigate_ref(path) {
+            Ok(found) if group_size > 0 => found,
+            _ => return vec![],
+        };
+
+        let is_number_part = |node: &UnstructuredNode|
+            matches!(node, UnstructuredNode::Token(Token::Digit(_) | Token::Point));
+
+        // Find the extent of the number around the cursor
+        let mut start = index;
+        while start > 0 && is_number_part(&list.items[start - 1]) {
+            start -= 1;
+        }
+        let mut end = index;
+        while end < list.items.len() && is_number_part(&list.items[end]) {
+            end += 1;
+        }
+
+        // Only the integer part is grouped
+        let integer_end = list.items[start..end].iter()
+            .position(|node| matches!(node, UnstructuredNode::Token(Token::Point)))
+            .map(|i| start + i)
+            .unwrap_or(end);
+
+        ((start + 1)..integer_end)
+            .filter(|i| (integer_end - i) % group_size == 0)
+            .map(|i| {
+                let mut separator_path = path.clone();
+                separator_path.offset(i as isize - index as isize);
+                separator_path
+            })
+            .collect()
+    }
+
+    /// Returns a copy of the items covered by a selection, as a new list, or an error if the
+    /// selection isn't valid for this tree.
+    pub fn selected_items(&self, selection: &NavSelection) -> Result<UnstructuredNodeList, PathError> {
+        let (list, start, end) = self.navigate_selection(selection)?;
+        Ok(UnstructuredNodeList { items: list.items[start..end].to_vec() })
+    }
+
+    /// Upgrades and evaluates the items covered by a selection, independently of the rest of the
+    /// node tree.
+    pub fn evaluate_selection(&self, selection: &NavSelection, settings: &EvaluationSettings) -> Result<Number, EvaluationError> {
+        Ok(self.selected_items(selection)?.upgrade()?.evaluate(settings)?)
+    }
+
+    /// Evaluates the items covered by a selection like
+    /// [evaluate_selection](UnstructuredNodeRoot::evaluate_selection), and then replaces them with
+    /// the result, as created by [from_number](UnstructuredNodeRoot::from_number). The cursor is
+    /// moved to just after the inserted result.
+    ///
+    /// If evaluation fails, the node tree and cursor are not modified.
+    pub fn evaluate_selection_in_place(
+        &mut self,
+        selection: &NavSelection,
+        path: &mut NavPath,
+        renderer: &mut impl Renderer,
+        viewport: Option<&mut Viewport>,
+        settings: &EvaluationSettings,
+    ) -> Result<Number, EvaluationError> {
+        let result = self.evaluate_selection(selection, settings)?;
+        let result_items = UnstructuredNodeRoot::from_number(result).root.items;
+        let result_len = result_items.len();
+
+        // The selection was checked while evaluating it
+        let (_, start, end) = self.navigate_selection(selection)?;
+        let (list, _) = self.try_navigate(&selection.start())?;
+        list.items.splice(start..end, result_items);
+
+        *path = selection.start();
+        path.offset(result_len as isize);
+
+        self.ensure_cursor_visible(path, renderer, viewport);
+        Ok(result)
+    }
+
+    /// Resolves a selection into the list it covers, and the start (inclusive) and end (exclusive)
+    /// indexes of the selected items within it, or returns an error if the selection isn't valid
+    /// for this tree or extends past the end of the list.
+    fn navigate_selection(&self, selection: &NavSelection) -> Result<(&UnstructuredNodeList, usize, usize), PathError> {
+        let (list, start) = self.try_navigate_ref(&selection.start())?;
+        let end = match selection {
+            NavSelection::Range(_, len) => start + len,
+            NavSelection::Container(_) => list.items.len(),
+        };
+
+        if end > list.items.len() {
+            return Err(PathError::InvalidPath)
+        }
+
+        Ok((list, start, end))
     }
 
     /// Deletes the item behind the cursor.
@@ -362,29 +565,98 @@ impl UnstructuredNodeRoot {
     }
 
 
+    /// Inserts a node at the position described by a navigation path, without needing a cursor.
+    /// The path is interpreted in the same way as a cursor position, so the new node is placed
+    /// where a cursor at that path would be.
+    pub fn insert_at(&mut self, path: &NavPath, node: UnstructuredNode) -> Result<(), PathError> {
+        let (list, index) = self.try_navigate(path)?;
+        list.items.insert(index, node);
+        Ok(())
+    }
+
+    /// Removes and returns the node after the position described by a navigation path, without
+    /// needing a cursor.
+    pub fn remove_at(&mut self, path: &NavPath) -> Result<UnstructuredNode, PathError> {
+        let (list, index) = self.try_navigate(path)?;
+        if index == list.items.len() {
+            return Err(PathError::NoNodeAtPath)
+        }
+        Ok(list.items.remove(index))
+    }
+
+    /// Replaces the node after the position described by a navigation path with a sequence of
+    /// nodes, and returns the replaced node, without needing a cursor.
+    pub fn replace_at(&mut self, path: &NavPath, nodes: Vec<UnstructuredNode>) -> Result<UnstructuredNode, PathError> {
+        let (list, index) = self.try_navigate(path)?;
+        if index == list.items.len() {
+            return Err(PathError::NoNodeAtPath)
+        }
+        Ok(list.items.splice(index..=index, nodes).next().unwrap())
+    }
+
+    /// Like [navigate](Navigable::navigate), but returns an error instead of panicking if the path
+    /// isn't valid for this tree.
+    fn try_navigate(&mut self, path: &NavPath) -> Result<(&mut UnstructuredNodeList, usize), PathError> {
+        // Paths alternate between list indexes and slot indexes, ending on a list index
+        if path.len().is_even() {
+            return Err(PathError::InvalidPath)
+        }
+
+        let mut list = &mut self.root;
+        for i in (0..path.len() - 1).step_by(2) {
+            list = list.items.get_mut(path[i])
+                .and_then(|node| node.slots_mut().into_iter().nth(path[i + 1]))
+                .ok_or(PathError::InvalidPath)?;
+        }
+
+        let index = path[path.len() - 1];
+        if index > list.items.len() {
+            return Err(PathError::InvalidPath)
+        }
+        Ok((list, index))
+    }
+
+    /// Like [try_navigate](UnstructuredNodeRoot::try_navigate), but without needing mutable
+    /// access to the tree.
+    fn try_navigate_ref(&self, path: &NavPath) -> Result<(&UnstructuredNodeList, usize), PathError> {
+        if path.len().is_even() {
+            return Err(PathError::InvalidPath)
+        }
+
+        let mut list = &self.root;
+        for i in (0..path.len() - 1).step_by(2) {
+            list = list.items.get(path[i])
+                .and_then(|node| node.slots().into_iter().nth(path[i + 1]))
+                .ok_or(PathError::InvalidPath)?;
+        }
+
+        let index = path[path.len() - 1];
+        if index > list.items.len() {
+            return Err(PathError::InvalidPath)
+        }
+        Ok((list, index))
+    }
+
     /// Builds a list of the items at each element of the nav path.
     ///
     /// Each index in the returned vec has a direct mapping to each index in the nav path. If the
     /// item in the returned vec is None, the nav path item is not a node. If it is Some, the
     /// wrapped node is the node at that index in the nav path.
     ///
-    /// The returned nodes are clones, not references, so modifying them will not affect the node
-    /// tree.
-    fn nav_node_list(&mut self, path: &mut NavPath) -> Vec<Option<UnstructuredNode>> {
-        let mut nav_items = vec![];
-        self.root.navigate_trace(
-            &mut path.to_navigator(), 
-            |item| {
-                // I fought the borrow checker and lost :(
-                // We only care about nodes, so this makes our life easier
-                // We still want nav_items to be the right length
-                if let UnstructuredItem::Node(node) = item {
-                    nav_items.push(Some(node.clone()));
-                } else {
-                    nav_items.push(None);
-                }
-            }
-        );
+    /// Panics if the path is not valid for this tree.
+    fn nav_node_list(&self, path: &NavPath) -> Vec<Option<&UnstructuredNode>> {
+        let mut nav_items = vec![None];
+        let mut list = &self.root;
+
+        // The path alternates between an index into a list, and an index into the slots of the
+        // node found there
+        for i in (0..path.len() - 1).step_by(2) {
+            let node = &list.items[path[i]];
+            list = node.slots()[path[i + 1]];
+            nav_items.push(Some(node));
+            nav_items.push(None);
+        }
+
         nav_items
     }
 
@@ -394,10 +666,7 @@ impl UnstructuredNodeRoot {
     /// The returned vec items are of the form (nav list index, reverse nav list index, node). Since
     /// the list works outwards, the nav list indexes are strictly decreasing. The reverse indexes
     /// start from the beginning of the nav list instead and are strictly increasing.
-    ///
-    /// The returned nodes are clones, not references, so modifying them will not affect the node
-    /// tree.
-    fn nav_nodes_outwards(&mut self, path: &mut NavPath) -> Vec<(usize, usize, UnstructuredNode)> {
+    fn nav_nodes_outwards(&self, path: &NavPath) -> Vec<(usize, usize, &UnstructuredNode)> {
         let mut result = vec![];
 
         // Get items
@@ -1,10 +1,13 @@
 //! The definition of the unstructured node tree itself.
 
-use core::iter::repeat;
+use core::{fmt, iter::repeat};
 
 use alloc::{vec, vec::Vec, string::ToString};
 
-use crate::{node::function::Function, Number};
+use num_traits::Zero;
+use rust_decimal::{Decimal, MathematicalOps};
+
+use crate::{node::{function::Function, structured::EvaluationSettings}, Number, number::ScientificNotation};
 
 /// An unstructured item, either a node or a node list. Useful for making functions which traverse
 /// node trees more generic.
@@ -40,6 +43,86 @@ pub enum Token {
 
     /// A variable, denoted by a particular character.
     Variable(char),
+
+    /// The constant π.
+    Pi,
+
+    /// An engineering suffix, which scales the number before it.
+    EngineeringSuffix(EngineeringSuffix),
+
+    /// A boolean literal, true or false. These upgrade to the numbers 1 and 0 respectively.
+    Boolean(bool),
+}
+
+/// An SI prefix used as a suffix to scale a number by a power of ten, like `4.7k` for 4700. These
+/// are common on calculators for electronics, where component values are written this way.
+///
+/// Suffix tokens are always accepted when upgrading, regardless of
+/// [EvaluationSettings::engineering_suffix_results], which only controls how results are presented.
+/// Applications which don't want suffixes in their input should leave them out of their keymap.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub enum EngineeringSuffix {
+    Pico,
+    Nano,
+    Micro,
+    Milli,
+    Kilo,
+    Mega,
+    Giga,
+}
+
+impl EngineeringSuffix {
+    /// All suffixes, from smallest to largest.
+    pub const ALL: [EngineeringSuffix; 7] = [
+        EngineeringSuffix::Pico,
+        EngineeringSuffix::Nano,
+        EngineeringSuffix::Micro,
+        EngineeringSuffix::Milli,
+        EngineeringSuffix::Kilo,
+        EngineeringSuffix::Mega,
+        EngineeringSuffix::Giga,
+    ];
+
+    /// The power of ten which this suffix scales by.
+    pub fn exponent(&self) -> i32 {
+        match self {
+            EngineeringSuffix::Pico => -12,
+            EngineeringSuffix::Nano => -9,
+            EngineeringSuffix::Micro => -6,
+            EngineeringSuffix::Milli => -3,
+            EngineeringSuffix::Kilo => 3,
+            EngineeringSuffix::Mega => 6,
+            EngineeringSuffix::Giga => 9,
+        }
+    }
+
+    /// The symbol for this suffix.
+    pub fn symbol(&self) -> char {
+        match self {
+            EngineeringSuffix::Pico => 'p',
+            EngineeringSuffix::Nano => 'n',
+            EngineeringSuffix::Micro => 'µ',
+            EngineeringSuffix::Milli => 'm',
+            EngineeringSuffix::Kilo => 'k',
+            EngineeringSuffix::Mega => 'M',
+            EngineeringSuffix::Giga => 'G',
+        }
+    }
+
+    /// Returns the suffix which scales by the given power of ten, if there is one.
+    pub fn from_exponent(exponent: i32) -> Option<EngineeringSuffix> {
+        Self::ALL.iter().find(|s| s.exponent() == exponent).copied()
+    }
+
+    /// The exact value which this suffix multiplies by.
+    pub fn multiplier(&self) -> Number {
+        let power = 10_i64.pow(self.exponent().unsigned_abs());
+        if self.exponent() > 0 {
+            Number::Rational(power, 1)
+        } else {
+            Number::Rational(1, power)
+        }
+    }
 }
 
 impl Token {
@@ -67,7 +150,12 @@ impl Token {
 
 /// An unstructured node in the tree. See the
 /// [module-level documentation](crate::node::unstructured) for more information.
-#[derive(PartialEq, Eq, Debug, Clone)]
+///
+/// The [Debug] representation is a compact, one-line form of the tree, which is much easier to
+/// read in logs and assertion failures than the structure itself. Tokens are written as they
+/// appear, and the slots of other nodes are wrapped in square brackets, for example
+/// `1 2 + [3 4]/[sqrt[5]]`.
+#[derive(PartialEq, Eq, Clone)]
 pub enum UnstructuredNode {
     /// A plain token.
     Token(Token),
@@ -75,6 +163,10 @@ pub enum UnstructuredNode {
     /// A square root, applied to other unstructured nodes.
     Sqrt(UnstructuredNodeList),
 
+    /// A root of any degree, with two other lists of unstructured nodes as the degree and the
+    /// radicand.
+    Root(UnstructuredNodeList, UnstructuredNodeList),
+
     /// A fraction/division, with two other lists of unstructured nodes as the numerator and
     /// denominator.
     Fraction(UnstructuredNodeList, UnstructuredNodeList),
@@ -86,8 +178,22 @@ pub enum UnstructuredNode {
     /// only discovered by upgrading the tree.
     Power(UnstructuredNodeList),
 
+    /// A power of ten, drawn as `×10` with the exponent as a superscript, like the "×10ˣ" key on
+    /// many calculators. When upgrading, this binds to the number before it like an
+    /// [EngineeringSuffix], so `6÷1.5×10³` divides by 1500. With no number before it, it is just a
+    /// power of ten.
+    PowerOfTen(UnstructuredNodeList),
+
     /// A function call, with a sequence of arguments passed as unstructured nodes. 
     FunctionCall(Function, Vec<UnstructuredNodeList>),
+
+    /// Unstructured nodes drawn with an application-defined style, for example to emphasise part
+    /// of an expression. The style is passed through to each [SizedGlyph](crate::render::SizedGlyph)
+    /// within the nodes, for the renderer to interpret.
+    /// 
+    /// This has no effect on the meaning of the expression - when upgrading, the nodes are treated
+    /// as if they were not wrapped at all.
+    Styled(u8, UnstructuredNodeList),
 }
 
 impl UnstructuredNode {
@@ -96,10 +202,101 @@ impl UnstructuredNode {
         let arg_vec = repeat(UnstructuredNodeList::new()).take(func.argument_count()).collect();
         Self::FunctionCall(func, arg_vec)
     }
+
+    /// If this node has multiple slots which the cursor should move between one after the other
+    /// when moving left and right, returns them in order. Otherwise returns None.
+    pub fn sequential_slots(&self) -> Option<Vec<&UnstructuredNodeList>> {
+        match self {
+            Self::FunctionCall(_, args) => Some(args.iter().collect()),
+            Self::Root(degree, radicand) => Some(vec![degree, radicand]),
+
+            Self::Token(_) | Self::Sqrt(_) | Self::Fraction(_, _) | Self::Parentheses(_) | Self::Power(_)
+            | Self::PowerOfTen(_) | Self::Styled(_, _)
+                => None,
+        }
+    }
+
+    /// Returns all of the node lists directly inside this node, in the order that they are indexed
+    /// by a [NavPath](crate::nav::NavPath).
+    pub fn slots(&self) -> Vec<&UnstructuredNodeList> {
+        match self {
+            Self::Token(_) => vec![],
+            Self::Sqrt(inner) | Self::Parentheses(inner) | Self::Power(inner) | Self::PowerOfTen(inner)
+            | Self::Styled(_, inner) => vec![inner],
+            Self::Fraction(a, b) | Self::Root(a, b) => vec![a, b],
+            Self::FunctionCall(_, args) => args.iter().collect(),
+        }
+    }
+
+    /// Returns mutable references to all of the node lists directly inside this node, in the order
+    /// that they are indexed by a [NavPath](crate::nav::NavPath).
+    pub fn slots_mut(&mut self) -> Vec<&mut UnstructuredNodeList> {
+        match self {
+            Self::Token(_) => vec![],
+            Self::Sqrt(inner) | Self::Parentheses(inner) | Self::Power(inner) | Self::PowerOfTen(inner)
+            | Self::Styled(_, inner) => vec![inner],
+            Self::Fraction(a, b) | Self::Root(a, b) => vec![a, b],
+            Self::FunctionCall(_, args) => args.iter_mut().collect(),
+        }
+    }
+
+    /// Returns the number of nodes in this node's tree, including this node.
+    pub fn node_count(&self) -> usize {
+        1 + self.slots().iter().map(|s| s.node_count()).sum::<usize>()
+    }
+
+    /// Returns the number of slots which the most deeply nested node in this node's tree is inside,
+    /// including the slots of this node. A token has a depth of 0, and a fraction of two tokens
+    /// has a depth of 1.
+    pub fn nesting_depth(&self) -> usize {
+        self.slots().iter().map(|s| 1 + s.nesting_depth()).max().unwrap_or(0)
+    }
+
+    /// Creates a new `UnstructuredNode::Root` with an empty degree and radicand.
+    pub fn new_root() -> Self {
+        Self::Root(UnstructuredNodeList::new(), UnstructuredNodeList::new())
+    }
+}
+
+impl fmt::Debug for UnstructuredNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnstructuredNode::Token(token) => match token {
+                Token::Add => write!(f, "+"),
+                Token::Subtract => write!(f, "-"),
+                Token::Multiply => write!(f, "*"),
+                Token::Divide => write!(f, "/"),
+                Token::Digit(d) => write!(f, "{}", d),
+                Token::Point => write!(f, "."),
+                Token::Variable(v) => write!(f, "{}", v),
+                Token::Pi => write!(f, "π"),
+                Token::EngineeringSuffix(suffix) => write!(f, "{}", suffix.symbol()),
+                Token::Boolean(b) => write!(f, "{}", b),
+            },
+            UnstructuredNode::Sqrt(inner) => write!(f, "sqrt[{:?}]", inner),
+            UnstructuredNode::Root(degree, radicand) => write!(f, "root[{:?}][{:?}]", degree, radicand),
+            UnstructuredNode::Fraction(top, bottom) => write!(f, "[{:?}]/[{:?}]", top, bottom),
+            UnstructuredNode::Parentheses(inner) => write!(f, "({:?})", inner),
+            UnstructuredNode::Power(exp) => write!(f, "^[{:?}]", exp),
+            UnstructuredNode::PowerOfTen(exp) => write!(f, "×10^[{:?}]", exp),
+            UnstructuredNode::FunctionCall(func, args) => {
+                write!(f, "{}[", func.render_name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}", arg)?;
+                }
+                write!(f, "]")
+            },
+            UnstructuredNode::Styled(style, inner) => write!(f, "style{}[{:?}]", style, inner),
+        }
+    }
 }
 
-/// An ordered sequence of unstructured nodes.
-#[derive(PartialEq, Eq, Debug, Clone, Default)]
+/// An ordered sequence of unstructured nodes. See [UnstructuredNode] for a description of the
+/// [Debug] representation.
+#[derive(PartialEq, Eq, Clone, Default)]
 pub struct UnstructuredNodeList {
     pub items: Vec<UnstructuredNode>
 }
@@ -108,14 +305,54 @@ impl UnstructuredNodeList {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Returns the number of nodes in this list, including nested nodes.
+    pub fn node_count(&self) -> usize {
+        self.items.iter().map(|n| n.node_count()).sum()
+    }
+
+    /// Returns the greatest [nesting depth](UnstructuredNode::nesting_depth) of the nodes in this
+    /// list.
+    pub fn nesting_depth(&self) -> usize {
+        self.items.iter().map(|n| n.nesting_depth()).max().unwrap_or(0)
+    }
+
+    /// Returns true if this list is a single number, variable or π, which could be written as a
+    /// function argument without parentheses.
+    pub fn is_simple_argument(&self) -> bool {
+        match &self.items[..] {
+            [] => false,
+            [UnstructuredNode::Token(Token::Variable(_) | Token::Pi | Token::Boolean(_))] => true,
+            items => items.iter().all(|n| matches!(n, UnstructuredNode::Token(Token::Digit(_) | Token::Point))),
+        }
+    }
 }
 
-/// The root of a tree of unstructured nodes.
-#[derive(PartialEq, Eq, Debug, Clone, Default)]
+impl fmt::Debug for UnstructuredNodeList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, node) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:?}", node)?;
+        }
+        Ok(())
+    }
+}
+
+/// The root of a tree of unstructured nodes. See [UnstructuredNode] for a description of the
+/// [Debug] representation.
+#[derive(PartialEq, Eq, Clone, Default)]
 pub struct UnstructuredNodeRoot {
     pub root: UnstructuredNodeList
 }
 
+impl fmt::Debug for UnstructuredNodeRoot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.root)
+    }
+}
+
 impl UnstructuredNodeRoot {
     pub fn new() -> Self {
         Self::default()
@@ -126,26 +363,18 @@ impl UnstructuredNodeRoot {
     /// `Decimal`s and whole `Rational`s become a sequence of tokens. `Rational`s with a denominator
     /// greater than 1 become a `Fraction` with a sequence of tokens on the top and bottom.
     pub fn from_number(num: Number) -> Self {
-        fn str_to_nodes(s: &str) -> Vec<UnstructuredNode> {
-            s.chars()
-                .map(|c| UnstructuredNode::Token(
-                    Token::from_char(c).expect("unknown token in decimal")
-                ))
-                .collect::<Vec<_>>()
-        }
-
         Self {
             root: UnstructuredNodeList {
                 items: match num {
-                    Number::Decimal(d, _) => str_to_nodes(&d.to_string()),
+                    Number::Decimal(d, _) => Self::str_to_nodes(&d.to_string()),
 
                     Number::Rational(numer, denom) => {
                         if denom == 1 {
-                            str_to_nodes(&numer.to_string())
+                            Self::str_to_nodes(&numer.to_string())
                         } else {
                             vec![UnstructuredNode::Fraction(
-                                UnstructuredNodeList { items: str_to_nodes(&numer.to_string()) },
-                                UnstructuredNodeList { items: str_to_nodes(&denom.to_string()) },
+                                UnstructuredNodeList { items: Self::str_to_nodes(&numer.to_string()) },
+                                UnstructuredNodeList { items: Self::str_to_nodes(&denom.to_string()) },
                             )]
                         }
                     },
@@ -153,4 +382,91 @@ impl UnstructuredNodeRoot {
             }
         }
     }
+
+    /// Creates a new `UnstructuredNodeRoot` given a number, like
+    /// [from_number](UnstructuredNodeRoot::from_number), but respecting the presentation options
+    /// in the given settings.
+    /// 
+    /// Currently, this means that if [pi_multiples](EvaluationSettings::pi_multiples) is set, and
+    /// the number is a multiple of π according to [Number::to_pi_multiple], then it is presented
+    /// in terms of π.
+    ///
+    /// Otherwise, if [engineering_suffix_results](EvaluationSettings::engineering_suffix_results)
+    /// is set, and the number is outside of the range 1 to 1000 but within the range of the
+    /// suffixes, then it is presented with an [EngineeringSuffix], for example 4.7k rather than
+    /// 4700.
+    ///
+    /// If [decimal_places](EvaluationSettings::decimal_places) is set, decimal numbers which aren't
+    /// presented in terms of π are rounded to that many decimal places.
+    pub fn from_number_with_settings(num: Number, settings: &EvaluationSettings) -> Self {
+        if settings.pi_multiples {
+            if let Some(Number::Rational(numer, denom)) = num.to_pi_multiple() {
+                // Leave out a coefficient of 1, but keep the sign of -1
+                let mut numer_items = match numer {
+                    1 => vec![],
+                    -1 => vec![UnstructuredNode::Token(Token::Subtract)],
+                    _ => Self::str_to_nodes(&numer.to_string()),
+                };
+                numer_items.push(UnstructuredNode::Token(Token::Pi));
+
+                return Self {
+                    root: UnstructuredNodeList {
+                        items: if denom == 1 {
+                            numer_items
+                        } else {
+                            vec![UnstructuredNode::Fraction(
+                                UnstructuredNodeList { items: numer_items },
+                                UnstructuredNodeList { items: Self::str_to_nodes(&denom.to_string()) },
+                            )]
+                        }
+                    }
+                }
+            }
+        }
+
+        let num = match (num, settings.decimal_places) {
+            (Number::Decimal(d, accuracy), Some(places)) => Number::Decimal(d.round_dp(places), accuracy),
+            _ => num,
+        };
+
+        if settings.engineering_suffix_results && !num.is_zero() {
+            let exponent = num.to_scientific(28).exponent.div_euclid(3) * 3;
+            if let Some(suffix) = EngineeringSuffix::from_exponent(exponent) {
+                let d = num.to_decimal();
+                let power = Decimal::TEN.powi(exponent.abs() as i64);
+                let scaled = if exponent > 0 { d.checked_div(power) } else { d.checked_mul(power) };
+                if let Some(scaled) = scaled {
+                    let mut items = Self::str_to_nodes(&scaled.normalize().to_string());
+                    items.push(UnstructuredNode::Token(Token::EngineeringSuffix(suffix)));
+                    return Self { root: UnstructuredNodeList { items } }
+                }
+            }
+        }
+
+        Self::from_number(num)
+    }
+
+    /// Creates a new `UnstructuredNodeRoot` given a number in [ScientificNotation], in the form
+    /// `m×10^e`. If the exponent is 0, just the mantissa is used.
+    pub fn from_scientific(sci: &ScientificNotation) -> Self {
+        let mut items = Self::str_to_nodes(&sci.mantissa.to_string());
+        if sci.exponent != 0 {
+            items.push(UnstructuredNode::Token(Token::Multiply));
+            items.push(UnstructuredNode::Token(Token::Digit(1)));
+            items.push(UnstructuredNode::Token(Token::Digit(0)));
+            items.push(UnstructuredNode::Power(UnstructuredNodeList {
+                items: Self::str_to_nodes(&sci.exponent.to_string()),
+            }));
+        }
+
+        Self { root: UnstructuredNodeList { items } }
+    }
+
+    fn str_to_nodes(s: &str) -> Vec<UnstructuredNode> {
+        s.chars()
+            .map(|c| UnstructuredNode::Token(
+                Token::from_char(c).expect("unknown token in decimal")
+            ))
+            .collect::<Vec<_>>()
+    }
 }
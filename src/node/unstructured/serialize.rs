@@ -2,141 +2,253 @@
 
 use alloc::{vec::Vec, vec};
 
-use crate::{serialize::Serializable, UnstructuredNodeRoot, UnstructuredNodeList, UnstructuredNode, Token, node::function::Function};
+use crate::{serialize::{Serializable, ByteRead}, error::DeserializeError, UnstructuredNodeRoot, UnstructuredNodeList, UnstructuredNode, Token, node::{function::Function, unstructured::EngineeringSuffix}};
 
 impl Serializable for UnstructuredNodeRoot {
-    fn serialize(&self) -> Vec<u8> {
-        self.root.serialize()
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.root.serialize_into(bytes)
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        Some(UnstructuredNodeRoot {
-            root: UnstructuredNodeList::deserialize(bytes)?
+    fn serialized_size_hint(&self) -> usize {
+        self.root.serialized_size_hint()
+    }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        Ok(UnstructuredNodeRoot {
+            root: UnstructuredNodeList::deserialize_from(reader)?
         })
     }
+
+    fn validate(reader: &mut dyn ByteRead) -> Result<(), DeserializeError> {
+        UnstructuredNodeList::validate(reader)
+    }
 }
 
 impl Serializable for UnstructuredNode {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
         match self {
             UnstructuredNode::Token(t) => {
-                let mut token_bytes = t.serialize();
-                if token_bytes[0] > 0b01111111 { panic!(); }
+                let token_start = bytes.len();
+                t.serialize_into(bytes);
+                if bytes[token_start] > 0b01111111 { panic!(); }
 
-                token_bytes[0] |= 0b10000000;
-                token_bytes
+                bytes[token_start] |= 0b10000000;
             },
             UnstructuredNode::Sqrt(i) => {
-                let mut n = vec![1];
-                n.append(&mut i.serialize());
-                n
+                bytes.push(1);
+                i.serialize_into(bytes);
             },
             UnstructuredNode::Fraction(t, b) => {
-                let mut n = vec![2];
-                n.append(&mut t.serialize());
-                n.append(&mut b.serialize());
-                n
+                bytes.push(2);
+                t.serialize_into(bytes);
+                b.serialize_into(bytes);
             }
             UnstructuredNode::Parentheses(i) => {
-                let mut n = vec![3];
-                n.append(&mut i.serialize());
-                n
+                bytes.push(3);
+                i.serialize_into(bytes);
             },
             UnstructuredNode::Power(e) => {
-                let mut n = vec![4];
-                n.append(&mut e.serialize());
-                n
+                bytes.push(4);
+                e.serialize_into(bytes);
             },
             UnstructuredNode::FunctionCall(func, args) => {
-                let mut n = vec![5];
-                n.append(&mut func.serialize());
-                n.append(&mut vec![args.len() as u8]);
+                bytes.push(5);
+                func.serialize_into(bytes);
+                bytes.push(args.len() as u8);
                 for arg in args {
-                    n.append(&mut arg.serialize());
+                    arg.serialize_into(bytes);
                 }
-                n
             }
+            UnstructuredNode::Root(d, r) => {
+                bytes.push(6);
+                d.serialize_into(bytes);
+                r.serialize_into(bytes);
+            }
+            UnstructuredNode::Styled(style, i) => {
+                bytes.push(7);
+                bytes.push(*style);
+                i.serialize_into(bytes);
+            }
+            UnstructuredNode::PowerOfTen(e) => {
+                bytes.push(8);
+                e.serialize_into(bytes);
+            }
+        }
+    }
+
+    fn serialized_size_hint(&self) -> usize {
+        match self {
+            UnstructuredNode::Token(t) => t.serialized_size_hint(),
+            UnstructuredNode::Sqrt(i) | UnstructuredNode::Parentheses(i) | UnstructuredNode::Power(i)
+            | UnstructuredNode::PowerOfTen(i)
+                => 1 + i.serialized_size_hint(),
+            UnstructuredNode::Fraction(a, b) | UnstructuredNode::Root(a, b)
+                => 1 + a.serialized_size_hint() + b.serialized_size_hint(),
+            UnstructuredNode::Styled(_, i) => 2 + i.serialized_size_hint(),
+            UnstructuredNode::FunctionCall(func, args)
+                => 1 + func.serialized_size_hint() + 1
+                    + args.iter().map(|a| a.serialized_size_hint()).sum::<usize>(),
         }
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        let first_byte = bytes.next()?;
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        let first_byte = reader.read_u8()?;
         match first_byte {
             _ if first_byte & 0b10000000 > 0 =>
-                Some(UnstructuredNode::Token(
-                    Token::deserialize(&mut vec![first_byte & 0b01111111]
-                        .into_iter()
-                        .chain(bytes))?)
-                ),
-            1 => Some(UnstructuredNode::Sqrt(UnstructuredNodeList::deserialize(bytes)?)),
-            2 => Some(UnstructuredNode::Fraction(
-                UnstructuredNodeList::deserialize(bytes)?,
-                UnstructuredNodeList::deserialize(bytes)?,
+                Ok(UnstructuredNode::Token(
+                    Token::deserialize_with_tag(first_byte & 0b01111111, reader)?
+                )),
+            1 => Ok(UnstructuredNode::Sqrt(UnstructuredNodeList::deserialize_from(reader)?)),
+            2 => Ok(UnstructuredNode::Fraction(
+                UnstructuredNodeList::deserialize_from(reader)?,
+                UnstructuredNodeList::deserialize_from(reader)?,
             )),
-            3 => Some(UnstructuredNode::Parentheses(UnstructuredNodeList::deserialize(bytes)?)),
-            4 => Some(UnstructuredNode::Power(
-                UnstructuredNodeList::deserialize(bytes)?,
+            3 => Ok(UnstructuredNode::Parentheses(UnstructuredNodeList::deserialize_from(reader)?)),
+            4 => Ok(UnstructuredNode::Power(
+                UnstructuredNodeList::deserialize_from(reader)?,
             )),
             5 => {
-                let func = Function::deserialize(bytes)?;
-                let arg_count = bytes.next()?;
+                let func = Function::deserialize_from(reader)?;
+                let arg_count = reader.read_u8()?;
                 let mut args = vec![];
                 for _ in 0..arg_count {
-                    args.push(UnstructuredNodeList::deserialize(bytes)?);
+                    args.push(UnstructuredNodeList::deserialize_from(reader)?);
                 }
-                Some(UnstructuredNode::FunctionCall(func, args))
+                Ok(UnstructuredNode::FunctionCall(func, args))
             },
+            6 => Ok(UnstructuredNode::Root(
+                UnstructuredNodeList::deserialize_from(reader)?,
+                UnstructuredNodeList::deserialize_from(reader)?,
+            )),
+            7 => {
+                let style = reader.read_u8()?;
+                Ok(UnstructuredNode::Styled(style, UnstructuredNodeList::deserialize_from(reader)?))
+            },
+            8 => Ok(UnstructuredNode::PowerOfTen(UnstructuredNodeList::deserialize_from(reader)?)),
 
-            _ => None,
+            _ => Err(DeserializeError::InvalidTag(first_byte)),
+        }
+    }
+
+    fn validate(reader: &mut dyn ByteRead) -> Result<(), DeserializeError> {
+        let first_byte = reader.read_u8()?;
+        match first_byte {
+            _ if first_byte & 0b10000000 > 0 =>
+                Token::deserialize_with_tag(first_byte & 0b01111111, reader).map(|_| ()),
+            1 | 3 | 4 | 8 => UnstructuredNodeList::validate(reader),
+            2 | 6 => {
+                UnstructuredNodeList::validate(reader)?;
+                UnstructuredNodeList::validate(reader)
+            },
+            5 => {
+                Function::validate(reader)?;
+                let arg_count = reader.read_u8()?;
+                for _ in 0..arg_count {
+                    UnstructuredNodeList::validate(reader)?;
+                }
+                Ok(())
+            },
+            7 => {
+                reader.read_u8()?;
+                UnstructuredNodeList::validate(reader)
+            },
+
+            _ => Err(DeserializeError::InvalidTag(first_byte)),
         }
     }
 }
 
 impl Serializable for UnstructuredNodeList {
-    fn serialize(&self) -> Vec<u8> {
-        let mut result = vec![];
-        result.append(&mut self.items.len().serialize());
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.items.len().serialize_into(bytes);
         for item in &self.items {
-            result.append(&mut item.serialize());
+            item.serialize_into(bytes);
         }
-        result
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        let len = usize::deserialize(bytes)?;
+    fn serialized_size_hint(&self) -> usize {
+        self.items.len().serialized_size_hint()
+            + self.items.iter().map(|i| i.serialized_size_hint()).sum::<usize>()
+    }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        let len = usize::deserialize_from(reader)?;
         let mut result = vec![];
         for _ in 0..len {
-            result.push(UnstructuredNode::deserialize(bytes)?);
+            result.push(UnstructuredNode::deserialize_from(reader)?);
         }
-        Some(UnstructuredNodeList { items: result })
+        Ok(UnstructuredNodeList { items: result })
+    }
+
+    fn validate(reader: &mut dyn ByteRead) -> Result<(), DeserializeError> {
+        let len = usize::deserialize_from(reader)?;
+        for _ in 0..len {
+            UnstructuredNode::validate(reader)?;
+        }
+        Ok(())
     }
 }
 
 impl Serializable for Token {
-    fn serialize(&self) -> Vec<u8> {
-        vec![match self {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self {
             Token::Add => 1,
             Token::Subtract => 2,
             Token::Multiply => 3,
             Token::Divide => 4,
             Token::Digit(d) => 5 + *d,
             Token::Point => 15,
-            Token::Variable(c) => return vec![16, *c as u8],
-        }]
+            Token::Pi => 17,
+            Token::Boolean(false) => 19,
+            Token::Boolean(true) => 20,
+            Token::Variable(c) => {
+                bytes.extend_from_slice(&[16, *c as u8]);
+                return
+            },
+            Token::EngineeringSuffix(suffix) => {
+                bytes.extend_from_slice(&[18, suffix.exponent() as i8 as u8]);
+                return
+            },
+        })
+    }
+
+    fn serialized_size_hint(&self) -> usize {
+        match self {
+            Token::Variable(_) | Token::EngineeringSuffix(_) => 2,
+            _ => 1,
+        }
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        let byte = bytes.next()?;
-        Some(match byte {
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        let tag = reader.read_u8()?;
+        Token::deserialize_with_tag(tag, reader)
+    }
+}
+
+impl Token {
+    /// Deserializes a token whose first byte, `tag`, has already been read from `reader`. This is
+    /// needed by [UnstructuredNode], which flags the first byte of its tokens.
+    fn deserialize_with_tag(tag: u8, reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        Ok(match tag {
             1 => Token::Add,
             2 => Token::Subtract,
             3 => Token::Multiply,
             4 => Token::Divide,
-            5..=14 => Token::Digit(byte - 5),
+            5..=14 => Token::Digit(tag - 5),
             15 => Token::Point,
-            16 => Token::Variable(bytes.next()? as char),
+            16 => Token::Variable(reader.read_u8()? as char),
+            17 => Token::Pi,
+            19 => Token::Boolean(false),
+            20 => Token::Boolean(true),
+            18 => {
+                let exponent = reader.read_u8()?;
+                Token::EngineeringSuffix(
+                    EngineeringSuffix::from_exponent(exponent as i8 as i32)
+                        .ok_or(DeserializeError::InvalidTag(exponent))?
+                )
+            },
 
-            _ => return None,
+            _ => return Err(DeserializeError::InvalidTag(tag)),
         })
     }
 }
@@ -1,7 +1,7 @@
 //! Defines and implements the [Upgradable] trait, for converting to a 
 //! [structured](crate::node::structured) node tree.
 
-use alloc::{vec::Vec, boxed::Box};
+use alloc::{vec, vec::Vec, boxed::Box};
 
 use crate::{StructuredNode, error::NodeError, UnstructuredNodeList, node::parser, UnstructuredNodeRoot, UnstructuredNode};
 
@@ -19,6 +19,17 @@ pub trait Upgradable {
 
 impl Upgradable for UnstructuredNodeList {
     fn upgrade(&self) -> Result<StructuredNode, NodeError> {
+        // Styles don't affect the meaning of the expression, so parse the contents of any styled
+        // nodes as if they were part of this list
+        if self.items.iter().any(|n| matches!(n, UnstructuredNode::Styled(_, _))) {
+            let mut flattened = vec![];
+            Self::flatten_styled(&self.items, &mut flattened);
+            return parser::Parser {
+                index: 0,
+                nodes: &flattened[..]
+            }.parse()
+        }
+
         parser::Parser {
             index: 0,
             nodes: &self.items[..]
@@ -26,6 +37,18 @@ impl Upgradable for UnstructuredNodeList {
     }
 }
 
+impl UnstructuredNodeList {
+    fn flatten_styled(items: &[UnstructuredNode], into: &mut Vec<UnstructuredNode>) {
+        for item in items {
+            if let UnstructuredNode::Styled(_, inner) = item {
+                Self::flatten_styled(&inner.items, into);
+            } else {
+                into.push(item.clone());
+            }
+        }
+    }
+}
+
 impl Upgradable for UnstructuredNodeRoot {
     fn upgrade(&self) -> Result<StructuredNode, NodeError> {
         self.root.upgrade()
@@ -38,6 +61,9 @@ impl Upgradable for UnstructuredNode {
             UnstructuredNode::Sqrt(inner)
                 => Ok(StructuredNode::Sqrt(Box::new(inner.upgrade()?))),
 
+            UnstructuredNode::Root(degree, radicand)
+                => Ok(StructuredNode::Root(Box::new(degree.upgrade()?), Box::new(radicand.upgrade()?))),
+
             UnstructuredNode::Parentheses(inner)
                 => Ok(StructuredNode::Parentheses(Box::new(inner.upgrade()?))),
 
@@ -53,6 +79,14 @@ impl Upgradable for UnstructuredNode {
                     args.iter().map(|a| a.upgrade()).collect::<Result<Vec<_>, _>>()?
                 )),
 
+            UnstructuredNode::Styled(_, inner) => inner.upgrade(),
+
+            // With nothing to scale, this is just a power of ten
+            UnstructuredNode::PowerOfTen(_) => parser::Parser {
+                index: 0,
+                nodes: core::slice::from_ref(self),
+            }.parse(),
+
             UnstructuredNode::Token(_) => Err(NodeError::CannotUpgradeToken),
         }
     }
@@ -1,14 +1,14 @@
 //! Defines a number format which offers improved practicality over traditional floating-point
 //! numbers.
 
-use core::{cmp::Ordering, convert::TryInto, ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign}};
+use core::{cmp::Ordering, convert::TryInto, fmt, ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign}};
 
-use alloc::{vec, vec::Vec, string::ToString};
+use alloc::{vec::Vec, string::ToString};
 use num_integer::{Roots, Integer};
 use num_traits::{FromPrimitive, One, ToPrimitive, Zero, Signed};
 use rust_decimal::{Decimal, MathematicalOps};
 
-use crate::{decimal_ext::DecimalExtensions, serialize::Serializable, error::MathsError};
+use crate::{decimal_ext::DecimalExtensions, serialize::{Serializable, ByteRead}, error::{MathsError, DeserializeError}};
 
 /// Represents the accuracy of a [Decimal] number, based on how it was created.
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
@@ -30,6 +30,71 @@ impl DecimalAccuracy {
     }
 }
 
+/// How the indeterminate form 0⁰ is evaluated.
+/// 
+/// [Number] has no way to represent an undefined value, so the choices are to give a conventional
+/// result, to fail, or to fail in a way which can be presented as an undefined result.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum ZeroPowerZero {
+    /// 0⁰ = 1, which is the convention followed by most calculators.
+    #[default]
+    One,
+
+    /// 0⁰ is an error, [MathsError::Indeterminate].
+    Error,
+
+    /// 0⁰ is undefined, [MathsError::Undefined].
+    Undefined,
+}
+
+/// How the indeterminate form 0/0 is evaluated.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum ZeroOverZero {
+    /// 0/0 is a division by zero like any other, [MathsError::DivisionByZero].
+    #[default]
+    DivisionByZero,
+
+    /// 0/0 is an error, [MathsError::Indeterminate].
+    Indeterminate,
+
+    /// 0/0 is undefined, [MathsError::Undefined].
+    Undefined,
+}
+
+/// A number written in scientific notation, as a mantissa multiplied by a power of ten. Created by
+/// [Number::to_scientific].
+/// 
+/// The mantissa and exponent are kept separate so that displays with dedicated fields for each,
+/// such as seven-segment calculator displays, can draw them individually.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct ScientificNotation {
+    /// The mantissa, with an absolute value of at least 1 and less than 10 (unless the number is
+    /// 0, in which case this is also 0).
+    pub mantissa: Decimal,
+
+    /// The power of ten which the mantissa is multiplied by.
+    pub exponent: i32,
+}
+
+/// The ratio of two numbers as whole numbers in their simplest form, like 3:2. Created by
+/// [Number::to_ratio].
+///
+/// The [Display](fmt::Display) representation separates the two sides with a colon.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Ratio {
+    /// The left-hand side, which carries the sign if the ratio is negative.
+    pub left: i64,
+
+    /// The right-hand side, which is never negative.
+    pub right: i64,
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.left, self.right)
+    }
+}
+
 /// A versatile format for representing numbers. There are currently two variants - see their
 /// documentation for more info.
 /// 
@@ -61,6 +126,72 @@ pub enum Number {
 }
 
 impl Number {
+    /// The largest denominator which [to_pi_multiple](#method.to_pi_multiple) will find.
+    const PI_MULTIPLE_MAX_DENOMINATOR: i64 = 12;
+
+    /// Returns an approximation of the constant π.
+    pub fn pi() -> Number {
+        Number::Decimal(Decimal::PI, DecimalAccuracy::Approximation)
+    }
+
+    /// If this is an approximate `Decimal` which is very close to a non-zero rational multiple of
+    /// π, with a denominator of at most 12, returns that multiple as a simplified `Rational`.
+    /// Otherwise returns None.
+    /// 
+    /// This allows results such as angles to be presented exactly, e.g. π/6 rather than 0.5235987.
+    /// Rationals and exact decimals can't be multiples of π, so always return None.
+    pub fn to_pi_multiple(&self) -> Option<Number> {
+        let d = if let Number::Decimal(d, DecimalAccuracy::Approximation) = self { *d } else { return None };
+        if d.is_zero() { return None }
+
+        // Loose enough to allow for results computed with floats
+        let tolerance = Decimal::new(1, 6);
+        let multiple = d / Decimal::PI;
+        for denom in 1..=Self::PI_MULTIPLE_MAX_DENOMINATOR {
+            let scaled = multiple * Decimal::from(denom);
+            let numer = scaled.round();
+            if (scaled - numer).abs() < tolerance {
+                return Some(Number::Rational(numer.to_i64()?, denom).simplify())
+            }
+        }
+
+        None
+    }
+
+    /// Converts this number to [ScientificNotation], with the mantissa rounded to the given
+    /// number of significant figures.
+    /// 
+    /// This never fails, regardless of the size of the number, so can be used to display results
+    /// which would be unwieldy to write out in full.
+    pub fn to_scientific(&self, significant_figures: u32) -> ScientificNotation {
+        let d = self.to_decimal().normalize();
+        if d.is_zero() {
+            return ScientificNotation { mantissa: Decimal::ZERO, exponent: 0 }
+        }
+
+        // A decimal is an integer mantissa, with a scale giving the number of digits after the
+        // point. Rescale so that there's exactly one digit before the point instead - this can't
+        // overflow, because the integer mantissa isn't changed
+        let digits = d.mantissa().unsigned_abs().to_string().len() as u32;
+        let mut mantissa = Decimal::from_i128_with_scale(d.mantissa(), digits - 1)
+            .round_dp(significant_figures.saturating_sub(1))
+            .normalize();
+        let mut exponent = digits as i32 - 1 - d.scale() as i32;
+
+        // Rounding might have carried into another digit, e.g. 9.99 -> 10.0
+        if mantissa.abs() >= Decimal::TEN {
+            mantissa /= Decimal::TEN;
+            exponent += 1;
+        }
+
+        ScientificNotation { mantissa, exponent }
+    }
+
+    /// Interprets this number as a boolean, where any non-zero number is true.
+    pub fn to_bool(&self) -> bool {
+        !self.is_zero()
+    }
+
     /// Gets the accuracy of this number, if it is a `Decimal`.
     /// 
     /// `Rational` numbers always return [DecimalAccuracy::Exact].
@@ -212,6 +343,42 @@ impl Number {
         }
     }
 
+    /// Returns the ratio of this number to another, as whole numbers in their simplest form. For
+    /// example, the ratio of 1.5 to 1 is 3:2.
+    ///
+    /// Returns None if either number is an approximation, since the ratio couldn't be exact, or if
+    /// both numbers are zero, or if the sides of the ratio don't fit into an `i64`.
+    pub fn to_ratio(&self, other: Number) -> Option<Ratio> {
+        let (ln, ld) = self.exact_fraction()?;
+        let (rn, rd) = other.exact_fraction()?;
+
+        // Multiply both sides by both denominators, then move any sign to the left
+        let (mut left, mut right) = (ln.checked_mul(rd)?, rn.checked_mul(ld)?);
+        if right < 0 {
+            left = left.checked_neg()?;
+            right = right.checked_neg()?;
+        }
+
+        let gcd = Self::gcd(left.checked_abs()?, right);
+        if gcd == 0 {
+            return None
+        }
+        Some(Ratio { left: left / gcd, right: right / gcd })
+    }
+
+    /// Returns the numerator and denominator of this number, unless it is an approximation, or is
+    /// a decimal which doesn't fit into an `i64` once scaled to a whole number.
+    fn exact_fraction(&self) -> Option<(i64, i64)> {
+        match self {
+            Self::Rational(numer, denom) => Some((*numer, *denom)),
+            Self::Decimal(d, DecimalAccuracy::Exact) => {
+                let d = d.normalize();
+                Some((d.mantissa().to_i64()?, 10_i64.checked_pow(d.scale())?))
+            },
+            Self::Decimal(_, DecimalAccuracy::Approximation) => None,
+        }
+    }
+
     /// Raises this number to an integer power.
     pub fn powi(&self, exp: i64) -> Number {
         let mut n = *self;
@@ -266,17 +433,53 @@ impl Number {
         }
     }
 
-    /// Divides this number by another number, or returns an error if the divisor is zero.
+    /// Divides this number by another number, or returns an error if the divisor is zero or an
+    /// overflow occurs.
+    ///
+    /// 0/0 is a division by zero. Use [checked_div_with](Number::checked_div_with) to choose a
+    /// different behaviour.
     pub fn checked_div(&self, other: Number) -> Result<Number, MathsError> {
-        if other.is_zero() {
-            Err(MathsError::DivisionByZero)
-        } else {
-            Ok(*self / other)
+        self.checked_div_with(other, ZeroOverZero::DivisionByZero)
+    }
+
+    /// Divides this number by another number, treating 0/0 as described by `zero_over_zero`.
+    pub fn checked_div_with(&self, other: Number, zero_over_zero: ZeroOverZero) -> Result<Number, MathsError> {
+        match other {
+            _ if other.is_zero() && self.is_zero() => Err(match zero_over_zero {
+                ZeroOverZero::DivisionByZero => MathsError::DivisionByZero,
+                ZeroOverZero::Indeterminate => MathsError::Indeterminate,
+                ZeroOverZero::Undefined => MathsError::Undefined,
+            }),
+            _ if other.is_zero() => Err(MathsError::DivisionByZero),
+
+            // The reciprocal of a very small decimal may not fit
+            Number::Decimal(d, a) => self.checked_mul(Number::Decimal(
+                Decimal::one().checked_div(d).ok_or(MathsError::Overflow)?,
+                a,
+            )),
+            Number::Rational(_, _) => self.checked_mul(other.reciprocal()),
         }
     }
 
     /// Raises this number to the power of another number.
+    /// 
+    /// 0⁰ is treated as 1. Use [checked_pow_with](Number::checked_pow_with) to choose a different
+    /// behaviour.
     pub fn checked_pow(&self, power: Number) -> Result<Number, MathsError> {
+        self.checked_pow_with(power, ZeroPowerZero::One)
+    }
+
+    /// Raises this number to the power of another number, treating 0⁰ as described by
+    /// `zero_power_zero`.
+    pub fn checked_pow_with(&self, power: Number, zero_power_zero: ZeroPowerZero) -> Result<Number, MathsError> {
+        if self.is_zero() && power.is_zero() {
+            return match zero_power_zero {
+                ZeroPowerZero::One => Ok(Number::one()),
+                ZeroPowerZero::Error => Err(MathsError::Indeterminate),
+                ZeroPowerZero::Undefined => Err(MathsError::Undefined),
+            }
+        }
+
         // If both power and base are rational, we can get a bit more accuracy by breaking it down
         if let (Self::Rational(bn, bd), Self::Rational(pn, pd)) = (self, power) {
             // Can only keep as rational if (power denominator)th root of both base numerator and
@@ -291,16 +494,17 @@ impl Number {
             if bn_pd_nth_root.pow(pd.abs().try_into().map_err(|_| MathsError::Overflow)?) == *bn
                && bd_pd_nth_root.pow(pd.abs().try_into().map_err(|_| MathsError::Overflow)?) == *bd {
 
-                let mut result = Number::Rational(
-                    bn_pd_nth_root.pow(pn.abs().try_into().map_err(|_| MathsError::Overflow)?), 
-                    bd_pd_nth_root.pow(pn.abs().try_into().map_err(|_| MathsError::Overflow)?), 
-                );
+                // If the result doesn't fit in a rational, fall back to decimals below
+                let pn_abs = pn.abs().try_into().map_err(|_| MathsError::Overflow)?;
+                if let (Some(numer), Some(denom)) = (bn_pd_nth_root.checked_pow(pn_abs), bd_pd_nth_root.checked_pow(pn_abs)) {
+                    let mut result = Number::Rational(numer, denom);
 
-                if pn < 0 {
-                    result = result.reciprocal();
-                }
+                    if pn < 0 {
+                        result = result.reciprocal();
+                    }
 
-                return Ok(result)
+                    return Ok(result)
+                }
             }
         }
 
@@ -445,6 +649,13 @@ impl From<i64> for Number {
     }
 }
 
+impl From<bool> for Number {
+    /// Converts a boolean to a number, where true is 1 and false is 0.
+    fn from(b: bool) -> Self {
+        Self::Rational(if b { 1 } else { 0 }, 1)
+    }
+}
+
 impl Neg for Number {
     type Output = Self;
 
@@ -532,62 +743,69 @@ impl One for Number {
 }
 
 impl Serializable for DecimalAccuracy {
-    fn serialize(&self) -> Vec<u8> {
-        vec![match self {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.push(match self {
             DecimalAccuracy::Exact => 1,
             DecimalAccuracy::Approximation => 2,
-        }]
+        })
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        match bytes.next()? {
-            1 => Some(DecimalAccuracy::Exact),
-            2 => Some(DecimalAccuracy::Approximation),
-            _ => None,
+    fn serialized_size_hint(&self) -> usize { 1 }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        match reader.read_u8()? {
+            1 => Ok(DecimalAccuracy::Exact),
+            2 => Ok(DecimalAccuracy::Approximation),
+            tag => Err(DeserializeError::InvalidTag(tag)),
         }
     }
 }
 
 impl Serializable for Number {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
         match self {
             Number::Decimal(d, a) => {
-                let mut result = vec![1];
-                result.append(&mut d.serialize().to_vec());
-                result.append(&mut a.serialize());
-                result
+                bytes.push(1);
+                bytes.extend_from_slice(&d.serialize());
+                a.serialize_into(bytes);
             }
 
             Self::Rational(numer, denom) => {
-                let mut result = vec![2];
-                result.append(&mut numer.to_ne_bytes().to_vec());
-                result.append(&mut denom.to_ne_bytes().to_vec());
-                result
+                bytes.push(2);
+                bytes.extend_from_slice(&numer.to_ne_bytes());
+                bytes.extend_from_slice(&denom.to_ne_bytes());
             }
         }
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
-        let first_byte = bytes.next()?;
-        match first_byte {
+    fn serialized_size_hint(&self) -> usize {
+        match self {
+            Number::Decimal(_, a) => 1 + 16 + a.serialized_size_hint(),
+            Number::Rational(_, _) => 1 + 8 + 8,
+        }
+    }
+
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
+        match reader.read_u8()? {
             1 => {
-                let decimal = Decimal::deserialize(
-                    bytes.take(16).collect::<Vec<_>>().try_into().ok()?
-                );
-                let accuracy = DecimalAccuracy::deserialize(bytes)?;
-                Some(Number::Decimal(decimal, accuracy))
+                let mut decimal = [0; 16];
+                reader.read_exact(&mut decimal)?;
+                let accuracy = DecimalAccuracy::deserialize_from(reader)?;
+                Ok(Number::Decimal(Decimal::deserialize(decimal), accuracy))
             }
 
             2 => {
-                let numer: [u8; 8] = bytes.take(8).collect::<Vec<_>>().try_into().ok()?;
-                let denom: [u8; 8] = bytes.take(8).collect::<Vec<_>>().try_into().ok()?;
-                Some(Number::Rational(
+                let mut numer = [0; 8];
+                let mut denom = [0; 8];
+                reader.read_exact(&mut numer)?;
+                reader.read_exact(&mut denom)?;
+                Ok(Number::Rational(
                     i64::from_ne_bytes(numer),
                     i64::from_ne_bytes(denom),
                 ))
             }
 
-            _ => None
+            tag => Err(DeserializeError::InvalidTag(tag)),
         }
     }
 }
@@ -0,0 +1,27 @@
+//! Re-exports the traits and types which almost every integration needs, so that they can be
+//! imported at once:
+//!
+//! ```ignore
+//! use rbop::prelude::*;
+//! ```
+//!
+//! Many of rbop's methods are provided by traits, such as [Upgradable::upgrade] and
+//! [Renderer::draw_all], so they can't be called unless the trait is imported. Importing the prelude
+//! avoids this.
+
+pub use crate::{
+    Number, Token, UnstructuredNode, UnstructuredNodeList, UnstructuredNodeRoot, StructuredNode,
+    calculator::{SimpleCalculator, CalculatorKey},
+    editor::{Editor, EditOperation},
+    evaluate::Evaluable,
+    nav::NavPath,
+    node::{
+        function::Function,
+        simplified::Simplifiable,
+        structured::EvaluationSettings,
+        unstructured::{Navigable, Upgradable},
+    },
+    render::{Layoutable, LayoutComputationProperties, Renderer, Viewport},
+    renderers::AsciiRenderer,
+    serialize::Serializable,
+};
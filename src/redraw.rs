@@ -0,0 +1,97 @@
+//! Tracking of what was last drawn, so that redraws can be skipped when nothing has changed.
+//!
+//! Redrawing the whole screen after every input can cause visible flicker on slow displays, and
+//! many inputs (such as moving the cursor left when it's already at the start) don't change what
+//! is displayed at all. A [RedrawTracker] remembers a hash of the last layout and viewport which
+//! were drawn, so applications can check whether a new layout actually needs drawing.
+//!
+//! The tracker only stores a hash, rather than a copy of the layout, so it doesn't allocate.
+//!
+//! This module is only available with the `redraw` feature.
+
+use core::hash::{Hash, Hasher};
+
+use crate::render::{LayoutBlock, Renderer, Viewport};
+
+/// A 64-bit FNV-1a hasher. This isn't suitable for anything security-sensitive, but is fast and
+/// available without `std`.
+struct FnvHasher(u64);
+
+impl FnvHasher {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+}
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+}
+
+/// Computes a hash of a layout's glyphs and area, and the viewport it is drawn in. If two layouts
+/// have the same hash, they will almost certainly be drawn identically.
+pub fn layout_hash(layout: &LayoutBlock, viewport: Option<&Viewport>) -> u64 {
+    let mut hasher = FnvHasher::default();
+    layout.glyphs.hash(&mut hasher);
+    layout.area.hash(&mut hasher);
+    viewport.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Remembers the last layout and viewport which were drawn. See the
+/// [module-level documentation](crate::redraw) for more information.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct RedrawTracker {
+    last_drawn: Option<u64>,
+}
+
+impl RedrawTracker {
+    /// Creates a new tracker. Nothing has been drawn yet, so the first layout will always need
+    /// drawing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if the given layout and viewport differ from those which were last drawn.
+    pub fn needs_redraw(&self, layout: &LayoutBlock, viewport: Option<&Viewport>) -> bool {
+        self.last_drawn != Some(layout_hash(layout, viewport))
+    }
+
+    /// Records that the given layout and viewport have been drawn.
+    pub fn mark_drawn(&mut self, layout: &LayoutBlock, viewport: Option<&Viewport>) {
+        self.last_drawn = Some(layout_hash(layout, viewport));
+    }
+
+    /// Forgets what was last drawn, so that the next layout will always need drawing. This should
+    /// be called if the graphics surface was changed by something other than rbop, such as the
+    /// window being resized.
+    pub fn invalidate(&mut self) {
+        self.last_drawn = None;
+    }
+
+    /// Draws a layout with [draw_all_by_layout](Renderer::draw_all_by_layout), but only if it
+    /// needs redrawing. Returns true if it was drawn.
+    pub fn draw_if_changed(&mut self, renderer: &mut impl Renderer, layout: &LayoutBlock, viewport: Option<&Viewport>) -> bool {
+        let hash = layout_hash(layout, viewport);
+        if self.last_drawn == Some(hash) {
+            return false
+        }
+
+        renderer.draw_all_by_layout(layout, viewport);
+        self.last_drawn = Some(hash);
+        true
+    }
+}
@@ -1,40 +1,101 @@
+use core::cmp::min;
+
 use alloc::{vec::Vec, vec};
 use crate::Token;
+use crate::node::unstructured::EngineeringSuffix;
+
+use crate::nav::{NavPath, NavPathNavigator};
+use crate::UnstructuredNodeRoot;
+use crate::node::function::{Function, FunctionNameTable};
+use crate::error::LayoutError;
+
+/// The scalar type used for sizes and positions in layouts. This is `u32` on 32-bit targets, where
+/// 64-bit arithmetic is slow, and `u64` otherwise. Enabling the `u64_dimensions` feature uses `u64`
+/// on every target, which may be needed for extremely large layouts or sub-pixel
+/// [units](Renderer::units_per_pixel).
+#[cfg(any(feature = "u64_dimensions", not(target_pointer_width = "32")))]
+pub type Dimension = u64;
 
-use crate::nav::NavPathNavigator;
-use crate::node::function::Function;
+/// The scalar type used for sizes and positions in layouts. This is `u32` on 32-bit targets, where
+/// 64-bit arithmetic is slow, and `u64` otherwise. Enabling the `u64_dimensions` feature uses `u64`
+/// on every target, which may be needed for extremely large layouts or sub-pixel
+/// [units](Renderer::units_per_pixel).
+#[cfg(all(not(feature = "u64_dimensions"), target_pointer_width = "32"))]
+pub type Dimension = u32;
+
+/// The signed equivalent of [Dimension], used for positions which can be negative, such as within a
+/// [Viewport], and for moving layout points.
+#[cfg(any(feature = "u64_dimensions", not(target_pointer_width = "32")))]
+pub type SignedDimension = i64;
+
+/// The signed equivalent of [Dimension], used for positions which can be negative, such as within a
+/// [Viewport], and for moving layout points.
+#[cfg(all(not(feature = "u64_dimensions"), target_pointer_width = "32"))]
+pub type SignedDimension = i32;
+
+/// Converts a coordinate in layout units to pixels, rounding to the nearest pixel. See
+/// [Renderer::units_per_pixel].
+fn round_to_pixels(value: SignedDimension, units_per_pixel: Dimension) -> SignedDimension {
+    let units_per_pixel = units_per_pixel.max(1) as SignedDimension;
+    (value + units_per_pixel / 2).div_euclid(units_per_pixel)
+}
 
-pub type Dimension = u64;
+/// Converts a length in layout units, starting at the given coordinate, to pixels. Both ends are
+/// rounded rather than the length itself, so that items which are adjacent in the layout are also
+/// adjacent once converted, no matter how many there are.
+fn length_to_pixels(start: SignedDimension, length: Dimension, units_per_pixel: Dimension) -> Dimension {
+    (round_to_pixels(start + length as SignedDimension, units_per_pixel) - round_to_pixels(start, units_per_pixel)) as Dimension
+}
 
 /// A point relative to the top-left of the layout.
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub struct CalculatedPoint {
-    pub x: u64,
-    pub y: u64,
+    pub x: Dimension,
+    pub y: Dimension,
 }
 
 impl CalculatedPoint {
-    pub fn dx(&self, delta: i64) -> CalculatedPoint {
-        CalculatedPoint { x: (self.x as i64 + delta) as u64, y: self.y }
+    /// Moves this point horizontally.
+    ///
+    /// Panics if the point would move left of the layout's origin. Use
+    /// [checked_dx](CalculatedPoint::checked_dx) if this is possible.
+    pub fn dx(&self, delta: SignedDimension) -> CalculatedPoint {
+        self.checked_dx(delta).expect("layout point moved to a negative x coordinate")
     }
 
-    pub fn dy(&self, delta: i64) -> CalculatedPoint {
-        CalculatedPoint { x: self.x, y: (self.y as i64 + delta) as u64 }
+    /// Moves this point vertically.
+    ///
+    /// Panics if the point would move above the layout's origin. Use
+    /// [checked_dy](CalculatedPoint::checked_dy) if this is possible.
+    pub fn dy(&self, delta: SignedDimension) -> CalculatedPoint {
+        self.checked_dy(delta).expect("layout point moved to a negative y coordinate")
+    }
+
+    /// Moves this point horizontally, or returns None if it would move left of the layout's origin
+    /// (or overflow).
+    pub fn checked_dx(&self, delta: SignedDimension) -> Option<CalculatedPoint> {
+        Some(CalculatedPoint { x: self.x.checked_add_signed(delta)?, y: self.y })
+    }
+
+    /// Moves this point vertically, or returns None if it would move above the layout's origin (or
+    /// overflow).
+    pub fn checked_dy(&self, delta: SignedDimension) -> Option<CalculatedPoint> {
+        Some(CalculatedPoint { x: self.x, y: self.y.checked_add_signed(delta)? })
     }
 
     pub fn to_viewport_point(&self, viewport: Option<&Viewport>) -> ViewportPoint {
         if let Some(viewport) = viewport {
             ViewportPoint {
-                x: self.x as i64 - viewport.offset.x as i64,
-                y: self.y as i64 - viewport.offset.y as i64,
+                x: self.x as SignedDimension - viewport.offset.x as SignedDimension,
+                y: self.y as SignedDimension - viewport.offset.y as SignedDimension,
             }
         } else {
-            ViewportPoint { x: self.x as i64, y: self.y as i64 }
+            ViewportPoint { x: self.x as SignedDimension, y: self.y as SignedDimension }
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Viewport {
     pub size: Area,
     pub offset: CalculatedPoint,
@@ -48,29 +109,29 @@ impl Viewport {
     pub fn includes_point(&self, point: &ViewportPoint) -> bool {
         // The ViewportPoint is relative to the top-left anyway, so the offset doesn't matter
         point.x >= 0 && point.y >= 0
-        && point.x < self.size.width as i64 && point.y < self.size.height as i64
+        && point.x < self.size.width as SignedDimension && point.y < self.size.height as SignedDimension
     }
 
     pub fn visibility(&self, point: &ViewportPoint, area: &Area) -> ViewportVisibility {
-        let left_clip = if point.x < 0 { point.x.abs() } else { 0 } as u64;
-        let top_clip = if point.y < 0 { point.y.abs() } else { 0 } as u64;
+        let left_clip = if point.x < 0 { point.x.abs() } else { 0 } as Dimension;
+        let top_clip = if point.y < 0 { point.y.abs() } else { 0 } as Dimension;
 
-        let end_x = point.x + area.width as i64;
-        let right_clip = if end_x > self.size.width as i64 {
-            end_x - self.size.width as i64
-        } else { 0 } as u64;
+        let end_x = point.x + area.width as SignedDimension;
+        let right_clip = if end_x > self.size.width as SignedDimension {
+            end_x - self.size.width as SignedDimension
+        } else { 0 } as Dimension;
 
-        let end_y = point.y + area.height as i64;
-        let bottom_clip = if end_y > self.size.height as i64 {
-            end_y - self.size.height as i64 
-        } else { 0 } as u64;
+        let end_y = point.y + area.height as SignedDimension;
+        let bottom_clip = if end_y > self.size.height as SignedDimension {
+            end_y - self.size.height as SignedDimension 
+        } else { 0 } as Dimension;
 
         if top_clip == 0 && bottom_clip == 0 && left_clip == 0 && right_clip == 0 {
             ViewportVisibility::Visible
         } else {
             ViewportVisibility::Clipped { 
-                invisible: end_x + (area.width as i64) < 0 || end_y + (area.height as i64) < 0
-                    || point.x > self.size.width as i64 || point.y > self.size.height as i64,
+                invisible: end_x + (area.width as SignedDimension) < 0 || end_y + (area.height as SignedDimension) < 0
+                    || point.x > self.size.width as SignedDimension || point.y > self.size.height as SignedDimension,
                 top_clip, bottom_clip, left_clip, right_clip
             }
         }
@@ -80,16 +141,16 @@ impl Viewport {
 /// A point relative to the top-left of the viewport.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct ViewportPoint {
-    pub x: i64,
-    pub y: i64,
+    pub x: SignedDimension,
+    pub y: SignedDimension,
 }
 
 impl ViewportPoint {
-    pub fn dx(&self, delta: i64) -> ViewportPoint {
+    pub fn dx(&self, delta: SignedDimension) -> ViewportPoint {
         ViewportPoint { x: self.x + delta, y: self.y }
     }
 
-    pub fn dy(&self, delta: i64) -> ViewportPoint {
+    pub fn dy(&self, delta: SignedDimension) -> ViewportPoint {
         ViewportPoint { x: self.x, y: self.y + delta }
     }
 }
@@ -127,7 +188,44 @@ pub struct ViewportGlyph {
     pub visibility: ViewportVisibility,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+impl ViewportGlyph {
+    /// Converts the position, size and clipping of this glyph from layout units to pixels. See
+    /// [Renderer::units_per_pixel].
+    pub fn to_pixels(&self, units_per_pixel: Dimension) -> ViewportGlyph {
+        let ViewportPoint { x, y } = self.point;
+        let area = self.glyph.area;
+        let end_x = x + area.width as SignedDimension;
+        let end_y = y + area.height as SignedDimension;
+
+        ViewportGlyph {
+            glyph: SizedGlyph {
+                glyph: self.glyph.glyph.to_pixels(units_per_pixel),
+                area: Area {
+                    width: length_to_pixels(x, area.width, units_per_pixel),
+                    height: length_to_pixels(y, area.height, units_per_pixel),
+                },
+                ..self.glyph
+            },
+            point: ViewportPoint {
+                x: round_to_pixels(x, units_per_pixel),
+                y: round_to_pixels(y, units_per_pixel),
+            },
+            visibility: match self.visibility {
+                ViewportVisibility::Visible => ViewportVisibility::Visible,
+                ViewportVisibility::Clipped { invisible, top_clip, bottom_clip, left_clip, right_clip } =>
+                    ViewportVisibility::Clipped {
+                        invisible,
+                        top_clip: length_to_pixels(y, top_clip, units_per_pixel),
+                        bottom_clip: length_to_pixels(end_y - bottom_clip as SignedDimension, bottom_clip, units_per_pixel),
+                        left_clip: length_to_pixels(x, left_clip, units_per_pixel),
+                        right_clip: length_to_pixels(end_x - right_clip as SignedDimension, right_clip, units_per_pixel),
+                    },
+            },
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub struct Area {
     pub width: Dimension,
     pub height: Dimension,
@@ -141,32 +239,117 @@ impl Area {
     pub fn square(size: Dimension) -> Area {
         Area { width: size, height: size }
     }
+
+    /// Converts this area from layout units to pixels, rounding to the nearest pixel. See
+    /// [Renderer::units_per_pixel].
+    pub fn to_pixels(&self, units_per_pixel: Dimension) -> Area {
+        Area {
+            width: length_to_pixels(0, self.width, units_per_pixel),
+            height: length_to_pixels(0, self.height, units_per_pixel),
+        }
+    }
+
+    /// Converts this area from pixels to layout units. This can be used to create a [Viewport]
+    /// from the size of a draw surface. See [Renderer::units_per_pixel].
+    pub fn from_pixels(&self, units_per_pixel: Dimension) -> Area {
+        Area {
+            width: self.width * units_per_pixel,
+            height: self.height * units_per_pixel,
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub enum Glyph {
     Digit { number: u8 },
     Point,
 
-    FunctionName { function: Function },
+    /// The name of a function. If `parenthesised` is false, the argument follows without
+    /// parentheses, so renderers should leave a gap after the name.
+    FunctionName { function: Function, name: &'static str, parenthesised: bool },
     Comma,
 
     Variable { name: char },
+    Pi,
+    EngineeringSuffix { suffix: EngineeringSuffix },
+
+    /// A boolean literal, with the text to display for it.
+    Boolean { value: bool, name: &'static str },
     
     Add,
     Subtract,
     Multiply,
     Divide,
+    Equals,
+
+    /// A caret before the exponent of a power which is laid out inline, rather than as a
+    /// superscript. See [LayoutComputationProperties::max_superscript_depth].
+    Caret,
 
     Fraction { inner_width: Dimension },
 
-    LeftParenthesis { inner_height: Dimension },
-    RightParenthesis { inner_height: Dimension },
+    /// An opening parenthesis, spanning content of height `inner_height` whose baseline is
+    /// `inner_baseline` below its top. Renderers which assemble tall brackets from separate top,
+    /// middle and bottom pieces can use the baseline to place the middle piece, rather than
+    /// assuming that the content is vertically centred.
+    LeftParenthesis { inner_height: Dimension, inner_baseline: Dimension },
+
+    /// A closing parenthesis. See [Glyph::LeftParenthesis].
+    RightParenthesis { inner_height: Dimension, inner_baseline: Dimension },
+
+    /// A brace opening a piecewise conditional, spanning each of its cases.
+    LeftBrace { inner_height: Dimension },
+
+    /// A word of text, such as "otherwise" in a piecewise conditional. Renderers should leave a gap
+    /// either side of it.
+    Text { text: &'static str },
 
     Sqrt { inner_area: Area },
 
-    Cursor { height: Dimension },
+    /// The left-hand part of a radical symbol: a diagonal tick, and a vertical stroke rising to the
+    /// overline. Only used instead of [Glyph::Sqrt] if [Renderer::decompose_radicals] is true.
+    RadicalTick { inner_height: Dimension },
+
+    /// The horizontal line over the inner item of a radical symbol, to the right of a
+    /// [Glyph::RadicalTick].
+    RadicalOverline { inner_width: Dimension },
+
+    /// The cursor. See [CursorStyle] for how it should be drawn.
+    Cursor {
+        height: Dimension,
+
+        /// The width of the item after the cursor, or of a digit if there is nothing after it.
+        next_width: Dimension,
+
+        style: CursorStyle,
+
+        /// False during the "off" phase of a blinking cursor, in which case nothing should be
+        /// drawn. This can be changed without recomputing the layout, using
+        /// [LayoutBlock::set_cursor_visible].
+        visible: bool,
+    },
     Placeholder,
+
+    /// A wavy line drawn underneath part of a layout, usually to mark an error. See [Decoration].
+    WavyUnderline { width: Dimension },
+
+    /// A line drawn through the middle of part of a layout. See [Decoration].
+    Strikethrough { width: Dimension },
+}
+
+/// How the cursor should be drawn.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Hash)]
+pub enum CursorStyle {
+    /// A vertical bar between items.
+    #[default]
+    Bar,
+
+    /// A line underneath the item after the cursor.
+    Underline,
+
+    /// A solid block over the item after the cursor, which is typically drawn by inverting the
+    /// item's colours.
+    Block,
 }
 
 impl From<Token> for Glyph {
@@ -179,21 +362,61 @@ impl From<Token> for Glyph {
             Token::Digit(d) => Glyph::Digit { number: d },
             Token::Point => Glyph::Point,
             Token::Variable(c) => Glyph::Variable { name: c },
+            Token::Pi => Glyph::Pi,
+            Token::EngineeringSuffix(suffix) => Glyph::EngineeringSuffix { suffix },
+            Token::Boolean(value) => Glyph::Boolean { value, name: Glyph::boolean_name(value, false) },
         }
     }
 }
 
 impl Glyph {
+    /// The text to display for a boolean literal, either as a word or a symbol.
+    pub fn boolean_name(value: bool, symbol: bool) -> &'static str {
+        match (value, symbol) {
+            (true, false) => "true",
+            (false, false) => "false",
+            (true, true) => "⊤",
+            (false, true) => "⊥",
+        }
+    }
+
     pub fn to_sized(self, renderer: &mut impl Renderer, size_reduction_level: u32) -> SizedGlyph {
         SizedGlyph::from_glyph(self, renderer, size_reduction_level)
     }
+
+    /// Converts any dimensions within this glyph from layout units to pixels, rounding to the
+    /// nearest pixel. See [Renderer::units_per_pixel].
+    pub fn to_pixels(self, units_per_pixel: Dimension) -> Glyph {
+        let convert = |length| length_to_pixels(0, length, units_per_pixel);
+        match self {
+            Glyph::Fraction { inner_width } => Glyph::Fraction { inner_width: convert(inner_width) },
+            Glyph::LeftParenthesis { inner_height, inner_baseline } =>
+                Glyph::LeftParenthesis { inner_height: convert(inner_height), inner_baseline: convert(inner_baseline) },
+            Glyph::RightParenthesis { inner_height, inner_baseline } =>
+                Glyph::RightParenthesis { inner_height: convert(inner_height), inner_baseline: convert(inner_baseline) },
+            Glyph::LeftBrace { inner_height } => Glyph::LeftBrace { inner_height: convert(inner_height) },
+            Glyph::Sqrt { inner_area } => Glyph::Sqrt { inner_area: inner_area.to_pixels(units_per_pixel) },
+            Glyph::RadicalTick { inner_height } => Glyph::RadicalTick { inner_height: convert(inner_height) },
+            Glyph::RadicalOverline { inner_width } => Glyph::RadicalOverline { inner_width: convert(inner_width) },
+            Glyph::Cursor { height, next_width, style, visible } =>
+                Glyph::Cursor { height: convert(height), next_width: convert(next_width), style, visible },
+            Glyph::WavyUnderline { width } => Glyph::WavyUnderline { width: convert(width) },
+            Glyph::Strikethrough { width } => Glyph::Strikethrough { width: convert(width) },
+            _ => self,
+        }
+    }
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub struct SizedGlyph {
     pub glyph: Glyph,
     pub area: Area,
     pub size_reduction_level: u32,
+
+    /// An application-defined style for this glyph, from an enclosing
+    /// [UnstructuredNode::Styled](crate::UnstructuredNode::Styled). Glyphs outside of any styled
+    /// node have style 0.
+    pub style: u8,
 }
 
 impl SizedGlyph {
@@ -202,6 +425,7 @@ impl SizedGlyph {
             glyph,
             area: renderer.size(glyph, size_reduction_level),
             size_reduction_level,
+            style: 0,
         }
     }
 }
@@ -212,6 +436,10 @@ pub struct LayoutBlock {
     pub baseline: Dimension,
     pub area: Area,
     pub special: LayoutBlockSpecial,
+
+    /// The region occupied by each unstructured node in this layout, relative to the list which
+    /// the layout was computed for. Use [bounds_of](LayoutBlock::bounds_of) to look one up.
+    pub node_bounds: Vec<(NavPath, LayoutBounds)>,
 }
 
 
@@ -254,6 +482,59 @@ pub struct LayoutBlockSpecial {
     pub superscript: bool,
 }
 
+/// A rectangular region of a layout, such as the region occupied by one node. See
+/// [LayoutBlock::bounds_of].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct LayoutBounds {
+    /// The top-left corner of the region.
+    pub point: CalculatedPoint,
+    pub area: Area,
+}
+
+impl LayoutBounds {
+    /// Returns true if a point lies within this region, for example to find the node which was
+    /// tapped.
+    pub fn contains(&self, point: CalculatedPoint) -> bool {
+        point.x >= self.point.x && point.x < self.point.x + self.area.width
+            && point.y >= self.point.y && point.y < self.point.y + self.area.height
+    }
+}
+
+/// A glyph used in a layout, returned by [LayoutBlock::glyph_usage].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct GlyphUsage {
+    pub glyph: Glyph,
+    pub size_reduction_level: u32,
+
+    /// The size which the glyph is drawn at.
+    pub area: Area,
+
+    /// The number of times the glyph appears in the layout.
+    pub count: usize,
+}
+
+/// A line which can be drawn over part of a layout with [LayoutBlock::decorate], for example to
+/// show which part of an expression caused an error.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Decoration {
+    /// A [wavy underline](Glyph::WavyUnderline), placed below the region.
+    WavyUnderline,
+
+    /// A [strike-through](Glyph::Strikethrough), vertically centred on the region.
+    Strikethrough,
+}
+
+/// Where the result is placed relative to the expression by
+/// [layout_with_result](Renderer::layout_with_result).
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ResultPlacement {
+    /// The result is on the same line as the expression, aligned along the baseline.
+    Right,
+
+    /// The result is right-aligned on a new line below the expression.
+    Below,
+}
+
 pub enum MergeBaseline {
     SelfAsBaseline,
     OtherAsBaseline,
@@ -261,7 +542,13 @@ pub enum MergeBaseline {
 
 impl LayoutBlock {
     pub fn empty() -> LayoutBlock {
-        LayoutBlock { glyphs: vec![], baseline: 0, area: Area::new(0, 0), special: LayoutBlockSpecial::default() }
+        LayoutBlock {
+            glyphs: vec![],
+            baseline: 0,
+            area: Area::new(0, 0),
+            special: LayoutBlockSpecial::default(),
+            node_bounds: vec![],
+        }
     }
 
     pub fn new(glyphs: Vec<(SizedGlyph, CalculatedPoint)>, baseline: Dimension) -> Self {
@@ -271,6 +558,7 @@ impl LayoutBlock {
             baseline,
             area,
             special: LayoutBlockSpecial::default(),
+            node_bounds: vec![],
         }
     }
 
@@ -284,12 +572,13 @@ impl LayoutBlock {
     /// Creates a new layout block with one glyph at the origin. The baseline is the centre of this
     /// glyph.
     pub fn from_glyph(renderer: &mut impl Renderer, glyph: Glyph, properties: LayoutComputationProperties) -> LayoutBlock {
-        let glyph = glyph.to_sized(renderer, properties.size_reduction_level);
+        let glyph = SizedGlyph { style: properties.style, ..glyph.to_sized(renderer, properties.size_reduction_level) };
         LayoutBlock {
             glyphs: vec![(glyph, CalculatedPoint { x: 0, y: 0 })],
             baseline: glyph.area.height / 2,
             area: glyph.area,
             special: LayoutBlockSpecial::default(),
+            node_bounds: vec![],
         }
     }
 
@@ -299,8 +588,8 @@ impl LayoutBlock {
 
         for (glyph, point) in glyphs {
             let size = glyph.area;
-            let ex = point.x + size.width;
-            let ey = point.y + size.height;
+            let ex = point.x.saturating_add(size.width);
+            let ey = point.y.saturating_add(size.height);
             if ex > width { width = ex }
             if ey > height { height = ey }
         }
@@ -308,17 +597,46 @@ impl LayoutBlock {
         Area { width, height }
     }
 
+    /// Moves every glyph in this block right and down. Coordinates saturate rather than overflowing,
+    /// so that an absurdly large layout is clipped instead of panicking.
     pub fn offset(&self, dx: Dimension, dy: Dimension) -> LayoutBlock {
         LayoutBlock {
             glyphs: self.glyphs
                 .iter()
-                .map(|(g, p)| (*g, p.dx(dx as i64).dy(dy as i64)))
+                .map(|(g, p)| (*g, CalculatedPoint { x: p.x.saturating_add(dx), y: p.y.saturating_add(dy) }))
+                .collect(),
+            baseline: self.baseline.saturating_add(dy),
+            area: self.area,
+            special: self.special,
+            node_bounds: self.node_bounds
+                .iter()
+                .map(|(path, b)| (path.clone(), LayoutBounds {
+                    point: CalculatedPoint { x: b.point.x.saturating_add(dx), y: b.point.y.saturating_add(dy) },
+                    area: b.area,
+                }))
                 .collect(),
-            baseline: self.baseline + dy,
-            ..*self
         }.update_area()
     }
 
+    /// Moves every glyph in this block, in any direction. Returns None if any glyph, or the
+    /// baseline, would end up with a negative coordinate; in this case, the other block involved
+    /// in the layout should be moved in the opposite direction instead.
+    pub fn checked_offset(&self, dx: SignedDimension, dy: SignedDimension) -> Option<LayoutBlock> {
+        Some(LayoutBlock {
+            glyphs: self.glyphs
+                .iter()
+                .map(|(g, p)| Some((*g, p.checked_dx(dx)?.checked_dy(dy)?)))
+                .collect::<Option<Vec<_>>>()?,
+            baseline: self.baseline.checked_add_signed(dy)?,
+            area: self.area,
+            special: self.special,
+            node_bounds: self.node_bounds
+                .iter()
+                .map(|(path, b)| Some((path.clone(), LayoutBounds { point: b.point.checked_dx(dx)?.checked_dy(dy)?, area: b.area })))
+                .collect::<Option<Vec<_>>>()?,
+        }.update_area())
+    }
+
     pub fn merge_along_baseline(&self, other: &LayoutBlock) -> LayoutBlock {
         // Is the other block is a superscript? This needs to be handled totally differently...
         if other.special.superscript {
@@ -354,17 +672,11 @@ impl LayoutBlock {
 
         let baseline_difference = greater_baselined.baseline - lesser_baselined.baseline;
 
-        let glyphs =
-            // Re-align the lesser-baselined glyphs
-            lesser_baselined.glyphs
-            .iter()
-            .cloned()
-            .map(|(g, p)| (g, p.dy(baseline_difference as i64)))
-            // Chain with the unmodified greater-baselined glyphs
-            .chain(greater_baselined.glyphs.iter().cloned())
-            .collect::<Vec<_>>();
-
-        LayoutBlock::new(glyphs, greater_baselined.baseline)
+        // Re-align the lesser-baselined glyphs, and merge with the unmodified greater-baselined
+        // glyphs
+        lesser_baselined
+            .offset(0, baseline_difference)
+            .merge_in_place(greater_baselined, MergeBaseline::OtherAsBaseline)
     }
 
     /// Merges the glyphs of two layout blocks along their vertical centre.
@@ -381,20 +693,15 @@ impl LayoutBlock {
 
         let centre_difference = wider_centre - thinner_centre;
 
-        let glyphs =
-            // Re-align the lesser-baselined glyphs
-            thinner.glyphs
-            .iter()
-            .cloned()
-            .map(|(g, p)| (g, p.dx(centre_difference as i64)))
-            // Chain with the unmodified greater-baselined glyphs
-            .chain(wider.glyphs.iter().cloned())
-            .collect::<Vec<_>>();
-
-        LayoutBlock::new(glyphs, match baseline {
+        // Re-align the thinner glyphs, and merge with the unmodified wider glyphs
+        let mut merged = thinner
+            .offset(centre_difference, 0)
+            .merge_in_place(wider, MergeBaseline::SelfAsBaseline);
+        merged.baseline = match baseline {
             MergeBaseline::SelfAsBaseline => self.baseline,
             MergeBaseline::OtherAsBaseline => other.baseline,
-        })
+        };
+        merged
     }
 
     /// Merge the the glyphs of two layout blocks exactly, without moving them.
@@ -408,10 +715,13 @@ impl LayoutBlock {
             .chain(other.glyphs.iter().cloned())
             .collect::<Vec<_>>();
 
-        LayoutBlock::new(glyphs, match baseline {
-            MergeBaseline::SelfAsBaseline => self.baseline,
-            MergeBaseline::OtherAsBaseline => other.baseline,
-        })
+        LayoutBlock {
+            node_bounds: self.node_bounds.iter().chain(other.node_bounds.iter()).cloned().collect(),
+            ..LayoutBlock::new(glyphs, match baseline {
+                MergeBaseline::SelfAsBaseline => self.baseline,
+                MergeBaseline::OtherAsBaseline => other.baseline,
+            })
+        }
     }
 
     /// Assuming that two layout blocks start at the same point, returns a clone of this block moved
@@ -468,6 +778,126 @@ impl LayoutBlock {
         block
     }
 
+    /// Sets whether the cursor in this layout is drawn, if it contains one. Toggling this
+    /// periodically gives a blinking cursor, without needing to recompute the layout.
+    pub fn set_cursor_visible(&mut self, is_visible: bool) {
+        for (glyph, _) in &mut self.glyphs {
+            if let Glyph::Cursor { visible, .. } = &mut glyph.glyph {
+                *visible = is_visible;
+            }
+        }
+    }
+
+    /// Returns the smallest region containing every glyph with the given
+    /// [style](SizedGlyph::style), or None if there are no such glyphs.
+    pub fn bounds_of_style(&self, style: u8) -> Option<LayoutBounds> {
+        Self::glyph_bounds(self.glyphs.iter().filter(|(g, _)| g.style == style))
+    }
+
+    /// Returns the region occupied by one unstructured node in this layout, or None if there is no
+    /// node at the path, or it has no glyphs. The path is interpreted in the same way as a cursor
+    /// position, so the node after the cursor is used. For example, [3, 0, 0] is the first node in
+    /// the first slot of the fourth node.
+    ///
+    /// This can be used to implement features such as tap-to-select, or highlighting part of an
+    /// expression which caused an error.
+    pub fn bounds_of(&self, path: &NavPath) -> Option<LayoutBounds> {
+        self.node_bounds.iter().find(|(p, _)| p == path).map(|(_, bounds)| *bounds)
+    }
+
+    /// Records this block as the layout of the node at `index` in an unstructured list, so that it
+    /// can be found with [bounds_of](LayoutBlock::bounds_of). Any nodes already recorded in this
+    /// block are within the node's slots, and are moved inside it.
+    pub(crate) fn record_node(mut self, index: usize) -> LayoutBlock {
+        for (path, _) in &mut self.node_bounds {
+            path.push_front(index);
+        }
+        if let Some(bounds) = Self::glyph_bounds(self.glyphs.iter()) {
+            self.node_bounds.push((NavPath::new(vec![index]), bounds));
+        }
+        self
+    }
+
+    /// Moves any nodes recorded in this block into the given slot of the node being laid out. This
+    /// must be used on the layout of each of a node's slots, before
+    /// [record_node](LayoutBlock::record_node) is used on the node itself.
+    pub(crate) fn in_slot(mut self, slot: usize) -> LayoutBlock {
+        for (path, _) in &mut self.node_bounds {
+            path.push_front(slot);
+        }
+        self
+    }
+
+    /// Returns the smallest region containing every one of the given glyphs, or None if there are
+    /// no glyphs.
+    fn glyph_bounds<'a>(glyphs: impl Iterator<Item = &'a (SizedGlyph, CalculatedPoint)>) -> Option<LayoutBounds> {
+        let mut bounds: Option<(CalculatedPoint, CalculatedPoint)> = None;
+        for (glyph, point) in glyphs {
+            let end = CalculatedPoint { x: point.x + glyph.area.width, y: point.y + glyph.area.height };
+            bounds = Some(match bounds {
+                Some((start, bounds_end)) => (
+                    CalculatedPoint { x: start.x.min(point.x), y: start.y.min(point.y) },
+                    CalculatedPoint { x: bounds_end.x.max(end.x), y: bounds_end.y.max(end.y) },
+                ),
+                None => (*point, end),
+            });
+        }
+
+        bounds.map(|(start, end)| LayoutBounds {
+            point: start,
+            area: Area::new(end.x - start.x, end.y - start.y),
+        })
+    }
+
+    /// Adds a decoration spanning the width of a region of this layout, such as one returned by
+    /// [LayoutBlock::bounds_of]. An underline may make the layout taller.
+    ///
+    /// Decorations are added after every other glyph, so are drawn on top of them.
+    pub fn decorate(&mut self, renderer: &mut impl Renderer, bounds: LayoutBounds, decoration: Decoration, properties: LayoutComputationProperties) {
+        let width = bounds.area.width;
+        let (glyph, y) = match decoration {
+            Decoration::WavyUnderline => (
+                Glyph::WavyUnderline { width }.to_sized(renderer, properties.size_reduction_level),
+                bounds.point.y + bounds.area.height,
+            ),
+            Decoration::Strikethrough => {
+                let glyph = Glyph::Strikethrough { width }.to_sized(renderer, properties.size_reduction_level);
+                (glyph, bounds.point.y + bounds.area.height.saturating_sub(glyph.area.height) / 2)
+            },
+        };
+
+        self.glyphs.push((
+            SizedGlyph { style: properties.style, ..glyph },
+            CalculatedPoint { x: bounds.point.x, y },
+        ));
+        self.area = Self::area(&self.glyphs);
+    }
+
+    /// Returns each distinct combination of glyph and size reduction level used in this layout,
+    /// with the size it is drawn at and how many times it appears, in the order that each first
+    /// appears.
+    ///
+    /// Renderers with limited memory can use this to prepare only the glyphs which are needed,
+    /// such as by rasterising them into an atlas, before drawing the layout.
+    pub fn glyph_usage(&self) -> Vec<GlyphUsage> {
+        let mut usage: Vec<GlyphUsage> = vec![];
+        for (glyph, _) in &self.glyphs {
+            let existing = usage.iter_mut()
+                .find(|u| u.glyph == glyph.glyph && u.size_reduction_level == glyph.size_reduction_level);
+            if let Some(existing) = existing {
+                existing.count += 1;
+            } else {
+                usage.push(GlyphUsage {
+                    glyph: glyph.glyph,
+                    size_reduction_level: glyph.size_reduction_level,
+                    area: glyph.area,
+                    count: 1,
+                });
+            }
+        }
+        usage
+    }
+
     pub fn for_viewport(&self, viewport: Option<&Viewport>) -> Vec<ViewportGlyph> {
         self.glyphs
             .iter()
@@ -489,20 +919,86 @@ impl LayoutBlock {
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub struct LayoutComputationProperties {
-    pub size_reduction_level: u32
+    pub size_reduction_level: u32,
+
+    /// The highest size reduction level which glyphs are drawn at. Items nested more deeply, such
+    /// as the exponent of an exponent of an exponent, are drawn at this level rather than smaller.
+    pub max_size_reduction_level: u32,
+
+    /// The number of powers which the item being laid out is the exponent of.
+    pub superscript_depth: u32,
+
+    /// The number of powers which can be nested as superscripts. Powers nested more deeply than
+    /// this are laid out inline, with a caret before the exponent, like `2^(3^4)`.
+    pub max_superscript_depth: u32,
+
+    /// The names to display for functions.
+    pub function_names: &'static FunctionNameTable,
+
+    /// If true, the parentheses around a function's argument are left out when the argument is a
+    /// single number, variable or π, for example `sin 30`. Parentheses are kept for compound
+    /// arguments, such as `sin(30+x)`, and when something other than an operator follows the call,
+    /// so that `sin(30)5` isn't drawn as `sin 305`.
+    pub elide_function_parentheses: bool,
+
+    /// If true, function names are vertically centred against their arguments, rather than sitting
+    /// on the arguments' baseline. This suits fonts whose names look out of place beside a tall
+    /// argument with most of its height below the baseline, like `gcd(1/(2/3), 4)`.
+    pub centre_function_names: bool,
+
+    /// The style given to glyphs, set by [UnstructuredNode::Styled](crate::UnstructuredNode::Styled).
+    pub style: u8,
+
+    /// How the cursor should be drawn.
+    pub cursor_style: CursorStyle,
+
+    /// If true, boolean literals and logical functions are displayed as symbols, like `⊤` and `∧`,
+    /// rather than as words.
+    pub boolean_symbols: bool,
+
+    /// If true, [conditionals](crate::StructuredNode::Conditional) are displayed as a piecewise
+    /// brace with each case on its own line. Otherwise, they are displayed inline as a call to
+    /// `if`.
+    pub piecewise_conditionals: bool,
 }
 
 impl Default for LayoutComputationProperties {
     fn default() -> Self {
         LayoutComputationProperties {
             size_reduction_level: 0,
+            max_size_reduction_level: 3,
+            superscript_depth: 0,
+            max_superscript_depth: 4,
+            function_names: &FunctionNameTable::DEFAULT,
+            elide_function_parentheses: false,
+            centre_function_names: false,
+            style: 0,
+            cursor_style: CursorStyle::Bar,
+            boolean_symbols: false,
+            piecewise_conditionals: false,
         }
     }
 }
 
 impl LayoutComputationProperties {
+    /// Returns these properties with the size reduction level increased by one, unless it is
+    /// already at [max_size_reduction_level](Self::max_size_reduction_level).
     pub fn reduce_size(self) -> Self {
-        Self { size_reduction_level: self.size_reduction_level + 1, ..self }
+        Self {
+            size_reduction_level: min(self.size_reduction_level + 1, self.max_size_reduction_level),
+            ..self
+        }
+    }
+
+    /// Returns these properties for laying out the exponent of a power.
+    pub fn enter_superscript(self) -> Self {
+        Self { superscript_depth: self.superscript_depth.saturating_add(1), ..self }
+    }
+
+    /// Returns true if a power laid out with these properties has reached
+    /// [max_superscript_depth](Self::max_superscript_depth), and should be laid out inline.
+    pub fn is_linear_power(&self) -> bool {
+        self.superscript_depth >= self.max_superscript_depth
     }
 }
 
@@ -510,6 +1006,39 @@ pub trait Layoutable {
     /// Computes the layout for a node tree, converting it into a set of glyphs at particular 
     /// locations.
     fn layout(&self, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock;
+
+    /// Returns an upper bound on the number of glyphs which [layout](Layoutable::layout) will
+    /// produce for this item, not including the cursor. This is much cheaper to compute than the
+    /// layout itself, so can be used to reject a layout before computing it.
+    ///
+    /// The default implementation knows nothing about the item, so returns `usize::MAX`. This
+    /// means items which don't override it are always rejected by
+    /// [try_layout](Renderer::try_layout), rather than risking a layout which is too large.
+    fn glyph_count_bound(&self) -> usize {
+        usize::MAX
+    }
+}
+
+/// Allows borrowed items to be laid out, so that layout helpers which take a slice of items can be
+/// given references to nodes without cloning them.
+impl<T: Layoutable> Layoutable for &T {
+    fn layout(&self, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock {
+        (**self).layout(renderer, path, properties)
+    }
+
+    fn glyph_count_bound(&self) -> usize {
+        (**self).glyph_count_bound()
+    }
+}
+
+/// Limits on the size of a layout computed by [try_layout](Renderer::try_layout).
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct LayoutLimits {
+    /// The maximum number of glyphs which the layout may contain, including the cursor.
+    pub max_glyphs: usize,
+
+    /// The maximum area which the layout may occupy, or None if there is no limit.
+    pub max_area: Option<Area>,
 }
 
 pub trait Renderer {
@@ -529,6 +1058,28 @@ pub trait Renderer {
         root.layout(self, path, properties)
     }
 
+    /// Computes the layout for a node tree like [layout](Renderer::layout), but returns an error
+    /// rather than a layout if it would exceed the given limits.
+    ///
+    /// On `no_std` targets, an allocation failure while laying out a pathologically large tree will
+    /// abort. The glyph limit is checked before any layout is computed, so this can be used to
+    /// show an error message instead.
+    fn try_layout(&mut self, root: &impl Layoutable, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties, limits: LayoutLimits) -> Result<LayoutBlock, LayoutError> where Self: Sized {
+        let cursor_glyphs = if path.is_some() { 1 } else { 0 };
+        if root.glyph_count_bound().saturating_add(cursor_glyphs) > limits.max_glyphs {
+            return Err(LayoutError::TooManyGlyphs)
+        }
+
+        let layout = self.layout(root, path, properties);
+        if let Some(max_area) = limits.max_area {
+            if layout.area.width > max_area.width || layout.area.height > max_area.height {
+                return Err(LayoutError::TooLarge)
+            }
+        }
+
+        Ok(layout)
+    }
+
     /// Initialises the graphics surface and draws a node tree onto it.
     fn draw_all(&mut self, root: &impl Layoutable, path: Option<&mut NavPathNavigator>, viewport: Option<&Viewport>) -> LayoutBlock where Self: Sized {
         let layout = self.layout(root, path, LayoutComputationProperties::default()); 
@@ -538,6 +1089,9 @@ pub trait Renderer {
 
     /// Initializes the graphics surface and draws a node tree onto it, assuming that a layout has
     /// already been calculated.
+    ///
+    /// The surface size and glyphs are converted to pixels before being passed to
+    /// [init](Renderer::init) and [draw](Renderer::draw).
     fn draw_all_by_layout(&mut self, layout: &LayoutBlock, viewport: Option<&Viewport>) where Self: Sized {
         let area = if let Some(v) = viewport {
             v.size
@@ -547,10 +1101,86 @@ pub trait Renderer {
 
         let viewport_glyphs = layout.for_viewport(viewport);
 
-        self.init(area);
+        let units_per_pixel = self.units_per_pixel().max(1);
+        self.init(area.to_pixels(units_per_pixel));
         for glyph in viewport_glyphs {
-            self.draw(glyph);
+            self.draw(glyph.to_pixels(units_per_pixel));
+        }
+    }
+
+    /// Computes a layout which shows an expression, followed by an equals sign and its result.
+    /// This is useful for rendering an input line alongside its answer.
+    ///
+    /// The result is placed to the right of the expression along the baseline, unless this would
+    /// make the layout wider than `max_width`, in which case it is placed below instead. The
+    /// placement which was used is returned alongside the layout.
+    ///
+    /// The result can be any layoutable item, but this will often be an
+    /// [UnstructuredNodeRoot](crate::UnstructuredNodeRoot) created with
+    /// [from_number](crate::UnstructuredNodeRoot::from_number).
+    fn layout_with_result(
+        &mut self,
+        expression: &impl Layoutable,
+        path: Option<&mut NavPathNavigator>,
+        result: &impl Layoutable,
+        max_width: Option<Dimension>,
+        properties: LayoutComputationProperties,
+    ) -> (LayoutBlock, ResultPlacement) where Self: Sized {
+        let expression_layout = self.layout(expression, path, properties);
+        let result_layout = LayoutBlock::layout_horizontal(&[
+            LayoutBlock::from_glyph(self, Glyph::Equals, properties),
+            self.layout(result, None, properties),
+        ]);
+
+        let right_layout = LayoutBlock::layout_horizontal(&[
+            expression_layout.clone(),
+            result_layout.clone(),
+        ]);
+        if !matches!(max_width, Some(w) if right_layout.area.width > w) {
+            return (right_layout, ResultPlacement::Right);
+        }
+
+        // Right-align the result under the expression, or shift the expression if the result is
+        // the wider of the two
+        let expression_width = expression_layout.area.width;
+        let result_width = result_layout.area.width;
+        let expression_layout = expression_layout.offset(result_width.saturating_sub(expression_width), 0);
+        let result_layout = result_layout
+            .offset(expression_width.saturating_sub(result_width), 0)
+            .move_below_other(&expression_layout);
+        (
+            expression_layout.merge_in_place(&result_layout, MergeBaseline::SelfAsBaseline),
+            ResultPlacement::Below,
+        )
+    }
+
+    /// Returns the region occupied by one node of a tree in its layout, or None if there is no node
+    /// at the path, or it has no glyphs. See [LayoutBlock::bounds_of] for how paths are
+    /// interpreted.
+    ///
+    /// The layout is computed with the given cursor and properties, which should match those used
+    /// to draw the tree. To find the regions of many nodes at once, compute the layout once and
+    /// use [LayoutBlock::bounds_of] instead.
+    fn bounds_of(&mut self, root: &UnstructuredNodeRoot, path: &NavPath, cursor: Option<&NavPath>, properties: LayoutComputationProperties) -> Option<LayoutBounds> where Self: Sized {
+        let mut navigator = cursor.map(NavPath::to_navigator);
+        self.layout(root, navigator.as_mut(), properties).bounds_of(path)
+    }
+
+    /// Computes the layout for a tree, and [decorates](LayoutBlock::decorate) the node at each of
+    /// the given paths. Paths which don't point to a node with glyphs are ignored. See
+    /// [LayoutBlock::bounds_of] for how paths are interpreted.
+    ///
+    /// This can be used to show an error inline, for example by underlining the function call
+    /// which an evaluation error came from.
+    fn layout_decorated(&mut self, root: &UnstructuredNodeRoot, cursor: Option<&NavPath>, decorations: &[(NavPath, Decoration)], properties: LayoutComputationProperties) -> LayoutBlock where Self: Sized {
+        let mut navigator = cursor.map(NavPath::to_navigator);
+        let mut layout = self.layout(root, navigator.as_mut(), properties);
+        for (path, decoration) in decorations {
+            if let Some(bounds) = layout.bounds_of(path) {
+                layout.decorate(self, bounds, *decoration, properties);
+            }
         }
+        layout
     }
 
     /// Returns the visibility of the cursor when rendering a set of nodes in a viewport.
@@ -569,5 +1199,29 @@ pub trait Renderer {
 
     /// An overridable special option: the padding from the right of a square root node where the
     /// inner expression should be rendered. 
-    fn square_root_padding(&self) -> u64 { 0 }
+    fn square_root_padding(&self) -> Dimension { 0 }
+
+    /// An overridable special option: if true, radical symbols are laid out as a
+    /// [RadicalTick](Glyph::RadicalTick) and a [RadicalOverline](Glyph::RadicalOverline), each
+    /// sized and positioned by the layout, instead of as a single [Sqrt](Glyph::Sqrt) glyph which
+    /// the renderer must draw in its entirety. This suits renderers which only draw simple lines.
+    ///
+    /// The [square_root_padding](Renderer::square_root_padding) is added to the overline's width.
+    fn decompose_radicals(&self) -> bool { false }
+
+    /// An overridable special option: the number of layout units in one pixel of the draw surface.
+    ///
+    /// Layouts are computed with integer [Dimension]s. Renderers which measure glyphs with
+    /// fractional sizes, such as from fonts on high-DPI displays, can return a value greater than
+    /// 1 and give the sizes from [size](Renderer::size) (and
+    /// [square_root_padding](Renderer::square_root_padding)) in these smaller units. Layout and
+    /// viewport calculations then take place in layout units, so rounding errors don't accumulate
+    /// across a long expression and shift its glyphs or baselines, and positions are rounded to
+    /// pixels only once, when the glyphs are drawn.
+    ///
+    /// Viewports are also measured in layout units - [Area::from_pixels] can be used to convert
+    /// the size of a draw surface.
+    ///
+    /// This must be at least 1. A value of 0 is treated as 1.
+    fn units_per_pixel(&self) -> Dimension { 1 }
 }
@@ -1,5 +1,5 @@
-use crate::render::{Area, Glyph, Renderer, ViewportGlyph, ViewportPoint, ViewportVisibility};
-use alloc::{vec::Vec, string::{String, ToString}};
+use crate::{nav::NavPath, render::{Area, Dimension, SignedDimension, Glyph, Renderer, ViewportGlyph, ViewportPoint, ViewportVisibility, LayoutComputationProperties}, Token, UnstructuredNode, UnstructuredNodeList, UnstructuredNodeRoot};
+use alloc::{format, vec, vec::Vec, string::{String, ToString}};
 
 #[derive(Default, Clone, Debug)]
 pub struct AsciiRenderer {
@@ -8,33 +8,196 @@ pub struct AsciiRenderer {
 
 impl AsciiRenderer {
     fn put_char(&mut self, char: char, point: ViewportPoint) {
-        self.lines[point.y as usize].replace_range(
-            (point.x as usize)..(point.x as usize + 1),
+        // Lines may contain multi-byte characters like π, so find the byte range of the character
+        // at this point
+        let line = &mut self.lines[point.y as usize];
+        let (start, existing) = line.char_indices().nth(point.x as usize).unwrap();
+        line.replace_range(
+            start..(start + existing.len_utf8()),
             &char.to_string()
         );
     }
+
+    /// Returns true if nothing has been drawn at a point yet.
+    fn is_blank(&self, point: ViewportPoint) -> bool {
+        self.lines[point.y as usize].chars().nth(point.x as usize) == Some(' ')
+    }
+
+    /// Draws a node tree like [draw_all](Renderer::draw_all), but reflows it so that each line is
+    /// at most `max_width` characters wide where possible. This is useful for printing large
+    /// expressions to logs or serial consoles.
+    ///
+    /// The expression is broken before additions and subtractions at the top level of the tree,
+    /// and each line except the last ends with a `\` continuation marker on its baseline. A single
+    /// term which is wider than `max_width` is placed on its own line, without being broken.
+    pub fn draw_wrapped(&mut self, root: &UnstructuredNodeRoot, max_width: usize, properties: LayoutComputationProperties) {
+        // Split into terms, keeping unary minuses with the term they belong to
+        let mut terms: Vec<UnstructuredNodeList> = vec![];
+        let mut previous_is_operator = true;
+        for node in &root.root.items {
+            let is_operator = matches!(node, UnstructuredNode::Token(Token::Add | Token::Subtract | Token::Multiply | Token::Divide));
+            let starts_term = matches!(node, UnstructuredNode::Token(Token::Add | Token::Subtract)) && !previous_is_operator;
+            match terms.last_mut() {
+                Some(term) if !starts_term => term.items.push(node.clone()),
+                _ => terms.push(UnstructuredNodeList { items: vec![node.clone()] }),
+            }
+            previous_is_operator = is_operator;
+        }
+
+        // Greedily pack terms into lines, leaving room for the continuation marker
+        let mut line_groups: Vec<UnstructuredNodeList> = vec![];
+        let mut current_width = 0;
+        for term in terms {
+            let width = self.layout(&term, None, properties).area.width as usize;
+            match line_groups.last_mut() {
+                Some(group) if current_width + width < max_width => {
+                    group.items.extend(term.items);
+                    current_width += width;
+                },
+                _ => {
+                    line_groups.push(term);
+                    current_width = width;
+                },
+            }
+        }
+        if line_groups.is_empty() {
+            line_groups.push(UnstructuredNodeList::new());
+        }
+
+        let mut lines = vec![];
+        let group_count = line_groups.len();
+        for (i, group) in line_groups.into_iter().enumerate() {
+            let layout = self.layout(&group, None, properties);
+            self.draw_all_by_layout(&layout, None);
+            if i < group_count - 1 {
+                for (row, line) in self.lines.iter_mut().enumerate() {
+                    line.push(if row as Dimension == layout.baseline { '\\' } else { ' ' });
+                }
+            }
+            lines.append(&mut self.lines);
+        }
+        self.lines = lines;
+    }
+
+    /// Draws a node tree with diagnostic information, which is useful when developing new node
+    /// types or renderers.
+    ///
+    /// The expression is drawn in a frame showing the bounds of its layout, with `>` and `<`
+    /// marking the baseline. Below it, each node in the tree is listed with its path, formatted as
+    /// a [NavPath], and the bounding box of its glyphs.
+    ///
+    /// Nodes are identified using [styles](crate::UnstructuredNode::Styled), so any styles in the
+    /// tree are ignored, and only the first 255 nodes are listed.
+    pub fn draw_debug(&mut self, root: &UnstructuredNodeRoot, properties: LayoutComputationProperties) {
+        let mut root = root.clone();
+        let mut nodes = vec![];
+        Self::tag_debug_nodes(&mut root.root, &mut vec![], None, &mut nodes);
+
+        let layout = self.layout(&root, None, LayoutComputationProperties { style: 0, ..properties });
+        self.draw_all_by_layout(&layout, None);
+
+        // Find the bounds of each node from the glyphs within it, and any nested nodes
+        let mut bounds: Vec<Option<(Dimension, Dimension, Dimension, Dimension)>> = vec![None; nodes.len()];
+        for (glyph, point) in &layout.glyphs {
+            let mut id = (glyph.style as usize).checked_sub(1);
+            while let Some(i) = id {
+                let (x1, y1) = (point.x + glyph.area.width, point.y + glyph.area.height);
+                bounds[i] = Some(match bounds[i] {
+                    Some((bx, by, bx1, by1)) => (bx.min(point.x), by.min(point.y), bx1.max(x1), by1.max(y1)),
+                    None => (point.x, point.y, x1, y1),
+                });
+                id = nodes[i].1;
+            }
+        }
+
+        let border = format!("+{}+", "-".repeat(layout.area.width as usize));
+        let mut lines = vec![border.clone()];
+        for (row, line) in self.lines.iter().enumerate() {
+            if row as Dimension == layout.baseline {
+                lines.push(format!(">{}<", line));
+            } else {
+                lines.push(format!("|{}|", line));
+            }
+        }
+        lines.push(border);
+
+        for ((path, _, kind), bounds) in nodes.iter().zip(bounds) {
+            lines.push(match bounds {
+                Some((x, y, x1, y1)) => format!("{:?} {} at ({}, {}), {}x{}", path, kind, x, y, x1 - x, y1 - y),
+                None => format!("{:?} {} has no glyphs", path, kind),
+            });
+        }
+
+        self.lines = lines;
+    }
+
+    /// Wraps each node of a list in a styled node with a unique ID, recording the path, parent ID
+    /// and description of each node in `nodes`. The style of each node is its ID plus one.
+    fn tag_debug_nodes(list: &mut UnstructuredNodeList, path: &mut Vec<usize>, parent: Option<usize>, nodes: &mut Vec<(NavPath, Option<usize>, String)>) {
+        for (i, node) in list.items.iter_mut().enumerate() {
+            let id = nodes.len();
+            if id >= u8::MAX as usize {
+                return
+            }
+
+            path.push(i);
+            nodes.push((NavPath::new(path.clone()), parent, match node {
+                UnstructuredNode::Token(token) => format!("{:?}", token),
+                UnstructuredNode::Sqrt(_) => "Sqrt".to_string(),
+                UnstructuredNode::Root(_, _) => "Root".to_string(),
+                UnstructuredNode::Fraction(_, _) => "Fraction".to_string(),
+                UnstructuredNode::Parentheses(_) => "Parentheses".to_string(),
+                UnstructuredNode::Power(_) => "Power".to_string(),
+                UnstructuredNode::PowerOfTen(_) => "PowerOfTen".to_string(),
+                UnstructuredNode::FunctionCall(func, _) => format!("FunctionCall({:?})", func),
+                UnstructuredNode::Styled(_, _) => "Styled".to_string(),
+            }));
+
+            for (slot, inner) in node.slots_mut().into_iter().enumerate() {
+                path.push(slot);
+                Self::tag_debug_nodes(inner, path, Some(id), nodes);
+                path.pop();
+            }
+            path.pop();
+
+            // Existing styles are replaced rather than wrapped, so they don't leak into the
+            // contents' bounds
+            if let UnstructuredNode::Styled(style, _) = node {
+                *style = id as u8 + 1;
+            } else {
+                node.wrap_styled(id as u8 + 1);
+            }
+        }
+    }
 }
 
 impl Renderer for AsciiRenderer {
     fn size(&mut self, glyph: Glyph, _: u32) -> Area {
         match glyph {
-            Glyph::Digit { .. } | Glyph::Point | Glyph::Variable { .. } | Glyph::Add | Glyph::Subtract | Glyph::Multiply | Glyph::Divide | Glyph::Comma => Area::square(1),
+            Glyph::Digit { .. } | Glyph::Point | Glyph::Variable { .. } | Glyph::Pi | Glyph::EngineeringSuffix { .. } | Glyph::Add | Glyph::Subtract | Glyph::Multiply | Glyph::Divide | Glyph::Equals | Glyph::Caret | Glyph::Comma => Area::square(1),
 
             Glyph::Fraction { inner_width } => Area::new(inner_width, 1),
 
             Glyph::Sqrt { inner_area } => Area::new(inner_area.width + 3, inner_area.height + 1),
+            Glyph::RadicalTick { inner_height } => Area::new(2, inner_height),
+            Glyph::RadicalOverline { inner_width } => Area::new(inner_width, 1),
 
-            Glyph::LeftParenthesis { inner_height } | Glyph::RightParenthesis { inner_height }
+            Glyph::LeftParenthesis { inner_height, .. } | Glyph::RightParenthesis { inner_height, .. }
                 => Area::new(1, inner_height),
 
-            Glyph::FunctionName { function } => Area::new(function.render_name().len() as u64, 1),
+            Glyph::FunctionName { name, parenthesised, .. }
+                => Area::new(name.chars().count() as Dimension + if parenthesised { 0 } else { 1 }, 1),
+            Glyph::Boolean { name, .. } => Area::new(name.chars().count() as Dimension, 1),
+            Glyph::Text { text } => Area::new(text.chars().count() as Dimension + 2, 1),
+            Glyph::LeftBrace { inner_height } => Area::new(1, inner_height),
 
-            Glyph::Cursor { height } => Area::new(1, height),
+            Glyph::Cursor { height, .. } => Area::new(1, height),
             Glyph::Placeholder => Area::new(1, 1),
+            Glyph::WavyUnderline { width } | Glyph::Strikethrough { width } => Area::new(width, 1),
         }
     }
 
-    fn square_root_padding(&self) -> u64 { 1 }
+    fn square_root_padding(&self) -> Dimension { 1 }
 
     fn init(&mut self, size: Area) {
         self.lines = Vec::new();
@@ -92,64 +255,111 @@ impl Renderer for AsciiRenderer {
             Glyph::Point => self.put_char('.', point),
             Glyph::Comma => self.put_char(',', point),
             Glyph::Variable { name } => self.put_char(name, point),
+            Glyph::Pi => self.put_char('π', point),
+            Glyph::EngineeringSuffix { suffix } => self.put_char(suffix.symbol(), point),
             Glyph::Add => self.put_char('+', point),
             Glyph::Subtract => self.put_char('-', point),
             Glyph::Multiply => self.put_char('*', point),
             Glyph::Divide => self.put_char('/', point),
+            Glyph::Equals => self.put_char('=', point),
+            Glyph::Caret => self.put_char('^', point),
             Glyph::Fraction { inner_width } => {
                 for dx in 0..inner_width {
-                    self.put_char('-', point.dx(dx as i64))
+                    self.put_char('-', point.dx(dx as SignedDimension))
                 }
             },
-            Glyph::LeftParenthesis { inner_height } => {
+            Glyph::LeftParenthesis { inner_height, .. } => {
                 if inner_height == 1 {
                     self.put_char('(', point)
                 } else {
                     self.put_char('/', point);
                     for dy in 1..(inner_height - 1) {
-                        self.put_char('|', point.dy(dy as i64))
+                        self.put_char('|', point.dy(dy as SignedDimension))
                     }
-                    self.put_char('\\', point.dy(inner_height as i64 - 1));
+                    self.put_char('\\', point.dy(inner_height as SignedDimension - 1));
                 }
             },
-            Glyph::RightParenthesis { inner_height } => {
+            Glyph::RightParenthesis { inner_height, .. } => {
                 if inner_height == 1 {
                     self.put_char(')', point)
                 } else {
                     self.put_char('\\', point);
                     for dy in 1..(inner_height - 1) {
-                        self.put_char('|', point.dy(dy as i64));
+                        self.put_char('|', point.dy(dy as SignedDimension));
+                    }
+                    self.put_char('/', point.dy(inner_height as SignedDimension - 1));
+                }
+            },
+            Glyph::LeftBrace { inner_height } => {
+                if inner_height == 1 {
+                    self.put_char('{', point)
+                } else {
+                    self.put_char('/', point);
+                    for dy in 1..(inner_height - 1) {
+                        self.put_char('|', point.dy(dy as SignedDimension))
                     }
-                    self.put_char('/', point.dy(inner_height as i64 - 1));
+                    self.put_char('\\', point.dy(inner_height as SignedDimension - 1));
+                }
+            },
+            Glyph::Text { text } => {
+                for (dx, c) in text.chars().enumerate() {
+                    self.put_char(c, point.dx(dx as SignedDimension + 1))
                 }
             },
             Glyph::Sqrt { inner_area } => {
                 self.put_char('\\', ViewportPoint {
                     x: point.x,
-                    y: point.y + inner_area.height as i64,
+                    y: point.y + inner_area.height as SignedDimension,
                 });
                 for dy in 1..=inner_area.height {
-                    self.put_char('|', point.dx(1).dy(dy as i64));
+                    self.put_char('|', point.dx(1).dy(dy as SignedDimension));
                 }
                 self.put_char('.', point.dx(1));
                 for dx in 2..(2+inner_area.width) {
-                    self.put_char('-', point.dx(dx as i64));
+                    self.put_char('-', point.dx(dx as SignedDimension));
+                }
+                self.put_char('.', point.dx(inner_area.width as SignedDimension + 2));
+                self.put_char('\'', point.dx(inner_area.width as SignedDimension + 2).dy(1));
+            },
+            Glyph::RadicalTick { inner_height } => {
+                self.put_char('\\', point.dy(inner_height as SignedDimension - 1));
+                for dy in 1..inner_height {
+                    self.put_char('|', point.dx(1).dy(dy as SignedDimension));
+                }
+                self.put_char('.', point.dx(1));
+            },
+            Glyph::RadicalOverline { inner_width } => {
+                for dx in 0..inner_width {
+                    self.put_char('-', point.dx(dx as SignedDimension));
                 }
-                self.put_char('.', point.dx(inner_area.width as i64 + 2));
-                self.put_char('\'', point.dx(inner_area.width as i64 + 2).dy(1));
             },
-            Glyph::Cursor { height } => {
+            // Characters can't be drawn over each other, so every cursor style is drawn as a bar
+            Glyph::Cursor { height, visible, .. } => {
                 for dy in 0..height {
-                    self.put_char('|', point.dy(dy as i64))
+                    self.put_char(if visible { '|' } else { ' ' }, point.dy(dy as SignedDimension))
                 }
             },
-            Glyph::FunctionName { function } => {
-                let chars = function.render_name().chars().collect::<Vec<_>>();
+            Glyph::FunctionName { name, .. } | Glyph::Boolean { name, .. } => {
+                let chars = name.chars().collect::<Vec<_>>();
                 for dx in 0..chars.len() {
-                    self.put_char(chars[dx], point.dx(dx as i64))
+                    self.put_char(chars[dx], point.dx(dx as SignedDimension))
                 }
             }
             Glyph::Placeholder => self.put_char('X', point),
+            Glyph::WavyUnderline { width } => {
+                for dx in 0..width {
+                    self.put_char('~', point.dx(dx as SignedDimension));
+                }
+            },
+            // Characters can't be drawn over each other, so this only fills the gaps between them
+            Glyph::Strikethrough { width } => {
+                for dx in 0..width {
+                    let point = point.dx(dx as SignedDimension);
+                    if self.is_blank(point) {
+                        self.put_char('-', point);
+                    }
+                }
+            },
         }
     }
 }
@@ -1,9 +1,111 @@
-use alloc::{vec::Vec, vec};
+use core::cmp::min;
+
+use alloc::vec::Vec;
+
+use crate::error::DeserializeError;
+
+/// A minimal source of bytes which items can be deserialized from.
+///
+/// This is designed to be simple to implement for storage which is read a chunk at a time, like
+/// flash memory or a serial port, so that a serialized node tree never needs to be copied into a
+/// buffer in its entirety before it is deserialized.
+pub trait ByteRead {
+    /// Reads up to `buf.len()` bytes into the start of `buf`, and returns the number of bytes read.
+    /// Returning 0 for a non-empty `buf` indicates that the end of the input has been reached.
+    fn read(&mut self, buf: &mut [u8]) -> usize;
+
+    /// Reads exactly enough bytes to fill `buf`, or returns
+    /// [DeserializeError::UnexpectedEof] if the input ends first.
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DeserializeError> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let read = self.read(&mut buf[filled..]);
+            if read == 0 {
+                return Err(DeserializeError::UnexpectedEof);
+            }
+            filled += read;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a single byte, or returns [DeserializeError::UnexpectedEof] if the input has ended.
+    fn read_u8(&mut self) -> Result<u8, DeserializeError> {
+        let mut buf = [0];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+}
+
+impl ByteRead for &[u8] {
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let len = min(buf.len(), self.len());
+        buf[..len].copy_from_slice(&self[..len]);
+        *self = &self[len..];
+        len
+    }
+}
+
+/// Adapts an iterator of bytes into a [ByteRead].
+pub struct IteratorByteReader<'a> {
+    pub iter: &'a mut dyn Iterator<Item = u8>,
+}
+
+impl<'a> ByteRead for IteratorByteReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for slot in buf.iter_mut() {
+            if let Some(byte) = self.iter.next() {
+                *slot = byte;
+                len += 1;
+            } else {
+                break;
+            }
+        }
+        len
+    }
+}
 
 pub trait Serializable where Self: Sized {
-    fn serialize(&self) -> Vec<u8>;
+    /// Serializes this item, appending the bytes to the end of `bytes`.
+    ///
+    /// Implementations should write directly into `bytes` rather than building temporary vecs, so
+    /// that serializing a whole node tree can reuse a single buffer.
+    fn serialize_into(&self, bytes: &mut Vec<u8>);
+
+    /// Returns the number of bytes which [serialize_into](#tymethod.serialize_into) will append.
+    /// This is used to preallocate buffers, so it does not strictly need to be accurate, but the
+    /// implementations provided by rbop are exact.
+    fn serialized_size_hint(&self) -> usize;
+
+    /// Serializes this item into a new vec of bytes, preallocated using
+    /// [serialized_size_hint](#tymethod.serialized_size_hint).
+    fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.serialized_size_hint());
+        self.serialize_into(&mut result);
+        result
+    }
+
+    /// Deserializes an item from a [ByteRead], consuming exactly the bytes which make up the item.
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError>;
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self>;
+    /// Checks that the [ByteRead] contains a valid serialized item, consuming exactly the bytes
+    /// which make up the item, without building the item itself.
+    ///
+    /// The default implementation deserializes the item and discards it. Implementations for node
+    /// trees override this to avoid allocating.
+    fn validate(reader: &mut dyn ByteRead) -> Result<(), DeserializeError> {
+        Self::deserialize_from(reader).map(|_| ())
+    }
+
+    /// Deserializes an item from an iterator of bytes, returning `None` if this fails for any
+    /// reason.
+    ///
+    /// Prefer [deserialize_from](#tymethod.deserialize_from), which reports why deserialization
+    /// failed, and does not require the bytes to be available as an iterator.
+    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
+        Self::deserialize_from(&mut IteratorByteReader { iter: bytes }).ok()
+    }
 }
 
 impl<T : num_traits::PrimInt> Serializable for T {
@@ -15,29 +117,31 @@ impl<T : num_traits::PrimInt> Serializable for T {
     //   0xFF 0x02 = 257
     //   0xFF 0xFF 0x02 = 512
     // 0xFF is always followed by another byte which is added to the 0xFF.
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
         if self < &Self::zero() { panic!("cannot serialize negative numbers"); }
 
-        let mut result = vec![];
         let mut current = *self;
         while current >= Self::from(0xFF).unwrap() {
             current = current - Self::from(0xFF).unwrap();
-            result.push(0xFF);
+            bytes.push(0xFF);
         }
-        result.push(num_traits::cast(current).unwrap());
+        bytes.push(num_traits::cast(current).unwrap());
+    }
 
-        result
+    fn serialized_size_hint(&self) -> usize {
+        // One 0xFF for every full 255, plus the final byte
+        num_traits::cast::<_, usize>(*self / Self::from(0xFF).unwrap()).unwrap_or(0) + 1
     }
 
-    fn deserialize(bytes: &mut dyn Iterator<Item = u8>) -> Option<Self> {
+    fn deserialize_from(reader: &mut dyn ByteRead) -> Result<Self, DeserializeError> {
         let mut result = Self::zero();
 
         loop {
-            let byte = bytes.next()?;
+            let byte = reader.read_u8()?;
             result = result + Self::from(byte).unwrap();
             if byte != 0xFF { break; }
         }
 
-        Some(result)
+        Ok(result)
     }
 }
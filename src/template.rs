@@ -0,0 +1,128 @@
+//! A small library of built-in expression templates for common formulas.
+//!
+//! Each [Template] stores its expression as a serialized [UnstructuredNodeRoot], along with a
+//! description of each variable used in it. Calculators can list the available templates, insert
+//! one into the user's input with [instantiate](Template::instantiate), or substitute known values
+//! with [fill](Template::fill).
+
+use crate::{Number, Token, UnstructuredNode, UnstructuredNodeList, UnstructuredNodeRoot, serialize::Serializable};
+
+/// A variable used by a [Template].
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct TemplateVariable {
+    /// The name of the variable, as it appears in the expression.
+    pub name: char,
+
+    /// A human-readable description of what the variable represents.
+    pub description: &'static str,
+}
+
+/// A built-in expression template for a common formula.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Template {
+    /// A short name for this template.
+    pub name: &'static str,
+
+    /// A human-readable description of what this template calculates.
+    pub description: &'static str,
+
+    /// The variables used by this template's expression.
+    pub variables: &'static [TemplateVariable],
+
+    /// The expression, as a serialized [UnstructuredNodeRoot].
+    pub expression: &'static [u8],
+}
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        name: "Quadratic formula",
+        description: "One root of ax²+bx+c=0",
+        variables: &[
+            TemplateVariable { name: 'a', description: "Coefficient of x²" },
+            TemplateVariable { name: 'b', description: "Coefficient of x" },
+            TemplateVariable { name: 'c', description: "Constant term" },
+        ],
+        // (-b+√(b²-4ac))/2a
+        expression: &[
+            1, 2, 4, 130, 144, 98, 129, 1, 6, 144, 98, 4, 1, 135, 130, 137, 144, 97, 144, 99, 2, 135,
+            144, 97,
+        ],
+    },
+    Template {
+        name: "Compound interest",
+        description: "Final amount after compound interest",
+        variables: &[
+            TemplateVariable { name: 'P', description: "Initial amount" },
+            TemplateVariable { name: 'r', description: "Annual interest rate, as a decimal" },
+            TemplateVariable { name: 'n', description: "Number of times compounded per year" },
+            TemplateVariable { name: 't', description: "Number of years" },
+        ],
+        // P(1+r/n)^(nt)
+        expression: &[
+            3, 144, 80, 3, 3, 134, 129, 2, 1, 144, 114, 1, 144, 110, 4, 2, 144, 110, 144, 116,
+        ],
+    },
+    Template {
+        name: "Pythagoras",
+        description: "Hypotenuse of a right-angled triangle",
+        variables: &[
+            TemplateVariable { name: 'a', description: "Length of one side" },
+            TemplateVariable { name: 'b', description: "Length of the other side" },
+        ],
+        // √(a²+b²)
+        expression: &[1, 1, 5, 144, 97, 4, 1, 135, 129, 144, 98, 4, 1, 135],
+    },
+];
+
+impl Template {
+    /// Returns all built-in templates.
+    pub fn all() -> &'static [Template] {
+        TEMPLATES
+    }
+
+    /// Finds a built-in template by name.
+    pub fn find(name: &str) -> Option<&'static Template> {
+        TEMPLATES.iter().find(|t| t.name == name)
+    }
+
+    /// Deserializes this template's expression, with its variables left as variables.
+    pub fn instantiate(&self) -> UnstructuredNodeRoot {
+        UnstructuredNodeRoot::deserialize(&mut self.expression.iter().copied())
+            .expect("invalid built-in template")
+    }
+
+    /// Deserializes this template's expression, replacing variables with the given values. Each
+    /// value is wrapped in parentheses so that it can't combine with surrounding tokens. Variables
+    /// which aren't given a value are left as variables.
+    pub fn fill(&self, values: &[(char, Number)]) -> UnstructuredNodeRoot {
+        let mut root = self.instantiate();
+        Self::fill_list(&mut root.root, values);
+        root
+    }
+
+    fn fill_list(list: &mut UnstructuredNodeList, values: &[(char, Number)]) {
+        for node in list.items.iter_mut() {
+            match node {
+                UnstructuredNode::Token(Token::Variable(v)) => {
+                    if let Some((_, value)) = values.iter().find(|(name, _)| name == v) {
+                        *node = UnstructuredNode::Parentheses(UnstructuredNodeRoot::from_number(*value).root);
+                    }
+                },
+                UnstructuredNode::Token(_) => (),
+
+                UnstructuredNode::Sqrt(inner) | UnstructuredNode::Parentheses(inner) | UnstructuredNode::Power(inner)
+                | UnstructuredNode::PowerOfTen(inner) | UnstructuredNode::Styled(_, inner)
+                    => Self::fill_list(inner, values),
+                UnstructuredNode::Fraction(a, b) | UnstructuredNode::Root(a, b) => {
+                    Self::fill_list(a, values);
+                    Self::fill_list(b, values);
+                },
+                UnstructuredNode::FunctionCall(_, args) => {
+                    for arg in args {
+                        Self::fill_list(arg, values);
+                    }
+                },
+            }
+        }
+    }
+}
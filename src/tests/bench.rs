@@ -1,6 +1,7 @@
+use alloc::vec::Vec;
 use test::{Bencher, black_box};
 
-use crate::{render::{LayoutComputationProperties, Layoutable}, renderers::AsciiRenderer};
+use crate::{render::{LayoutComputationProperties, Layoutable}, renderers::AsciiRenderer, serialize::Serializable};
 
 use super::util::complex_unstructured_expression;
 
@@ -13,3 +14,24 @@ fn bench_unstructured_layout(b: &mut Bencher) {
         black_box(tree.layout(&mut ascii_renderer, None, LayoutComputationProperties::default()));
     });
 }
+
+#[bench]
+fn bench_serialize(b: &mut Bencher) {
+    let tree = complex_unstructured_expression();
+
+    b.iter(|| {
+        black_box(tree.serialize());
+    });
+}
+
+#[bench]
+fn bench_serialize_into_reused_buffer(b: &mut Bencher) {
+    let tree = complex_unstructured_expression();
+    let mut buffer = Vec::with_capacity(tree.serialized_size_hint());
+
+    b.iter(|| {
+        buffer.clear();
+        tree.serialize_into(&mut buffer);
+        black_box(&buffer);
+    });
+}
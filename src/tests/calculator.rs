@@ -0,0 +1,49 @@
+use crate::{prelude::*, error::{EvaluationError, MathsError}};
+
+#[test]
+fn test_simple_calculator() {
+    let mut renderer = AsciiRenderer::default();
+    let mut calc = SimpleCalculator::new();
+    let type_all = |calc: &mut SimpleCalculator, keys: &str, renderer: &mut AsciiRenderer| {
+        for c in keys.chars() {
+            assert!(calc.press(CalculatorKey::Char(c), renderer));
+        }
+    };
+
+    // 12+3/4
+    type_all(&mut calc, "12+/3", &mut renderer);
+    calc.press(CalculatorKey::Down, &mut renderer);
+    type_all(&mut calc, "4", &mut renderer);
+    assert_eq!(calc.editor.root, UnstructuredNodeRoot { root: uns_list!(
+        token!(1), token!(2), token!(+), uns_frac!(tokens!(3), tokens!(4)),
+    ) });
+    assert_eq!(calc.evaluate(), Ok(rat!(51, 4)));
+
+    calc.draw(&mut renderer);
+
+    // Unrecognised keys are ignored
+    assert!(!calc.press(CalculatorKey::Char('?'), &mut renderer));
+
+    // Errors from both upgrading and evaluating are returned
+    calc.press(CalculatorKey::Clear, &mut renderer);
+    type_all(&mut calc, "1+", &mut renderer);
+    assert!(matches!(calc.evaluate(), Err(EvaluationError::Node(_))));
+    calc.press(CalculatorKey::Clear, &mut renderer);
+    type_all(&mut calc, "/1", &mut renderer);
+    calc.press(CalculatorKey::Down, &mut renderer);
+    type_all(&mut calc, "0", &mut renderer);
+    assert_eq!(calc.evaluate(), Err(EvaluationError::Maths(MathsError::DivisionByZero)));
+
+    // "r" converts a square root into a root, or inserts a new root
+    calc.press(CalculatorKey::Clear, &mut renderer);
+    type_all(&mut calc, "s8r3", &mut renderer);
+    assert_eq!(calc.editor.root, UnstructuredNodeRoot { root: uns_list!(
+        UnstructuredNode::Root(tokens!(3), tokens!(8)),
+    ) });
+    assert_eq!(calc.evaluate(), Ok(Number::from(2)));
+    calc.press(CalculatorKey::Clear, &mut renderer);
+    type_all(&mut calc, "r", &mut renderer);
+    assert_eq!(calc.editor.root, UnstructuredNodeRoot { root: uns_list!(
+        UnstructuredNode::Root(tokens!(), tokens!()),
+    ) });
+}
@@ -0,0 +1,266 @@
+use alloc::vec;
+use rust_decimal::Decimal;
+
+use crate::{editor::{Editor, EditOperation, EditMacro, EditorLimits, InputValidation}, error::EditError, nav::NavPath, renderers::AsciiRenderer, serialize::Serializable, UnstructuredNodeRoot, UnstructuredNode, UnstructuredNodeList, node::{structured::EvaluationSettings, unstructured::Upgradable}};
+
+#[test]
+fn test_macro_recording() {
+    let mut renderer = AsciiRenderer::default();
+    let mut editor = Editor::new();
+    editor.apply(EditOperation::Insert(token!(1)), &mut renderer);
+
+    // Record typing "+(2/3)" and moving out of the fraction
+    editor.start_recording();
+    assert!(editor.is_recording());
+    for operation in [
+        EditOperation::Insert(token!(+)),
+        EditOperation::Insert(uns_frac!(tokens!(), tokens!())),
+        EditOperation::Insert(token!(2)),
+        EditOperation::MoveDown,
+        EditOperation::Insert(token!(3)),
+        EditOperation::MoveRight,
+    ] {
+        editor.apply(operation, &mut renderer);
+    }
+    let recorded = editor.stop_recording().unwrap();
+    assert!(!editor.is_recording());
+    assert_eq!(recorded.operations.len(), 6);
+    assert_eq!(editor.stop_recording(), None);
+
+    assert_eq!(editor.root, UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        uns_frac!(tokens!(2), tokens!(3)),
+    ) });
+    assert_eq!(editor.path, NavPath::new(vec![3]));
+
+    // The macro survives serialization, and can be replayed onto another tree
+    let recorded = EditMacro::deserialize(&mut recorded.serialize().into_iter()).unwrap();
+    let mut other = Editor::new();
+    other.apply(EditOperation::Insert(token!(4)), &mut renderer);
+    recorded.replay(&mut other, &mut renderer);
+    recorded.replay(&mut other, &mut renderer);
+    assert_eq!(other.root, UnstructuredNodeRoot { root: uns_list!(
+        token!(4),
+        token!(+),
+        uns_frac!(tokens!(2), tokens!(3)),
+        token!(+),
+        uns_frac!(tokens!(2), tokens!(3)),
+    ) });
+    assert_eq!(other.path, NavPath::new(vec![5]));
+
+    // Operations which aren't recorded still apply
+    editor.apply(EditOperation::Delete, &mut renderer);
+    assert_eq!(editor.root.root.items, vec![token!(1), token!(+)]);
+}
+
+#[test]
+fn test_input_validation() {
+    let mut renderer = AsciiRenderer::default();
+    let type_all = |editor: &mut Editor, nodes: &[crate::UnstructuredNode], renderer: &mut AsciiRenderer| {
+        nodes.iter().map(|n| editor.apply(EditOperation::Insert(n.clone()), renderer)).collect::<alloc::vec::Vec<_>>()
+    };
+
+    // Without validation, anything goes
+    let mut editor = Editor::new();
+    assert_eq!(type_all(&mut editor, &[token!(*), token!(+), token!(+)], &mut renderer), vec![true; 3]);
+
+    // Rejecting ignores leading operators and operators next to others, but allows unary minus
+    let mut editor = Editor::new();
+    editor.validation = InputValidation::Reject;
+    editor.start_recording();
+    assert_eq!(
+        type_all(&mut editor, &[token!(*), token!(-), token!(2), token!(+), token!(+), token!(-), token!(3)], &mut renderer),
+        vec![false, true, true, true, false, true, true],
+    );
+    assert_eq!(editor.root.root, tokens!(- 2 + - 3));
+    assert_eq!(editor.stop_recording().unwrap().operations.len(), 5);
+
+    // Slots inside other nodes count as the start of an expression
+    editor.apply(EditOperation::Insert(uns_frac!(tokens!(), tokens!())), &mut renderer);
+    assert!(!editor.apply(EditOperation::Insert(token!(/)), &mut renderer));
+
+    // Correcting replaces the run of operators before the cursor...
+    let mut editor = Editor::new();
+    editor.validation = InputValidation::Correct;
+    assert_eq!(
+        type_all(&mut editor, &[token!(/), token!(2), token!(+), token!(*), token!(-), token!(/), token!(3)], &mut renderer),
+        vec![false, true, true, true, true, true, true],
+    );
+    assert_eq!(editor.root.root, tokens!(2 / 3));
+    assert_eq!(editor.path, NavPath::new(vec![3]));
+
+    // ...or the operator after it
+    editor.apply(EditOperation::MoveLeft, &mut renderer);
+    editor.apply(EditOperation::MoveLeft, &mut renderer);
+    assert!(editor.apply(EditOperation::Insert(token!(+)), &mut renderer));
+    assert_eq!(editor.root.root, tokens!(2 + 3));
+    assert_eq!(editor.path, NavPath::new(vec![2]));
+}
+
+#[test]
+fn test_auto_multiply() {
+    let mut renderer = AsciiRenderer::default();
+    let mut editor = Editor::new();
+    editor.auto_multiply = true;
+
+    // (1)2
+    editor.apply(EditOperation::Insert(UnstructuredNode::Parentheses(tokens!())), &mut renderer);
+    editor.apply(EditOperation::Insert(token!(1)), &mut renderer);
+    editor.apply(EditOperation::MoveRight, &mut renderer);
+    editor.apply(EditOperation::Insert(token!(2)), &mut renderer);
+    assert_eq!(editor.root.root, uns_list!(UnstructuredNode::Parentheses(tokens!(1)), token!(*), token!(2)));
+    assert_eq!(editor.path, NavPath::new(vec![3]));
+
+    // Digits after tokens are unaffected, as are other tokens after structures
+    editor.apply(EditOperation::Insert(token!(3)), &mut renderer);
+    editor.apply(EditOperation::Insert(uns_frac!(tokens!(4), tokens!(5))), &mut renderer);
+    editor.apply(EditOperation::MoveRight, &mut renderer);
+    editor.apply(EditOperation::MoveRight, &mut renderer);
+    editor.apply(EditOperation::Insert(token!(var x)), &mut renderer);
+    assert_eq!(editor.root.root, uns_list!(
+        UnstructuredNode::Parentheses(tokens!(1)), token!(*), token!(2), token!(3),
+        uns_frac!(tokens!(4), tokens!(5)), token!(var x),
+    ));
+
+    // Without the option, the multiplication is implicit
+    let mut editor = Editor::new();
+    editor.apply(EditOperation::Insert(uns_frac!(tokens!(1), tokens!(2))), &mut renderer);
+    editor.apply(EditOperation::MoveRight, &mut renderer);
+    editor.apply(EditOperation::MoveRight, &mut renderer);
+    editor.apply(EditOperation::Insert(token!(3)), &mut renderer);
+    assert_eq!(editor.root.root, uns_list!(uns_frac!(tokens!(1), tokens!(2)), token!(3)));
+}
+
+#[test]
+fn test_power_of_ten() {
+    let mut renderer = AsciiRenderer::default();
+    let mut editor = Editor::new();
+
+    // 1.5×10^-3
+    editor.start_recording();
+    for operation in [
+        EditOperation::Insert(token!(1)),
+        EditOperation::Insert(token!(.)),
+        EditOperation::Insert(token!(5)),
+        EditOperation::PowerOfTen,
+        EditOperation::Insert(token!(-)),
+        EditOperation::Insert(token!(3)),
+    ] {
+        editor.apply(operation, &mut renderer);
+    }
+    assert_eq!(editor.root.root, uns_list!(
+        token!(1), token!(.), token!(5),
+        UnstructuredNode::PowerOfTen(tokens!(- 3)),
+    ));
+    assert_eq!(editor.path, NavPath::new(vec![3, 0, 2]));
+    assert_eq!(
+        editor.root.upgrade().unwrap().evaluate(&EvaluationSettings::default()).unwrap().to_decimal(),
+        Decimal::new(15, 4),
+    );
+    let recorded = editor.stop_recording().unwrap();
+    assert_eq!(EditMacro::deserialize(&mut recorded.serialize().into_iter()), Some(recorded));
+
+    // The power of ten binds to the number before it, rather than being a separate multiplication
+    let mut editor = Editor::new();
+    for operation in [
+        EditOperation::Insert(token!(6)),
+        EditOperation::Insert(token!(/)),
+        EditOperation::Insert(token!(1)),
+        EditOperation::Insert(token!(.)),
+        EditOperation::Insert(token!(5)),
+        EditOperation::PowerOfTen,
+        EditOperation::Insert(token!(3)),
+    ] {
+        editor.apply(operation, &mut renderer);
+    }
+    assert_eq!(
+        editor.root.upgrade().unwrap().evaluate(&EvaluationSettings::default()).unwrap().to_decimal().round_dp(10),
+        Decimal::new(4, 3),
+    );
+
+    // With nothing before it, it's just a power of ten
+    let mut editor = Editor::new();
+    editor.apply(EditOperation::PowerOfTen, &mut renderer);
+    editor.apply(EditOperation::Insert(token!(2)), &mut renderer);
+    editor.apply(EditOperation::Insert(token!(0)), &mut renderer);
+    assert_eq!(editor.root.root, uns_list!(UnstructuredNode::PowerOfTen(tokens!(2 0))));
+
+    // Large powers which don't fit into a rational are still evaluated
+    assert_eq!(
+        editor.root.upgrade().unwrap().evaluate(&EvaluationSettings::default()).unwrap().to_decimal(),
+        Decimal::from(100_000_000_000_000_000_000_u128),
+    );
+}
+
+#[test]
+fn test_editor_limits() {
+    let mut renderer = AsciiRenderer::default();
+    let mut editor = Editor::new();
+    editor.limits = EditorLimits { max_nodes: Some(6), max_insert_nodes: Some(3), max_depth: Some(2) };
+
+    // Nesting is limited, counting the slots the cursor is already in
+    let fraction = || UnstructuredNode::Fraction(UnstructuredNodeList::new(), UnstructuredNodeList::new());
+    assert_eq!(editor.try_apply(EditOperation::Insert(fraction()), &mut renderer), Ok(()));
+    assert_eq!(editor.try_apply(EditOperation::Insert(fraction()), &mut renderer), Ok(()));
+    assert_eq!(editor.try_apply(EditOperation::Insert(fraction()), &mut renderer), Err(EditError::TooDeep));
+    assert_eq!(editor.root.root.nesting_depth(), 2);
+
+    // Large insertions are rejected, even if they would fit
+    editor.apply(EditOperation::Clear, &mut renderer);
+    let large = UnstructuredNode::Parentheses(tokens!(1 + 2));
+    assert_eq!(editor.try_apply(EditOperation::Insert(large), &mut renderer), Err(EditError::InsertTooLarge));
+    assert_eq!(editor.root.root, uns_list!());
+
+    // The total number of nodes is limited, and rejected operations aren't recorded
+    editor.start_recording();
+    for _ in 0..6 {
+        assert!(editor.apply(EditOperation::Insert(token!(1)), &mut renderer));
+    }
+    assert_eq!(editor.try_apply(EditOperation::Insert(token!(2)), &mut renderer), Err(EditError::TooManyNodes));
+    assert!(!editor.apply(EditOperation::PowerOfTen, &mut renderer));
+    assert_eq!(editor.root.root.node_count(), 6);
+    assert_eq!(editor.stop_recording().unwrap().operations.len(), 6);
+
+    // Making the expression smaller makes room again
+    editor.apply(EditOperation::Delete, &mut renderer);
+    assert!(editor.apply(EditOperation::Insert(token!(2)), &mut renderer));
+    assert_eq!(editor.root.root, tokens!(1 1 1 1 1 2));
+}
+
+#[test]
+fn test_digit_grouping() {
+    let mut renderer = AsciiRenderer::default();
+    let mut editor = Editor::new();
+
+    // 2+1234567.891
+    for token in tokens!(2 + 1 2 3 4 5 6 7 . 8 9 1).items {
+        editor.apply(EditOperation::Insert(token), &mut renderer);
+    }
+    assert_eq!(editor.digit_grouping(3), vec![NavPath::new(vec![3]), NavPath::new(vec![6])]);
+
+    // The tokens aren't changed
+    assert_eq!(editor.root.root, tokens!(2 + 1 2 3 4 5 6 7 . 8 9 1));
+
+    // The cursor can be anywhere in or next to the number
+    editor.path = NavPath::new(vec![2]);
+    assert_eq!(editor.digit_grouping(3), vec![NavPath::new(vec![3]), NavPath::new(vec![6])]);
+    assert_eq!(editor.digit_grouping(4), vec![NavPath::new(vec![5])]);
+
+    // Other numbers are unaffected, and short numbers don't need separators
+    editor.path = NavPath::new(vec![1]);
+    assert_eq!(editor.digit_grouping(3), vec![]);
+
+    // Numbers inside other nodes are grouped too
+    let mut editor = Editor::new();
+    editor.apply(EditOperation::Insert(UnstructuredNode::Sqrt(UnstructuredNodeList::new())), &mut renderer);
+    for token in tokens!(1 0 0 0 0).items {
+        editor.apply(EditOperation::Insert(token), &mut renderer);
+    }
+    assert_eq!(editor.digit_grouping(3), vec![NavPath::new(vec![0, 0, 2])]);
+
+    // The tree can be shared while grouping, and paths which don't fit it have no separators
+    let root = &editor.root;
+    assert_eq!(root.digit_grouping(&NavPath::new(vec![0, 0, 5]), 3), vec![NavPath::new(vec![0, 0, 2])]);
+    assert_eq!(root.digit_grouping(&NavPath::new(vec![0, 1, 0]), 3), vec![]);
+}
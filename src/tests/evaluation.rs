@@ -3,46 +3,598 @@ use core::assert_matches::assert_matches;
 use alloc::boxed::Box;
 use rust_decimal::Decimal;
 
-use crate::{StructuredNode, node::{structured::{EvaluationSettings, AngleUnit}, function::Function}, Number, number::DecimalAccuracy};
+use alloc::vec;
+use alloc::string::ToString;
+
+use crate::{StructuredNode, tests::util::{with_each_backend, assert_approx_eq}, node::{structured::{EvaluationSettings, AngleUnit}, function::Function, unstructured::{Upgradable, EngineeringSuffix}, compiled::CompiledNode}, Number, Token, number::{DecimalAccuracy, ZeroOverZero, ZeroPowerZero, ScientificNotation}, UnstructuredNodeRoot, UnstructuredNode, error::MathsError, serialize::Serializable, evaluate::IncrementalEvaluation};
 
 
 #[test]
 fn test_divide_by_zero() {
-    // Rational
-    let result = StructuredNode::Divide(
-        Box::new(StructuredNode::Number(rat!(12))),
-        Box::new(StructuredNode::Number(rat!(0))),
-    ).disambiguate().unwrap().evaluate(&EvaluationSettings::default());
-    assert_matches!(result, Err(_));
-
-    // Decimal
-    let result = StructuredNode::Add(
-        Box::new(StructuredNode::Divide(
-            Box::new(StructuredNode::Number(dec!(12))),
-            Box::new(StructuredNode::Number(dec!(0))),
-        )),
-        Box::new(StructuredNode::Number(dec!(0.1))),
-    ).disambiguate().unwrap().evaluate(&EvaluationSettings::default());
-    assert_matches!(result, Err(_));
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        // Rational
+        let result = StructuredNode::Divide(
+            Box::new(StructuredNode::Number(rat!(12))),
+            Box::new(StructuredNode::Number(rat!(0))),
+        ).disambiguate().unwrap().evaluate(settings);
+        assert_matches!(result, Err(_));
+
+        // Decimal
+        let result = StructuredNode::Add(
+            Box::new(StructuredNode::Divide(
+                Box::new(StructuredNode::Number(dec!(12))),
+                Box::new(StructuredNode::Number(dec!(0))),
+            )),
+            Box::new(StructuredNode::Number(dec!(0.1))),
+        ).disambiguate().unwrap().evaluate(settings);
+        assert_matches!(result, Err(_));
+    });
 }
 
 #[test]
 fn test_function_evaluation() {
-    assert_eq!(
-        Function::Sine.evaluate(&[dec!(90)], &EvaluationSettings { angle_unit: AngleUnit::Degree, use_floats: false }),
-        Ok(dec_approx!(1)),
-    );
-    assert_eq!(
-        Function::Sine.evaluate(&[Number::Decimal(Decimal::PI / Decimal::TWO, DecimalAccuracy::Exact)], &EvaluationSettings { angle_unit: AngleUnit::Radian, use_floats: false }),
-        Ok(dec_approx!(1)),
-    );
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let degrees = EvaluationSettings { angle_unit: AngleUnit::Degree, ..settings.clone() };
+        let radians = EvaluationSettings { angle_unit: AngleUnit::Radian, ..settings.clone() };
+
+        assert_approx_eq(Function::Sine.evaluate(&[dec!(90)], &degrees), Ok(dec_approx!(1)));
+        assert_approx_eq(
+            Function::Sine.evaluate(&[Number::Decimal(Decimal::PI / Decimal::TWO, DecimalAccuracy::Exact)], &radians),
+            Ok(dec_approx!(1)),
+        );
+
+        assert_approx_eq(Function::Cosine.evaluate(&[dec!(180)], &degrees), Ok(dec_approx!(-1)));
+        assert_approx_eq(
+            Function::Cosine.evaluate(&[Number::Decimal(Decimal::PI, DecimalAccuracy::Exact)], &radians),
+            Ok(dec_approx!(-1)),
+        );
+    });
+}
+
+#[test]
+fn test_trigonometry_at_quarter_pi() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let degrees = EvaluationSettings { angle_unit: AngleUnit::Degree, ..settings.clone() };
+        let radians = EvaluationSettings { angle_unit: AngleUnit::Radian, ..settings.clone() };
+        let radians_of = |d| Number::Decimal(d, DecimalAccuracy::Exact);
+
+        // Angles which reduce to exactly π/4 used to recurse forever with decimals
+        for (angle, sine, cosine) in [
+            (Decimal::QUARTER_PI, dec_approx!(0.7071068), dec_approx!(0.7071068)),
+            (-Decimal::QUARTER_PI, dec_approx!(-0.7071068), dec_approx!(0.7071068)),
+            (Decimal::PI + Decimal::QUARTER_PI, dec_approx!(-0.7071068), dec_approx!(-0.7071068)),
+        ] {
+            assert_approx_eq(Function::Sine.evaluate(&[radians_of(angle)], &radians), Ok(sine));
+            assert_approx_eq(Function::Cosine.evaluate(&[radians_of(angle)], &radians), Ok(cosine));
+        }
+        assert_approx_eq(Function::Tangent.evaluate(&[dec!(-45)], &degrees), Ok(dec_approx!(-1)));
+    });
+}
+
+#[test]
+fn test_power_functions() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        // Reciprocals and powers of ten are exact
+        assert_eq!(Function::Reciprocal.evaluate(&[rat!(4)], settings), Ok(rat!(1, 4)));
+        assert_eq!(Function::Reciprocal.evaluate(&[rat!(-2, 3)], settings), Ok(rat!(-3, 2)));
+        assert_eq!(Function::Reciprocal.evaluate(&[rat!(0)], settings), Err(MathsError::DivisionByZero));
+        assert_eq!(Function::PowerOfTen.evaluate(&[rat!(3)], settings), Ok(rat!(1000)));
+        assert_eq!(Function::PowerOfTen.evaluate(&[rat!(-2)], settings), Ok(rat!(1, 100)));
+
+        // e^x is only exact for 0
+        assert_eq!(Function::Exp.evaluate(&[rat!(0)], settings), Ok(rat!(1)));
+        assert_approx_eq(Function::Exp.evaluate(&[rat!(1)], settings), Ok(dec_approx!(2.718281828459045)));
+        assert_approx_eq(Function::Exp.evaluate(&[rat!(2)], settings), Ok(dec_approx!(7.389056)));
+
+        // They can be used like any other function
+        let tree = uns_list!(
+            UnstructuredNode::FunctionCall(Function::Reciprocal, vec![tokens!(2 + 3)]),
+            token!(+),
+            UnstructuredNode::FunctionCall(Function::PowerOfTen, vec![tokens!(2)]),
+        );
+        assert_eq!(tree.upgrade().unwrap().evaluate(settings), Ok(rat!(501, 5)));
+    });
+
+    for func in [Function::Reciprocal, Function::PowerOfTen, Function::Exp] {
+        assert_eq!(Function::deserialize(&mut func.serialize().into_iter()), Some(func));
+    }
+}
+
+#[test]
+fn test_percent_change_and_ratios() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        // Percentage changes are exact
+        assert_eq!(Function::PercentChange.evaluate(&[rat!(40), rat!(50)], settings), Ok(rat!(25)));
+        assert_eq!(Function::PercentChange.evaluate(&[rat!(3), rat!(2)], settings), Ok(rat!(-100, 3)));
+        assert_eq!(Function::PercentChange.evaluate(&[dec!(0.5), dec!(0.75)], settings).map(|n| n.to_decimal()), Ok(Decimal::from(50)));
+        assert_eq!(Function::PercentChange.evaluate(&[rat!(0), rat!(1)], settings), Err(MathsError::DivisionByZero));
+
+        // Changes are relative to the size of the starting value, so a fall is still negative
+        assert_eq!(Function::PercentChange.evaluate(&[rat!(-40), rat!(-50)], settings), Ok(rat!(-25)));
+        assert_eq!(Function::PercentChange.evaluate(&[rat!(-40), rat!(-20)], settings), Ok(rat!(50)));
+
+        // As a number, a ratio is a division, but it can also be evaluated into a ratio
+        let ratio = StructuredNode::FunctionCall(Function::Ratio, vec![
+            StructuredNode::Number(dec!(1.5)),
+            StructuredNode::Number(rat!(1)),
+        ]);
+        assert_eq!(ratio.evaluate(settings), Ok(dec!(1.5)));
+        assert_eq!(ratio.evaluate_ratio(settings).map(|r| r.map(|r| r.to_string())), Ok(Some("3:2".to_string())));
+        assert_eq!(StructuredNode::Number(rat!(3, 2)).evaluate_ratio(settings), Ok(None));
+    });
+    for func in [Function::PercentChange, Function::Ratio] {
+        assert_eq!(Function::deserialize(&mut func.serialize().into_iter()), Some(func));
+    }
+
+    // Ratios are reduced to whole numbers, with the sign on the left
+    let ratio = |a: Number, b| a.to_ratio(b).map(|r| (r.left, r.right));
+    assert_eq!(ratio(rat!(6), rat!(4)), Some((3, 2)));
+    assert_eq!(ratio(dec!(1.5), rat!(1)), Some((3, 2)));
+    assert_eq!(ratio(rat!(1, 3), dec!(0.25)), Some((4, 3)));
+    assert_eq!(ratio(rat!(2), rat!(-8)), Some((-1, 4)));
+    assert_eq!(ratio(rat!(0), rat!(5)), Some((0, 1)));
+    assert_eq!(rat!(10).to_ratio(rat!(4)).unwrap().to_string(), "5:2");
+
+    // Ratios of approximations can't be exact
+    assert_eq!(ratio(Number::pi(), rat!(1)), None);
+    assert_eq!(ratio(rat!(0), rat!(0)), None);
+}
+
+#[test]
+fn test_settings_builder() {
+    let settings = EvaluationSettings::new()
+        .with_angle_unit(AngleUnit::Radian)
+        .with_zero_power_zero(ZeroPowerZero::Error)
+        .with_decimal_places(Some(3))
+        .with_max_exponent(Some(100));
+    assert_eq!(settings, EvaluationSettings {
+        angle_unit: AngleUnit::Radian,
+        zero_power_zero: ZeroPowerZero::Error,
+        decimal_places: Some(3),
+        max_exponent: Some(100),
+        ..Default::default()
+    });
+
+    // Decimal results are rounded when presented, but rationals aren't
+    let present = |n| UnstructuredNodeRoot::from_number_with_settings(n, &settings);
+    assert_eq!(present(dec_approx!(0.333333)), UnstructuredNodeRoot::from_number(dec_approx!(0.333)));
+    assert_eq!(present(rat!(1, 3)), UnstructuredNodeRoot::from_number(rat!(1, 3)));
+
+    // Large exponents are rejected, however the tree is evaluated
+    let power = |exp| StructuredNode::Power(Box::new(StructuredNode::Number(rat!(2))), Box::new(StructuredNode::Number(exp)));
+    with_each_backend(settings.clone(), |settings| {
+        assert_eq!(power(rat!(10)).evaluate(settings), Ok(rat!(1024)));
+        assert_eq!(power(rat!(-101)).evaluate(settings), Err(MathsError::Overflow));
+        assert_eq!(IncrementalEvaluation::new(&power(rat!(101)), settings).finish(), Err(MathsError::Overflow));
+        assert_eq!(CompiledNode::from_structured(power(rat!(101)), None, settings).evaluate_raw(rat!(0)), Err(MathsError::Overflow));
+        assert_eq!(Function::PowerOfTen.evaluate(&[rat!(1000)], settings), Err(MathsError::Overflow));
+    });
+}
+
+#[test]
+fn test_inverse_trig_pi_multiples() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let radians = EvaluationSettings { angle_unit: AngleUnit::Radian, pi_multiples: true, ..settings.clone() };
+
+        // asin(1/2) = π/6
+        let result = Function::ArcSine.evaluate(&[rat!(1, 2)], &radians).unwrap();
+        assert_eq!(result.to_pi_multiple(), Some(rat!(1, 6)));
+        assert_eq!(
+            render!(UnstructuredNodeRoot::from_number_with_settings(result, &radians)),
+            vec![
+                "π",
+                "-",
+                "6",
+            ],
+        );
+
+        // acos(-1) = π, atan(-1) = -π/4
+        let result = Function::ArcCosine.evaluate(&[rat!(-1)], &radians).unwrap();
+        assert_eq!(render!(UnstructuredNodeRoot::from_number_with_settings(result, &radians)), vec!["π"]);
+        let result = Function::ArcTangent.evaluate(&[rat!(-1)], &radians).unwrap();
+        assert_eq!(result.to_pi_multiple(), Some(rat!(-1, 4)));
+
+        // Multiples of π can also be calculated with the π token, e.g. 5π/6
+        let result = uns_list!(
+            uns_frac!(tokens!(5 pi), tokens!(6))
+        ).upgrade().unwrap().evaluate(&radians).unwrap();
+        assert_eq!(
+            UnstructuredNodeRoot::from_number_with_settings(result, &radians),
+            UnstructuredNodeRoot { root: uns_list!(uns_frac!(tokens!(5 pi), tokens!(6))) },
+        );
+
+        // Without the setting, the result is a decimal
+        assert_eq!(
+            UnstructuredNodeRoot::from_number_with_settings(result, settings),
+            UnstructuredNodeRoot::from_number(result),
+        );
+
+        // Degrees and domain
+        let degrees = EvaluationSettings { angle_unit: AngleUnit::Degree, ..settings.clone() };
+        assert_eq!(Function::ArcSine.evaluate(&[rat!(1)], &degrees).unwrap().to_decimal().round_dp(6), Decimal::from(90));
+        assert_eq!(
+            Function::ArcCosine.evaluate(&[rat!(2)], &degrees),
+            Err(MathsError::DomainError { function: Function::ArcCosine, argument: rat!(2) }),
+        );
+    });
+}
+
+#[test]
+fn test_evaluate_detailed() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        // 1/2 + 3/(4-4)
+        let tree = StructuredNode::Add(
+            Box::new(StructuredNode::Divide(
+                Box::new(StructuredNode::Number(rat!(1))),
+                Box::new(StructuredNode::Number(rat!(2))),
+            )),
+            Box::new(StructuredNode::Divide(
+                Box::new(StructuredNode::Number(rat!(3))),
+                Box::new(StructuredNode::Subtract(
+                    Box::new(StructuredNode::Number(rat!(4))),
+                    Box::new(StructuredNode::Number(rat!(4))),
+                )),
+            )),
+        );
+        let detail = tree.evaluate_detailed(settings).unwrap_err();
+        assert_eq!(detail.error, MathsError::DivisionByZero);
+        assert_eq!(detail.operands, vec![rat!(3), rat!(0)]);
+        assert_eq!(detail.path, vec![1]);
+        assert_eq!(&detail.node, tree.children()[1]);
+
+        // Dividing by a tiny decimal overflows, rather than panicking
+        let tree = StructuredNode::Divide(
+            Box::new(StructuredNode::Number(rat!(100))),
+            Box::new(StructuredNode::Number(dec!(0.0000000000000000000000000001))),
+        );
+        let detail = tree.evaluate_detailed(settings).unwrap_err();
+        assert_eq!(detail.error, MathsError::Overflow);
+        assert_eq!(detail.operands, vec![rat!(100), dec!(0.0000000000000000000000000001)]);
+        assert_eq!(detail.path, vec![]);
+
+        // Successful evaluations give the same result as normal
+        let tree = StructuredNode::Divide(
+            Box::new(StructuredNode::Number(rat!(1))),
+            Box::new(StructuredNode::Number(rat!(3))),
+        );
+        assert_eq!(
+            tree.evaluate_detailed(settings).unwrap(),
+            tree.evaluate(settings).unwrap(),
+        );
+
+        // Logical functions short-circuit, so and(0, 1/0) isn't an error
+        let tree = StructuredNode::FunctionCall(Function::And, vec![
+            StructuredNode::Number(rat!(0)),
+            StructuredNode::Divide(
+                Box::new(StructuredNode::Number(rat!(1))),
+                Box::new(StructuredNode::Number(rat!(0))),
+            ),
+        ]);
+        assert_eq!(tree.evaluate_detailed(settings), Ok(rat!(0)));
+        assert_eq!(tree.evaluate(settings), Ok(rat!(0)));
+    });
+}
+
+#[test]
+fn test_zero_power_zero() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let tree = StructuredNode::Power(
+            Box::new(StructuredNode::Number(rat!(0))),
+            Box::new(StructuredNode::Number(dec!(0))),
+        );
+
+        // By default, 0^0 = 1
+        assert_eq!(tree.evaluate(settings), Ok(rat!(1)));
+
+        // But this can be an error instead, wherever evaluation happens
+        let error = EvaluationSettings { zero_power_zero: ZeroPowerZero::Error, ..settings.clone() };
+        assert_eq!(tree.evaluate(&error), Err(MathsError::Indeterminate));
+        assert_eq!(tree.evaluate_detailed(&error).unwrap_err().error, MathsError::Indeterminate);
+        let undefined = EvaluationSettings { zero_power_zero: ZeroPowerZero::Undefined, ..settings.clone() };
+        assert_eq!(tree.evaluate(&undefined), Err(MathsError::Undefined));
+        let compiled = CompiledNode::from_structured(tree, None, &error);
+        assert_eq!(compiled.evaluate_raw(rat!(0)), Err(MathsError::Indeterminate));
 
+        // Other powers of 0 are unaffected
+        assert_eq!(rat!(0).checked_pow_with(rat!(2), ZeroPowerZero::Error), Ok(rat!(0)));
+        assert_eq!(rat!(2).checked_pow_with(rat!(0), ZeroPowerZero::Error), Ok(rat!(1)));
+
+        // Roots go through the same path as powers, and never take 0⁰
+        let tree = StructuredNode::Root(
+            Box::new(StructuredNode::Number(rat!(2))),
+            Box::new(StructuredNode::Number(rat!(0))),
+        );
+        assert_eq!(tree.evaluate(&error), Ok(rat!(0)));
+        assert_eq!(tree.evaluate_detailed(&error), Ok(rat!(0)));
+        let compiled = CompiledNode::from_structured(tree, None, &error);
+        assert_eq!(compiled.evaluate_raw(rat!(0)), Ok(rat!(0)));
+
+        // By default, 0/0 is a division by zero like any other
+        let tree = StructuredNode::Divide(
+            Box::new(StructuredNode::Number(rat!(0))),
+            Box::new(StructuredNode::Number(rat!(0))),
+        );
+        assert_eq!(tree.evaluate(settings), Err(MathsError::DivisionByZero));
+        assert_eq!(dec!(0).checked_div(dec!(0)), Err(MathsError::DivisionByZero));
+
+        // But it can be indeterminate or undefined instead, wherever evaluation happens
+        for (zero_over_zero, error) in [
+            (ZeroOverZero::Indeterminate, MathsError::Indeterminate),
+            (ZeroOverZero::Undefined, MathsError::Undefined),
+        ] {
+            let settings = settings.clone().with_zero_over_zero(zero_over_zero);
+            assert_eq!(tree.evaluate(&settings), Err(error.clone()));
+            assert_eq!(tree.evaluate_detailed(&settings).unwrap_err().error, error);
+            let compiled = CompiledNode::from_structured(tree.clone(), None, &settings);
+            assert_eq!(compiled.evaluate_raw(rat!(0)), Err(error.clone()));
+            assert_eq!(dec!(0).checked_div_with(dec!(0), zero_over_zero), Err(error));
+            assert_eq!(rat!(1).checked_div_with(rat!(0), zero_over_zero), Err(MathsError::DivisionByZero));
+        }
+    });
+}
+
+#[test]
+fn test_domain_errors() {
+    // Domain checks happen before evaluating, so are the same with either backend
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let degrees = EvaluationSettings { angle_unit: AngleUnit::Degree, ..settings.clone() };
+        let radians = EvaluationSettings { angle_unit: AngleUnit::Radian, ..settings.clone() };
+
+        // Tangent is undefined at odd multiples of 90°, in either unit
+        assert_eq!(Function::Tangent.evaluate(&[rat!(45)], &degrees).unwrap().to_decimal().round_dp(6), Decimal::ONE);
+        for angle in [rat!(90), rat!(270), rat!(-90)] {
+            assert_eq!(
+                Function::Tangent.evaluate(&[angle], &degrees),
+                Err(MathsError::DomainError { function: Function::Tangent, argument: angle }),
+            );
+        }
+        let half_pi = uns_list!(uns_frac!(tokens!(pi), tokens!(2))).upgrade().unwrap().evaluate(&radians).unwrap();
+        assert_matches!(Function::Tangent.evaluate(&[half_pi], &radians), Err(MathsError::DomainError { .. }));
+
+        // Logarithms of non-positive numbers
+        assert_eq!(Function::NaturalLogarithm.evaluate(&[rat!(1)], &degrees), Ok(dec_approx!(0)));
+        for n in [rat!(0), rat!(-3)] {
+            assert_eq!(
+                Function::NaturalLogarithm.evaluate(&[n], &degrees),
+                Err(MathsError::DomainError { function: Function::NaturalLogarithm, argument: n }),
+            );
+        }
+
+        // Domain errors carry enough information to show a message, and can be serialized
+        let error = Function::ArcSine.evaluate(&[rat!(-2)], &degrees).unwrap_err();
+        assert_eq!(error.to_string(), "domain error: asin(-2)");
+        assert_eq!(MathsError::deserialize(&mut error.serialize().into_iter()), Some(error));
+    });
+}
+
+#[test]
+fn test_scientific_notation() {
+    let sci = |n: Number, sf| {
+        let ScientificNotation { mantissa, exponent } = n.to_scientific(sf);
+        (mantissa, exponent)
+    };
+
+    assert_eq!(sci(rat!(12345), 3), (Decimal::new(123, 2), 4));
+    assert_eq!(sci(dec!(-0.00042), 10), (Decimal::new(-42, 1), -4));
+    assert_eq!(sci(rat!(1, 3), 4), (Decimal::new(3333, 3), -1));
+    assert_eq!(sci(rat!(0), 4), (Decimal::ZERO, 0));
+
+    // Rounding can carry into the exponent
+    assert_eq!(sci(rat!(99999), 3), (Decimal::ONE, 5));
+
+    // Numbers at the edge of Decimal's range don't overflow
+    assert_eq!(sci(Number::Decimal(Decimal::MAX, DecimalAccuracy::Exact), 2), (Decimal::new(79, 1), 28));
+    assert_eq!(sci(Number::Decimal(Decimal::new(1, 28), DecimalAccuracy::Exact), 2), (Decimal::ONE, -28));
+
+    // These can be turned into nodes
     assert_eq!(
-        Function::Cosine.evaluate(&[dec!(180)], &EvaluationSettings { angle_unit: AngleUnit::Degree, use_floats: false }),
-        Ok(dec_approx!(-1)),
+        UnstructuredNodeRoot::from_scientific(&rat!(-12345).to_scientific(2)),
+        UnstructuredNodeRoot { root: uns_list!(
+            token!(-), token!(1), token!(.), token!(2), token!(*), token!(1), token!(0),
+            UnstructuredNode::Power(uns_list!(token!(4))),
+        ) },
     );
     assert_eq!(
-        Function::Cosine.evaluate(&[Number::Decimal(Decimal::PI, DecimalAccuracy::Exact)], &EvaluationSettings { angle_unit: AngleUnit::Radian, use_floats: false }),
-        Ok(dec_approx!(-1)),
+        UnstructuredNodeRoot::from_scientific(&rat!(5).to_scientific(2)),
+        UnstructuredNodeRoot { root: uns_list!(token!(5)) },
     );
 }
+
+#[test]
+fn test_engineering_suffixes() {
+    let settings = EvaluationSettings { engineering_suffix_results: true, ..Default::default() };
+    with_each_backend(settings, |settings| {
+        let kilo = UnstructuredNode::Token(Token::EngineeringSuffix(EngineeringSuffix::Kilo));
+        let micro = UnstructuredNode::Token(Token::EngineeringSuffix(EngineeringSuffix::Micro));
+
+        // 4.7k + 2µ*3, where suffixes scale only the number before them
+        let tree = UnstructuredNodeRoot { root: uns_list!(
+            token!(4), token!(.), token!(7), kilo.clone(), token!(+), token!(2), micro.clone(), token!(*), token!(3),
+        ) };
+        assert_eq!(render!(tree), vec!["4.7k+2µ*3"]);
+        assert_eq!(reserialize!(tree), tree);
+        assert_eq!(tree.upgrade().unwrap().evaluate(settings).unwrap().to_decimal(), Decimal::new(4700000006, 6));
+
+        // Suffixes on integers stay exact
+        assert_eq!(uns_list!(token!(5), micro.clone()).upgrade().unwrap().evaluate(settings), Ok(rat!(1, 200000)));
+
+        // A suffix must follow a number
+        assert_matches!(uns_list!(kilo.clone()).upgrade(), Err(_));
+        assert_matches!(uns_list!(token!(var x), kilo.clone()).upgrade(), Err(_));
+
+        // Results can be presented with suffixes
+        assert_eq!(
+            UnstructuredNodeRoot::from_number_with_settings(rat!(4700), settings).root,
+            uns_list!(token!(4), token!(.), token!(7), kilo),
+        );
+        assert_eq!(
+            UnstructuredNodeRoot::from_number_with_settings(dec!(0.0001), settings).root,
+            uns_list!(token!(1), token!(0), token!(0), micro),
+        );
+        assert_eq!(UnstructuredNodeRoot::from_number_with_settings(rat!(12), settings), UnstructuredNodeRoot::from_number(rat!(12)));
+        assert_eq!(
+            UnstructuredNodeRoot::from_number_with_settings(rat!(4700), &EvaluationSettings::default()),
+            UnstructuredNodeRoot::from_number(rat!(4700)),
+        );
+    });
+}
+
+#[test]
+fn test_boolean_logic() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let call = |func, args: &[bool]| Function::evaluate(&func, &args.iter().map(|b| (*b).into()).collect::<alloc::vec::Vec<_>>(), settings);
+
+        assert_eq!(call(Function::And, &[true, true]), Ok(rat!(1)));
+        assert_eq!(call(Function::And, &[true, false]), Ok(rat!(0)));
+        assert_eq!(call(Function::Or, &[false, true]), Ok(rat!(1)));
+        assert_eq!(call(Function::Not, &[true]), Ok(rat!(0)));
+        assert_eq!(call(Function::Xor, &[true, true]), Ok(rat!(0)));
+        assert_eq!(call(Function::Xor, &[false, true]), Ok(rat!(1)));
+
+        // Any non-zero number is true
+        assert_eq!(Function::Not.evaluate(&[dec!(0.5)], settings), Ok(rat!(0)));
+
+        // Literals upgrade to numbers
+        let tree = uns_list!(
+            UnstructuredNode::FunctionCall(Function::Or, vec![
+                uns_list!(UnstructuredNode::Token(Token::Boolean(false))),
+                uns_list!(UnstructuredNode::Token(Token::Boolean(true))),
+            ])
+        );
+        assert_eq!(tree.upgrade().unwrap().evaluate(settings), Ok(rat!(1)));
+        let root = UnstructuredNodeRoot { root: tree };
+        assert_eq!(reserialize!(root), root);
+
+        // Short-circuiting skips arguments which would fail
+        let failing = StructuredNode::Divide(
+            Box::new(StructuredNode::Number(rat!(1))),
+            Box::new(StructuredNode::Number(rat!(0))),
+        );
+        let tree = StructuredNode::FunctionCall(Function::And, vec![StructuredNode::Number(false.into()), failing.clone()]);
+        assert_eq!(tree.evaluate(settings), Ok(rat!(0)));
+        assert_eq!(CompiledNode::from_structured(tree, None, settings).evaluate_raw(rat!(0)), Ok(rat!(0)));
+        let tree = StructuredNode::FunctionCall(Function::And, vec![StructuredNode::Number(true.into()), failing]);
+        assert_eq!(tree.evaluate(settings), Err(MathsError::DivisionByZero));
+    });
+}
+
+#[test]
+fn test_conditionals() {
+    with_each_backend(EvaluationSettings::default(), |settings| {
+        let failing = || StructuredNode::Divide(
+            Box::new(StructuredNode::Number(rat!(1))),
+            Box::new(StructuredNode::Number(rat!(0))),
+        );
+
+        // if(x, 2, 1/0) upgrades to a conditional
+        let tree = uns_list!(UnstructuredNode::FunctionCall(Function::If, vec![
+            uns_list!(token!(var x)),
+            tokens!(2),
+            uns_list!(uns_frac!(tokens!(1), tokens!(0))),
+        ])).upgrade().unwrap();
+        assert_eq!(tree, StructuredNode::Conditional(
+            Box::new(StructuredNode::Variable('x')),
+            Box::new(StructuredNode::Number(rat!(2))),
+            Box::new(failing()),
+        ));
+
+        // Only the chosen branch is evaluated, however the tree is evaluated
+        assert_eq!(tree.substitute_variable('x', &StructuredNode::Number(rat!(1))).evaluate(settings), Ok(rat!(2)));
+        let compiled = CompiledNode::from_structured(tree.clone(), Some('x'), settings);
+        assert_eq!(compiled.evaluate_raw(rat!(5)), Ok(rat!(2)));
+        assert_eq!(compiled.evaluate_raw(rat!(0)), Err(MathsError::DivisionByZero));
+
+        // Detailed errors point into the chosen branch
+        let tree = tree.substitute_variable('x', &StructuredNode::Number(rat!(0)));
+        let detail = tree.evaluate_detailed(settings).unwrap_err();
+        assert_eq!(detail.error, MathsError::DivisionByZero);
+        assert_eq!(detail.path, vec![2]);
+    });
+}
+
+#[test]
+fn test_incremental_evaluation() {
+    let settings = EvaluationSettings::default();
+    let number = |n| Box::new(StructuredNode::Number(n));
+    let failing = || StructuredNode::Divide(number(rat!(1)), number(rat!(0)));
+
+    // 1+2 takes five steps: entering and evaluating each number, then evaluating the addition
+    let tree = StructuredNode::Add(number(rat!(1)), number(rat!(2)));
+    let mut evaluation = IncrementalEvaluation::new(&tree, &settings);
+    assert_eq!(evaluation.step(2), None);
+    assert_eq!(evaluation.step(2), None);
+    assert!(!evaluation.is_finished());
+    assert_eq!(evaluation.step(1), Some(Ok(rat!(3))));
+    assert!(evaluation.is_finished());
+    assert_eq!(evaluation.step(1), Some(Ok(rat!(3))));
+
+    // Larger trees give the same result as evaluating all at once, no matter how they're split
+    let tree = crate::tests::util::complex_unstructured_expression().upgrade().unwrap();
+    for max_steps in [1, 3, 100] {
+        let mut evaluation = IncrementalEvaluation::new(&tree, &settings);
+        let result = loop {
+            if let Some(result) = evaluation.step(max_steps) {
+                break result
+            }
+        };
+        assert_eq!(result, tree.evaluate(&settings));
+    }
+
+    // Logical functions short-circuit, and conditionals only evaluate their chosen branch
+    let tree = StructuredNode::FunctionCall(Function::And, vec![StructuredNode::Number(rat!(0)), failing()]);
+    assert_eq!(IncrementalEvaluation::new(&tree, &settings).finish(), Ok(rat!(0)));
+    let tree = StructuredNode::Conditional(number(rat!(1)), number(rat!(2)), Box::new(failing()));
+    assert_eq!(IncrementalEvaluation::new(&tree, &settings).finish(), Ok(rat!(2)));
+
+    // Errors finish the evaluation
+    let tree = StructuredNode::Conditional(number(rat!(0)), number(rat!(2)), Box::new(failing()));
+    let mut evaluation = IncrementalEvaluation::new(&tree, &settings);
+    assert_eq!(evaluation.finish(), Err(MathsError::DivisionByZero));
+    assert_eq!(evaluation.step(1), Some(Err(MathsError::DivisionByZero)));
+}
+
+#[test]
+fn test_float_parity() {
+    let call = |func, arg| StructuredNode::FunctionCall(func, vec![arg]);
+    let number = |n: Number| StructuredNode::Number(n);
+    let pi_over = |d| StructuredNode::Divide(Box::new(StructuredNode::Pi), Box::new(number(Number::Rational(d, 1))));
+
+    // The same expressions with each backend, in each angle unit, give the same results
+    let mut trees = vec![];
+    for func in [Function::Sine, Function::Cosine, Function::Tangent] {
+        for angle in [rat!(0), rat!(1), rat!(30), rat!(45), rat!(-45), rat!(60), rat!(225), rat!(135), rat!(1, 3), dec!(0.001), dec!(1234.5)] {
+            trees.push(call(func, number(angle)));
+        }
+        for d in [3, 6, -12] {
+            trees.push(call(func, pi_over(d)));
+        }
+    }
+    for n in [rat!(1, 1000), rat!(1, 2), rat!(2), rat!(10), rat!(12345), dec!(0.75)] {
+        trees.push(call(Function::NaturalLogarithm, number(n)));
+    }
+    for func in [Function::ArcSine, Function::ArcCosine, Function::ArcTangent] {
+        trees.push(call(func, number(rat!(1, 2))));
+    }
+
+    // sin²(x) + cos²(x), and ln(2) + ln(3) - ln(6), combining results from several functions
+    let sin_squared = StructuredNode::Power(Box::new(call(Function::Sine, number(rat!(2)))), Box::new(number(rat!(2))));
+    let cos_squared = StructuredNode::Power(Box::new(call(Function::Cosine, number(rat!(2)))), Box::new(number(rat!(2))));
+    trees.push(StructuredNode::Add(Box::new(sin_squared), Box::new(cos_squared)));
+    trees.push(StructuredNode::Subtract(
+        Box::new(StructuredNode::Add(
+            Box::new(call(Function::NaturalLogarithm, number(rat!(2)))),
+            Box::new(call(Function::NaturalLogarithm, number(rat!(3)))),
+        )),
+        Box::new(call(Function::NaturalLogarithm, number(rat!(6)))),
+    ));
+
+    for angle_unit in [AngleUnit::Degree, AngleUnit::Radian] {
+        let settings = EvaluationSettings { angle_unit, ..Default::default() };
+        for tree in &trees {
+            let mut results = vec![];
+            with_each_backend(settings.clone(), |settings| results.push(tree.evaluate(settings)));
+            assert_approx_eq(results[1].clone(), results[0].clone());
+        }
+    }
+
+}
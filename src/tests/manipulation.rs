@@ -1,6 +1,6 @@
-use alloc::boxed::Box;
+use alloc::{boxed::Box, format, vec};
 
-use crate::{StructuredNode, node::{structured::EvaluationSettings, unstructured::Upgradable}, UnstructuredNode, UnstructuredNodeRoot, tests::util::complex_unstructured_expression};
+use crate::{StructuredNode, node::{function::Function, structured::EvaluationSettings, unstructured::Upgradable}, nav::NavPath, Token, UnstructuredNode, UnstructuredNodeRoot, tests::util::complex_unstructured_expression, serialize::Serializable, error::DeserializeError};
 
 #[test]
 fn test_disambiguate() {
@@ -89,6 +89,13 @@ fn test_serialize() {
         complex_unstructured_expression(),
     );
 
+    // Size hints are exact
+    let bytes = complex_unstructured_expression().serialize();
+    assert_eq!(bytes.len(), complex_unstructured_expression().serialized_size_hint());
+    let fraction = &complex_unstructured_expression().root.items[3];
+    assert_eq!(fraction.serialized_size_hint(), fraction.serialize().len());
+    assert_eq!(300usize.serialized_size_hint(), 300usize.serialize().len());
+
     // Variables
     let e = UnstructuredNodeRoot { root: uns_list!(
         token!(var x),
@@ -102,3 +109,53 @@ fn test_serialize() {
         e
     );
 }
+
+#[test]
+fn test_deserialize_from_reader() {
+    let bytes = complex_unstructured_expression().serialize();
+
+    // Reading from a slice consumes exactly the serialized bytes
+    let mut reader = &bytes[..];
+    assert_eq!(
+        UnstructuredNodeRoot::deserialize_from(&mut reader),
+        Ok(complex_unstructured_expression()),
+    );
+    assert!(reader.is_empty());
+
+    // Validating does the same without building a tree
+    let mut reader = &bytes[..];
+    assert_eq!(UnstructuredNodeRoot::validate(&mut reader), Ok(()));
+    assert!(reader.is_empty());
+
+    // Truncated data
+    let mut reader = &bytes[..bytes.len() - 1];
+    assert_eq!(UnstructuredNodeRoot::validate(&mut reader), Err(DeserializeError::UnexpectedEof));
+    let mut reader = &bytes[..bytes.len() - 1];
+    assert_eq!(UnstructuredNodeRoot::deserialize_from(&mut reader), Err(DeserializeError::UnexpectedEof));
+
+    // Unknown node tag
+    let mut reader = &[1, 42][..];
+    assert_eq!(UnstructuredNodeRoot::validate(&mut reader), Err(DeserializeError::InvalidTag(42)));
+    let mut reader = &[1, 42][..];
+    assert_eq!(UnstructuredNodeRoot::deserialize_from(&mut reader), Err(DeserializeError::InvalidTag(42)));
+}
+
+#[test]
+fn test_compact_debug() {
+    assert_eq!(
+        format!("{:?}", complex_unstructured_expression()),
+        "1 2 + [3 4 + [5 6]/[7 8]]/[9 0] + 1 2",
+    );
+    assert_eq!(
+        format!("{:?}", uns_list!(
+            UnstructuredNode::Sqrt(tokens!(2)),
+            UnstructuredNode::Root(tokens!(3), uns_list!(token!(var x))),
+            token!(/),
+            UnstructuredNode::Parentheses(uns_list!(token!(pi), UnstructuredNode::Power(tokens!(2)))),
+            UnstructuredNode::FunctionCall(Function::GreatestCommonDenominator, vec![tokens!(4), uns_list!()]),
+            UnstructuredNode::Styled(1, uns_list!(UnstructuredNode::Token(Token::Boolean(true)))),
+        )),
+        "sqrt[2] root[3][x] / (π ^[2]) gcd[4, ] style1[true]",
+    );
+    assert_eq!(format!("{:?}", NavPath::new(vec![3, 0, 3, 1, 1])), "3,0,3,1,1");
+}
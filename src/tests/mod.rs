@@ -8,4 +8,10 @@ mod navigation;
 mod manipulation;
 mod simplified;
 mod evaluation;
+mod worksheet;
+mod template;
+mod editor;
+mod calculator;
+#[cfg(feature = "redraw")]
+mod redraw;
 mod bench;
@@ -1,6 +1,6 @@
 use alloc::vec;
 
-use crate::{nav::NavPath, UnstructuredNodeList, node::unstructured::Navigable, UnstructuredNode, tests::util::complex_unstructured_expression, renderers::AsciiRenderer, Token};
+use crate::{nav::{NavPath, NavSelection, FractionNavigationOrder}, node::structured::EvaluationSettings, UnstructuredNodeList, node::unstructured::Navigable, UnstructuredNode, UnstructuredNodeRoot, tests::util::complex_unstructured_expression, renderers::AsciiRenderer, Token, error::{PathError, EvaluationError}};
 
 #[test]
 fn test_navigation() {
@@ -18,7 +18,7 @@ fn test_navigation() {
     );
 
     // Path 1: beginning
-    let mut path = NavPath::new(vec![0]);
+    let path = NavPath::new(vec![0]);
     let result = {
         let (node, i) = unstructured.navigate(&mut path.to_navigator());
         let node_ptr: *mut UnstructuredNodeList = node;
@@ -30,7 +30,7 @@ fn test_navigation() {
     );
 
     // Path 2: middle
-    let mut path = NavPath::new(vec![3]);
+    let path = NavPath::new(vec![3]);
     let result = {
         let (node, i) = unstructured.navigate(&mut path.to_navigator());
         let node_ptr: *mut UnstructuredNodeList = node;
@@ -42,7 +42,7 @@ fn test_navigation() {
     );
 
     // Path 3: nested
-    let mut path = NavPath::new(vec![6, 1, 1]);
+    let path = NavPath::new(vec![6, 1, 1]);
     let result = {
         let (node, i) = unstructured.navigate(&mut path.to_navigator());
         (node.clone(), i)
@@ -217,3 +217,176 @@ fn test_modification() {
         ],
     );
 }
+
+#[test]
+fn test_sqrt_to_root() {
+    let mut node = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::Sqrt(tokens!(2)),
+    ) };
+    let mut nav_path = NavPath::new(vec![2, 0, 1]);
+    let mut renderer = AsciiRenderer::default();
+
+    // Convert, and check the cursor is moved into the degree
+    assert!(node.sqrt_to_root(&mut nav_path, &mut renderer, None));
+    assert_eq!(node.root, uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::Root(uns_list!(), tokens!(2)),
+    ));
+    assert_eq!(nav_path, NavPath::new(vec![2, 0, 0]));
+
+    // Moving right from the degree enters the radicand, and then leaves the root
+    node.move_right(&mut nav_path, &mut renderer, None);
+    assert_eq!(nav_path, NavPath::new(vec![2, 1, 0]));
+    node.move_right(&mut nav_path, &mut renderer, None);
+    node.move_right(&mut nav_path, &mut renderer, None);
+    assert_eq!(nav_path, NavPath::new(vec![3]));
+
+    // Moving left does the opposite
+    node.move_left(&mut nav_path, &mut renderer, None);
+    assert_eq!(nav_path, NavPath::new(vec![2, 1, 1]));
+    node.move_left(&mut nav_path, &mut renderer, None);
+    node.move_left(&mut nav_path, &mut renderer, None);
+    assert_eq!(nav_path, NavPath::new(vec![2, 0, 0]));
+    node.move_left(&mut nav_path, &mut renderer, None);
+    assert_eq!(nav_path, NavPath::new(vec![2]));
+
+    // Outside of a square root, nothing happens
+    assert!(!node.sqrt_to_root(&mut nav_path, &mut renderer, None));
+    assert_eq!(nav_path, NavPath::new(vec![2]));
+}
+
+#[test]
+fn test_evaluate_selection() {
+    // 2*3+4/5
+    let mut node = UnstructuredNodeRoot { root: uns_list!(
+        token!(2),
+        token!(*),
+        token!(3),
+        token!(+),
+        uns_frac!(tokens!(4), tokens!(5)),
+    ) };
+    let mut nav_path = NavPath::new(vec![0]);
+    let mut renderer = AsciiRenderer::default();
+    let settings = EvaluationSettings::default();
+
+    // Evaluating a range leaves the tree untouched
+    let range = NavSelection::Range(NavPath::new(vec![0]), 3);
+    assert_eq!(node.selected_items(&range), Ok(tokens!(2 * 3)));
+    assert_eq!(node.evaluate_selection(&range, &settings), Ok(rat!(6)));
+
+    // Selections which don't fit in the tree are errors, rather than panicking
+    let too_long = NavSelection::Range(NavPath::new(vec![3]), 3);
+    assert_eq!(node.selected_items(&too_long), Err(PathError::InvalidPath));
+    assert_eq!(
+        node.evaluate_selection_in_place(&too_long, &mut nav_path, &mut renderer, None, &settings),
+        Err(EvaluationError::Path(PathError::InvalidPath)),
+    );
+    let missing = NavSelection::Container(NavPath::new(vec![1, 0, 0]));
+    assert_eq!(node.evaluate_selection(&missing, &settings), Err(EvaluationError::Path(PathError::InvalidPath)));
+
+    // Errors are reported without modifying anything
+    let invalid = NavSelection::Range(NavPath::new(vec![1]), 3);
+    assert!(node.evaluate_selection_in_place(&invalid, &mut nav_path, &mut renderer, None, &settings).is_err());
+    assert_eq!(nav_path, NavPath::new(vec![0]));
+
+    // Replace the range with its result
+    assert_eq!(
+        node.evaluate_selection_in_place(&range, &mut nav_path, &mut renderer, None, &settings),
+        Ok(rat!(6)),
+    );
+    assert_eq!(node.root, uns_list!(
+        token!(6),
+        token!(+),
+        uns_frac!(tokens!(4), tokens!(5)),
+    ));
+    assert_eq!(nav_path, NavPath::new(vec![1]));
+
+    // Containers select a whole list, such as the top of a fraction
+    let container = NavSelection::Container(NavPath::new(vec![2, 0, 1]));
+    assert_eq!(node.evaluate_selection(&container, &settings), Ok(rat!(4)));
+
+    // Results which are fractions become fraction nodes
+    let all = NavSelection::Container(NavPath::new(vec![0]));
+    assert_eq!(
+        node.evaluate_selection_in_place(&all, &mut nav_path, &mut renderer, None, &settings),
+        Ok(rat!(34, 5)),
+    );
+    assert_eq!(node.root, uns_list!(uns_frac!(tokens!(3 4), tokens!(5))));
+    assert_eq!(nav_path, NavPath::new(vec![1]));
+}
+
+#[test]
+fn test_fraction_navigation_order() {
+    // 1+(2/3)4
+    let mut root = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        uns_frac!(tokens!(2), tokens!(3)),
+        token!(4),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+
+    // By default, moving right out of the numerator leaves the fraction
+    let mut path = NavPath::new(vec![2, 0, 1]);
+    root.move_right(&mut path, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![3]));
+
+    // In reading order, it moves into the denominator...
+    let order = FractionNavigationOrder::ReadingOrder;
+    let mut path = NavPath::new(vec![2, 0, 1]);
+    root.move_right_with_order(&mut path, order, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![2, 1, 0]));
+    root.move_right_with_order(&mut path, order, &mut renderer, None);
+    root.move_right_with_order(&mut path, order, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![3]));
+
+    // ...and the same applies in reverse
+    root.move_left_with_order(&mut path, order, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![2, 1, 1]));
+    root.move_left_with_order(&mut path, order, &mut renderer, None);
+    root.move_left_with_order(&mut path, order, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![2, 0, 1]));
+
+    // Whereas by default, moving left enters the numerator
+    let mut path = NavPath::new(vec![3]);
+    root.move_left(&mut path, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![2, 0, 1]));
+}
+
+#[test]
+fn test_structural_editing() {
+    // 1+(2/3)
+    let mut root = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        uns_frac!(tokens!(2), tokens!(3)),
+    ) };
+
+    // Insert into the denominator, giving 1+(2/34)
+    root.insert_at(&NavPath::new(vec![2, 1, 1]), token!(4)).unwrap();
+    assert_eq!(root.root.items[2], uns_frac!(tokens!(2), tokens!(3 4)));
+
+    // Replace the numerator's only token with a square root, giving 1+(√5/34)
+    assert_eq!(
+        root.replace_at(&NavPath::new(vec![2, 0, 0]), vec![UnstructuredNode::Sqrt(tokens!(5))]),
+        Ok(token!(2)),
+    );
+    assert_eq!(root.root.items[2], uns_frac!(uns_list!(UnstructuredNode::Sqrt(tokens!(5))), tokens!(3 4)));
+
+    // Remove the "1+", giving (√5/34)
+    assert_eq!(root.remove_at(&NavPath::new(vec![0])), Ok(token!(1)));
+    assert_eq!(root.remove_at(&NavPath::new(vec![0])), Ok(token!(+)));
+    assert_eq!(root.root.items.len(), 1);
+
+    // Invalid paths are rejected without changing anything
+    let before = root.clone();
+    assert_eq!(root.insert_at(&NavPath::new(vec![0, 2, 0]), token!(1)), Err(PathError::InvalidPath));
+    assert_eq!(root.insert_at(&NavPath::new(vec![0, 1]), token!(1)), Err(PathError::InvalidPath));
+    assert_eq!(root.insert_at(&NavPath::new(vec![0, 1, 3]), token!(1)), Err(PathError::InvalidPath));
+    assert_eq!(root.remove_at(&NavPath::new(vec![0, 0, 0, 0, 1])), Err(PathError::NoNodeAtPath));
+    assert_eq!(root.replace_at(&NavPath::new(vec![1]), vec![]), Err(PathError::NoNodeAtPath));
+    assert_eq!(root, before);
+}
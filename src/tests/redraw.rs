@@ -0,0 +1,39 @@
+use crate::{prelude::*, redraw::RedrawTracker, render::Area};
+
+#[test]
+fn test_redraw_tracker() {
+    let mut renderer = AsciiRenderer::default();
+    let mut tracker = RedrawTracker::new();
+    let mut calc = SimpleCalculator::new();
+
+    // The first draw always happens, but drawing again with nothing changed doesn't
+    assert!(calc.draw_if_changed(&mut renderer, &mut tracker));
+    assert!(!calc.draw_if_changed(&mut renderer, &mut tracker));
+
+    // Editing the expression needs a redraw
+    calc.press(CalculatorKey::Char('1'), &mut renderer);
+    calc.press(CalculatorKey::Char('2'), &mut renderer);
+    assert!(calc.draw_if_changed(&mut renderer, &mut tracker));
+    assert_eq!(renderer.lines, ["12|"]);
+
+    // So does moving the cursor...
+    calc.press(CalculatorKey::Left, &mut renderer);
+    assert!(calc.draw_if_changed(&mut renderer, &mut tracker));
+
+    // ...but not a key press which changes nothing
+    calc.press(CalculatorKey::Up, &mut renderer);
+    assert!(!calc.draw_if_changed(&mut renderer, &mut tracker));
+
+    // Changing the viewport needs a redraw, even though the layout is the same
+    let layout = renderer.layout(&calc.editor.root, None, LayoutComputationProperties::default());
+    let viewport = Viewport::new(Area::new(10, 1));
+    assert!(tracker.draw_if_changed(&mut renderer, &layout, None));
+    assert!(tracker.needs_redraw(&layout, Some(&viewport)));
+    assert!(!tracker.needs_redraw(&layout, None));
+
+    // Invalidating forces a redraw
+    tracker.invalidate();
+    assert!(tracker.needs_redraw(&layout, None));
+    tracker.mark_drawn(&layout, None);
+    assert!(!tracker.needs_redraw(&layout, None));
+}
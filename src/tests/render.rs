@@ -1,6 +1,6 @@
-use alloc::vec;
+use alloc::{vec, vec::Vec};
 
-use crate::{StructuredNode, tests::util::complex_unstructured_expression, nav::NavPath, render::{Viewport, Area, CalculatedPoint, Layoutable, LayoutComputationProperties, Glyph}, UnstructuredNode, node::{structured::EvaluationSettings, unstructured::Upgradable}, UnstructuredNodeRoot, Number, renderers::AsciiRenderer};
+use crate::{StructuredNode, tests::util::complex_unstructured_expression, nav::{NavPath, NavPathNavigator}, render::{Viewport, Area, CalculatedPoint, Dimension, Layoutable, LayoutComputationProperties, Glyph, Renderer, ResultPlacement, LayoutLimits, ViewportGlyph, ViewportVisibility, CursorStyle, LayoutBounds, Decoration, LayoutBlock}, UnstructuredNode, node::{structured::EvaluationSettings, unstructured::Upgradable}, UnstructuredNodeRoot, Number, Token, renderers::AsciiRenderer, node::function::{Function, FunctionNameTable}, error::LayoutError};
 
 #[test]
 fn test_ascii_render() {
@@ -394,3 +394,912 @@ fn test_power_with_no_base() {
     let block = UnstructuredNodeRoot { root: uns_list!(UnstructuredNode::Power(uns_list!())) };
     block.layout(&mut AsciiRenderer::default(), None, LayoutComputationProperties::default());
 }
+
+#[test]
+fn test_power_of_ten() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(.),
+        token!(5),
+        UnstructuredNode::PowerOfTen(tokens!(- 3)),
+    ) };
+    assert_eq!(
+        render!(tree),
+        vec![
+            "      -3",
+            "1.5*10  ",
+        ],
+    );
+}
+
+#[test]
+fn test_root() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::Root(tokens!(3), tokens!(8)),
+    ) };
+    assert_eq!(
+        render!(tree),
+        vec![
+            "  3   ",
+            "   .-.",
+            "1+\\|8'",
+        ],
+    );
+    assert_eq!(
+        tree.upgrade().unwrap().evaluate(&EvaluationSettings::default()).unwrap(),
+        rat!(3)
+    );
+    assert_eq!(
+        render!(tree.upgrade().unwrap()),
+        render!(tree),
+    );
+}
+
+#[test]
+fn test_layout_with_result() {
+    // 1/2 + 1/3
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        uns_frac!(tokens!(1), tokens!(2)),
+        token!(+),
+        uns_frac!(tokens!(1), tokens!(3)),
+    ) };
+    let result = UnstructuredNodeRoot::from_number(
+        tree.upgrade().unwrap().evaluate(&EvaluationSettings::default()).unwrap()
+    );
+    let mut renderer = AsciiRenderer::default();
+
+    // Fits on one line, aligned along the baseline
+    let (layout, placement) = renderer.layout_with_result(&tree, None, &result, None, LayoutComputationProperties::default());
+    assert_eq!(placement, ResultPlacement::Right);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "1 1 5",
+            "-+-=-",
+            "2 3 6",
+        ],
+    );
+
+    // Too wide, so the result is placed below and right-aligned
+    let (layout, placement) = renderer.layout_with_result(&tree, None, &result, Some(4), LayoutComputationProperties::default());
+    assert_eq!(placement, ResultPlacement::Below);
+    assert_eq!(layout.baseline, 1);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "1 1",
+            "-+-",
+            "2 3",
+            "  5",
+            " =-",
+            "  6",
+        ],
+    );
+}
+
+#[test]
+fn test_function_name_table() {
+    static SPANISH: FunctionNameTable = FunctionNameTable {
+        names: &[(Function::Sine, "sen"), (Function::Tangent, "tg")],
+    };
+
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(3 0)]),
+        token!(+),
+        UnstructuredNode::FunctionCall(Function::Cosine, vec![tokens!(3 0)]),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+
+    // Names from the table are used, others fall back to the default
+    let properties = LayoutComputationProperties { function_names: &SPANISH, ..Default::default() };
+    let layout = renderer.layout(&tree, None, properties);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec!["sen(30)+cos(30)"]);
+
+    // Serialization is unaffected
+    assert_eq!(reserialize!(tree), tree);
+}
+
+#[test]
+fn test_function_parenthesis_elision() {
+    let properties = LayoutComputationProperties { elide_function_parentheses: true, ..Default::default() };
+    let mut renderer = AsciiRenderer::default();
+    let mut render_with_elision = |node: UnstructuredNodeRoot| {
+        let layout = renderer.layout(&node, None, properties);
+        renderer.draw_all_by_layout(&layout, None);
+        renderer.lines.clone()
+    };
+
+    // Simple arguments lose their parentheses
+    assert_eq!(
+        render_with_elision(UnstructuredNodeRoot { root: uns_list!(
+            UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(3 0)]),
+            token!(+),
+            UnstructuredNode::FunctionCall(Function::Cosine, vec![tokens!(pi)]),
+        ) }),
+        vec!["sin 30+cos π"],
+    );
+
+    // Compound or empty arguments, or multiple arguments, keep them
+    assert_eq!(
+        render_with_elision(UnstructuredNodeRoot { root: uns_list!(
+            UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(3 0 + 1)]),
+            UnstructuredNode::FunctionCall(Function::GreatestCommonDenominator, vec![tokens!(2), tokens!(4)]),
+        ) }),
+        vec!["sin(30+1)gcd(2,4)"],
+    );
+
+    // Parentheses are kept when anything but an operator follows the call, so that it doesn't run
+    // into the argument
+    assert_eq!(
+        render_with_elision(UnstructuredNodeRoot { root: uns_list!(
+            UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(3 0)]),
+            token!(5),
+            token!(*),
+            UnstructuredNode::FunctionCall(Function::Cosine, vec![uns_list!(token!(var x))]),
+            token!(.),
+            token!(+),
+            UnstructuredNode::FunctionCall(Function::Tangent, vec![tokens!(1)]),
+            UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(2)]),
+        ) }),
+        vec!["sin(30)5*cos(x).+tan(1)sin 2"],
+    );
+
+    // Styled nodes are looked through on either side
+    assert_eq!(
+        render_with_elision(UnstructuredNodeRoot { root: uns_list!(
+            UnstructuredNode::Styled(1, uns_list!(UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(2)]))),
+            UnstructuredNode::Styled(1, uns_list!(token!(+))),
+            UnstructuredNode::Styled(1, tokens!(3)),
+            UnstructuredNode::FunctionCall(Function::Cosine, vec![tokens!(1)]),
+            UnstructuredNode::Styled(1, tokens!(4)),
+        ) }),
+        vec!["sin 2+3cos(1)4"],
+    );
+
+    // Structured nodes follow the same rules
+    let tree = StructuredNode::FunctionCall(Function::Sine, vec![StructuredNode::Number(rat!(1, 2))]);
+    let layout = renderer.layout(&tree, None, properties);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "   /1\\",
+            "sin|-|",
+            "   \\2/",
+        ],
+    );
+    let tree = StructuredNode::FunctionCall(Function::Sine, vec![StructuredNode::Variable('x')]);
+    let layout = renderer.layout(&tree, None, properties);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec!["sin x"]);
+    let tree = StructuredNode::Power(Box::new(tree), Box::new(StructuredNode::Number(rat!(2))));
+    let layout = renderer.layout(&tree, None, properties);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "      2",
+            "sin(x) ",
+        ],
+    );
+}
+
+#[test]
+fn test_function_argument_alignment() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::FunctionCall(Function::GreatestCommonDenominator, vec![
+            uns_list!(uns_frac!(tokens!(1), uns_list!(uns_frac!(tokens!(2), tokens!(3))))),
+            uns_list!(token!(2), UnstructuredNode::Power(tokens!(3))),
+        ]),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+    let mut render_with_properties = |properties| {
+        let layout = renderer.layout(&tree, None, properties);
+        renderer.draw_all_by_layout(&layout, None);
+        renderer.lines.clone()
+    };
+
+    // Arguments of different heights are aligned along their baselines, with the name on the same
+    // baseline by default
+    assert_eq!(
+        render_with_properties(LayoutComputationProperties::default()),
+        vec![
+            "     /1  3\\",
+            "1+gcd|-,2 |",
+            "     |2   |",
+            "     |-   |",
+            "     \\3   /",
+        ],
+    );
+
+    // The name can be centred against the arguments instead, without moving anything else
+    assert_eq!(
+        render_with_properties(LayoutComputationProperties { centre_function_names: true, ..Default::default() }),
+        vec![
+            "     /1  3\\",
+            "1+   |-,2 |",
+            "  gcd|2   |",
+            "     |-   |",
+            "     \\3   /",
+        ],
+    );
+}
+
+#[test]
+fn test_styled_nodes() {
+    // 1+[2*3] with the multiplication emphasised
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::Styled(3, tokens!(2 * 3)),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+
+    // Styles flow into the sized glyphs
+    let layout = renderer.layout(&tree, None, LayoutComputationProperties::default());
+    let mut styles = layout.glyphs.iter().map(|(g, p)| (p.x, g.style)).collect::<Vec<_>>();
+    styles.sort_unstable();
+    assert_eq!(styles, vec![(0, 0), (1, 0), (2, 3), (3, 3), (4, 3)]);
+
+    // Styled nodes don't affect rendering, evaluation or navigation
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec!["1+2*3"]);
+    assert_eq!(tree.upgrade().unwrap().evaluate(&EvaluationSettings::default()), Ok(rat!(7)));
+    let mut tree = tree;
+    let mut path = NavPath::new(vec![2]);
+    tree.move_right(&mut path, &mut renderer, None);
+    assert_eq!(path, NavPath::new(vec![2, 0, 0]));
+    assert_eq!(reserialize!(tree), tree);
+}
+
+#[test]
+fn test_bounded_layout() {
+    let mut renderer = AsciiRenderer::default();
+    let properties = LayoutComputationProperties::default();
+    let limits = LayoutLimits { max_glyphs: 100, max_area: None };
+
+    // Small trees lay out as normal, and the bound is never exceeded
+    let tree = complex_unstructured_expression();
+    let layout = renderer.try_layout(&tree, None, properties, limits).unwrap();
+    assert_eq!(layout.glyphs.len(), renderer.layout(&tree, None, properties).glyphs.len());
+    assert!(layout.glyphs.len() <= tree.glyph_count_bound());
+    let structured = tree.upgrade().unwrap();
+    assert!(renderer.layout(&structured, None, properties).glyphs.len() <= structured.glyph_count_bound());
+
+    // The cursor counts towards the limit
+    let tree = UnstructuredNodeRoot { root: tokens!(1 2 3) };
+    let path = NavPath::new(vec![0]);
+    let limits = LayoutLimits { max_glyphs: 3, max_area: None };
+    assert!(renderer.try_layout(&tree, None, properties, limits).is_ok());
+    assert_eq!(
+        renderer.try_layout(&tree, Some(&mut path.to_navigator()), properties, limits).unwrap_err(),
+        LayoutError::TooManyGlyphs,
+    );
+
+    // Huge trees are rejected
+    let mut tree = UnstructuredNodeRoot::new();
+    for _ in 0..1000 {
+        tree.root = uns_list!(uns_frac!(tree.root, tokens!(1)));
+    }
+    assert_eq!(renderer.try_layout(&tree, None, properties, limits).unwrap_err(), LayoutError::TooManyGlyphs);
+
+    // Layouts can also be limited by area
+    let tree = UnstructuredNodeRoot { root: uns_list!(uns_frac!(tokens!(1), tokens!(2))) };
+    let limits = LayoutLimits { max_glyphs: 100, max_area: Some(Area::new(10, 2)) };
+    assert_eq!(renderer.try_layout(&tree, None, properties, limits).unwrap_err(), LayoutError::TooLarge);
+
+    // Items without a bound of their own are assumed to be too large
+    struct Unbounded;
+    impl Layoutable for Unbounded {
+        fn layout(&self, renderer: &mut impl Renderer, path: Option<&mut NavPathNavigator>, properties: LayoutComputationProperties) -> LayoutBlock {
+            tokens!(1).layout(renderer, path, properties)
+        }
+    }
+    let limits = LayoutLimits { max_glyphs: 100, max_area: None };
+    assert_eq!(renderer.try_layout(&Unbounded, None, properties, limits).unwrap_err(), LayoutError::TooManyGlyphs);
+}
+
+#[test]
+fn test_boolean_rendering() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        UnstructuredNode::FunctionCall(Function::And, vec![
+            uns_list!(UnstructuredNode::Token(Token::Boolean(true))),
+            uns_list!(UnstructuredNode::FunctionCall(Function::Not, vec![
+                uns_list!(UnstructuredNode::Token(Token::Boolean(false))),
+            ])),
+        ])
+    ) };
+    let mut renderer = AsciiRenderer::default();
+
+    // As words, by default
+    let layout = renderer.layout(&tree, None, LayoutComputationProperties::default());
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec!["and(true,not(false))"]);
+
+    // Or as symbols
+    let properties = LayoutComputationProperties { boolean_symbols: true, ..Default::default() };
+    let layout = renderer.layout(&tree, None, properties);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec!["∧(⊤,¬(⊥))"]);
+}
+
+#[test]
+fn test_conditional_rendering() {
+    // if(x, 1/2, 3)
+    let tree = StructuredNode::Conditional(
+        Box::new(StructuredNode::Variable('x')),
+        Box::new(StructuredNode::Number(rat!(1, 2))),
+        Box::new(StructuredNode::Number(rat!(3))),
+    );
+    let mut renderer = AsciiRenderer::default();
+
+    let layout = renderer.layout(&tree, None, LayoutComputationProperties::default());
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "  /  1  \\",
+            "if|x,-,3|",
+            "  \\  2  /",
+        ],
+    );
+
+    let properties = LayoutComputationProperties { piecewise_conditionals: true, ..Default::default() };
+    let layout = renderer.layout(&tree, None, properties);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "/1           ",
+            "|- if x      ",
+            "|2           ",
+            "\\3 otherwise ",
+        ],
+    );
+}
+
+#[test]
+fn test_wrapped_ascii_render() {
+    // 12+34-56*-7+1/2+8
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1), token!(2), token!(+), token!(3), token!(4), token!(-), token!(5), token!(6),
+        token!(*), token!(-), token!(7), token!(+), uns_frac!(tokens!(1), tokens!(2)), token!(+), token!(8),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+
+    // Wide enough to fit on one line
+    renderer.draw_wrapped(&tree, 40, LayoutComputationProperties::default());
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "            1  ",
+            "12+34-56*-7+-+8",
+            "            2  ",
+        ],
+    );
+
+    // Breaks before top-level additions and subtractions, but not unary minus
+    renderer.draw_wrapped(&tree, 10, LayoutComputationProperties::default());
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "12+34\\",
+            "       1 ",
+            "-56*-7+-\\",
+            "       2 ",
+            "+8",
+        ],
+    );
+
+    // Terms too wide for a line get their own line
+    renderer.draw_wrapped(&tree, 1, LayoutComputationProperties::default());
+    assert_eq!(renderer.lines.len(), 7);
+    assert_eq!(renderer.lines[0], "12\\");
+}
+
+#[test]
+fn test_glyph_usage() {
+    // 1+1/2^1
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        uns_frac!(tokens!(1), tokens!(2)),
+        UnstructuredNode::Power(tokens!(1)),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+    let layout = renderer.layout(&tree, None, LayoutComputationProperties::default());
+    let mut usage = layout.glyph_usage()
+        .into_iter()
+        .map(|u| (u.glyph, u.size_reduction_level, u.area, u.count))
+        .collect::<Vec<_>>();
+    usage.sort_by_key(|(g, l, _, _)| (alloc::format!("{:?}", g), *l));
+
+    assert_eq!(usage, vec![
+        (Glyph::Add, 0, Area::square(1), 1),
+        (Glyph::Digit { number: 1 }, 0, Area::square(1), 2),
+        (Glyph::Digit { number: 1 }, 1, Area::square(1), 1),
+        (Glyph::Digit { number: 2 }, 0, Area::square(1), 1),
+        (Glyph::Fraction { inner_width: 1 }, 0, Area::new(1, 1), 1),
+    ]);
+    assert_eq!(layout.glyph_usage().iter().map(|u| u.count).sum::<usize>(), layout.glyphs.len());
+}
+
+#[test]
+fn test_debug_render() {
+    // 12+3/(empty denominator)
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1), token!(2), token!(+), uns_frac!(tokens!(3), tokens!()),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+    renderer.draw_debug(&tree, LayoutComputationProperties::default());
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "+----+",
+            "|   3|",
+            ">12+-<",
+            "|   X|",
+            "+----+",
+            "0 Digit(1) at (0, 1), 1x1",
+            "1 Digit(2) at (1, 1), 1x1",
+            "2 Add at (2, 1), 1x1",
+            "3 Fraction at (3, 0), 1x3",
+            "3,0,0 Digit(3) at (3, 0), 1x1",
+        ],
+    );
+
+    // Powers are measured from their exponent, so they stay superscripts
+    let tree = UnstructuredNodeRoot { root: uns_list!(token!(2), UnstructuredNode::Power(tokens!(3))) };
+    renderer.draw_debug(&tree, LayoutComputationProperties::default());
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "+--+",
+            "| 3|",
+            ">2 <",
+            "+--+",
+            "0 Digit(2) at (0, 1), 1x1",
+            "1 Power at (1, 0), 1x1",
+            "1,0,0 Digit(3) at (1, 0), 1x1",
+        ],
+    );
+
+    // Nodes which are already styled are retagged, rather than wrapped again
+    let tree = UnstructuredNodeRoot { root: uns_list!(UnstructuredNode::Styled(9, tokens!(4 5))) };
+    renderer.draw_debug(&tree, LayoutComputationProperties::default());
+    assert_eq!(
+        renderer.lines,
+        vec![
+            "+--+",
+            ">45<",
+            "+--+",
+            "0 Styled at (0, 0), 2x1",
+            "0,0,0 Digit(4) at (0, 0), 1x1",
+            "0,0,1 Digit(5) at (1, 0), 1x1",
+        ],
+    );
+}
+
+#[test]
+fn test_units_per_pixel() {
+    // A renderer where each digit is 1.5 pixels wide, measured in quarter-pixels
+    struct QuarterPixelRenderer {
+        size: Option<Area>,
+        drawn: Vec<ViewportGlyph>,
+    }
+
+    impl Renderer for QuarterPixelRenderer {
+        fn size(&mut self, glyph: Glyph, _: u32) -> Area {
+            match glyph {
+                Glyph::Cursor { height, .. } => Area::new(0, height),
+                _ => Area::new(6, 4),
+            }
+        }
+
+        fn init(&mut self, size: Area) { self.size = Some(size); }
+        fn draw(&mut self, glyph: ViewportGlyph) { self.drawn.push(glyph); }
+        fn units_per_pixel(&self) -> Dimension { 4 }
+    }
+
+    let mut renderer = QuarterPixelRenderer { size: None, drawn: vec![] };
+    let layout = renderer.draw_all(
+        &UnstructuredNodeRoot { root: tokens!(1 2 3) },
+        None,
+        None,
+    );
+    assert_eq!(layout.area, Area::new(18, 4));
+
+    // Each glyph is rounded to whole pixels, without leaving gaps or overlapping
+    assert_eq!(renderer.size, Some(Area::new(5, 1)));
+    renderer.drawn.sort_by_key(|g| g.point.x);
+    assert_eq!(
+        renderer.drawn.iter().map(|g| (g.point.x, g.glyph.area.width)).collect::<Vec<_>>(),
+        vec![(0, 2), (2, 1), (3, 2)],
+    );
+
+    // Viewports are measured in layout units
+    let viewport = Viewport::new(Area::new(4, 1).from_pixels(4));
+    assert_eq!(viewport.size, Area::new(16, 4));
+    renderer.drawn.clear();
+    renderer.draw_all_by_layout(&layout, Some(&viewport));
+    assert_eq!(renderer.size, Some(Area::new(4, 1)));
+    renderer.drawn.sort_by_key(|g| g.point.x);
+    assert_eq!(
+        renderer.drawn[2].visibility,
+        ViewportVisibility::Clipped {
+            invisible: false,
+            top_clip: 0,
+            bottom_clip: 0,
+            left_clip: 0,
+            right_clip: 1,
+        },
+    );
+
+    // Zero units per pixel would divide by zero, so it's treated as one
+    assert_eq!(Area::new(3, 2).to_pixels(0), Area::new(3, 2));
+}
+
+#[test]
+fn test_checked_offsets() {
+    let point = CalculatedPoint { x: 2, y: 1 };
+    assert_eq!(point.checked_dx(-2), Some(CalculatedPoint { x: 0, y: 1 }));
+    assert_eq!(point.checked_dx(-3), None);
+    assert_eq!(point.checked_dy(-1), Some(CalculatedPoint { x: 2, y: 0 }));
+    assert_eq!(point.checked_dy(-2), None);
+
+    // 1/2 is three rows tall, with its baseline in the middle
+    let mut renderer = AsciiRenderer::default();
+    let layout = renderer.layout(
+        &UnstructuredNodeRoot { root: uns_list!(uns_frac!(tokens!(1), tokens!(2))) },
+        None,
+        LayoutComputationProperties::default(),
+    );
+    assert_eq!(layout.baseline, 1);
+
+    let moved = layout.offset(1, 1).checked_offset(-1, -1).unwrap();
+    assert_eq!(moved.glyphs, layout.glyphs);
+    assert_eq!(moved.baseline, layout.baseline);
+    assert_eq!(moved.area, layout.area);
+
+    assert!(layout.checked_offset(-1, 0).is_none());
+    assert!(layout.checked_offset(0, -1).is_none());
+}
+
+#[test]
+#[should_panic]
+fn test_negative_offset_panics() {
+    CalculatedPoint { x: 0, y: 0 }.dx(-1);
+}
+
+#[test]
+fn test_cursor_styles() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1), uns_frac!(tokens!(2 3), tokens!(4)),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+    let cursor = |layout: &crate::render::LayoutBlock| layout.glyphs.iter()
+        .find(|(g, _)| matches!(g.glyph, Glyph::Cursor { .. }))
+        .unwrap().0.glyph;
+
+    // The cursor knows the width of the item after it, or of a digit at the end of the list
+    let properties = LayoutComputationProperties { cursor_style: CursorStyle::Block, ..Default::default() };
+    let layout = renderer.layout(&tree, Some(&mut NavPath::new(vec![1]).to_navigator()), properties);
+    assert_eq!(
+        cursor(&layout),
+        Glyph::Cursor { height: 3, next_width: 2, style: CursorStyle::Block, visible: true },
+    );
+    let layout = renderer.layout(&tree, Some(&mut NavPath::new(vec![2]).to_navigator()), properties);
+    assert_eq!(
+        cursor(&layout),
+        Glyph::Cursor { height: 3, next_width: 1, style: CursorStyle::Block, visible: true },
+    );
+
+    // Blinking doesn't change the layout
+    let mut layout = renderer.layout(&tree, Some(&mut NavPath::new(vec![1]).to_navigator()), LayoutComputationProperties::default());
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec![
+        " |23",
+        "1|--",
+        " | 4",
+    ]);
+    layout.set_cursor_visible(false);
+    renderer.draw_all_by_layout(&layout, None);
+    assert_eq!(renderer.lines, vec![
+        "  23",
+        "1 --",
+        "   4",
+    ]);
+}
+
+#[test]
+fn test_shared_layout() {
+    // Trees, paths and layouts can be shared with a background thread, to lay out an expression
+    // while it continues to be displayed
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<UnstructuredNodeRoot>();
+    assert_send_sync::<NavPath>();
+    assert_send_sync::<crate::render::LayoutBlock>();
+
+    // Laying out only needs shared references to the tree and path
+    let tree = complex_unstructured_expression();
+    let path = NavPath::new(vec![3, 0, 0]);
+    let (shared_tree, shared_path) = (&tree, &path);
+    let first = AsciiRenderer::default().layout(shared_tree, Some(&mut shared_path.to_navigator()), LayoutComputationProperties::default());
+    let second = AsciiRenderer::default().layout(shared_tree, Some(&mut shared_path.to_navigator()), LayoutComputationProperties::default());
+    assert_eq!(first.glyphs, second.glyphs);
+}
+
+#[test]
+fn test_deep_power_nesting() {
+    // Builds 2^(2^(2^...)), with the given number of powers
+    let nested_powers = |depth| {
+        let mut list = tokens!(2);
+        for _ in 0..depth {
+            list = uns_list!(token!(2), UnstructuredNode::Power(list));
+        }
+        UnstructuredNodeRoot { root: list }
+    };
+
+    // Powers beyond the maximum superscript depth are laid out inline
+    let tree = nested_powers(5);
+    assert_eq!(
+        render!(tree),
+        vec![
+            "    2^2",
+            "   2   ",
+            "  2    ",
+            " 2     ",
+            "2      ",
+        ],
+    );
+    assert_eq!(
+        render!(tree.upgrade().unwrap()),
+        render!(tree),
+    );
+
+    // Even very deep nesting stays a reasonable size, and glyphs stop shrinking
+    let tree = nested_powers(30);
+    let properties = LayoutComputationProperties::default();
+    for block in [
+        AsciiRenderer::default().layout(&tree, None, properties),
+        tree.upgrade().unwrap().layout(&mut AsciiRenderer::default(), None, properties),
+    ] {
+        assert_eq!(block.area.height, 5);
+        assert!(block.glyphs.iter().all(|(g, _)| g.size_reduction_level <= properties.max_size_reduction_level));
+        assert_eq!(block.glyphs.iter().filter(|(g, _)| g.glyph == Glyph::Caret).count(), 26);
+    }
+}
+
+#[test]
+fn test_parenthesis_baseline() {
+    let paren_glyphs = |inner: crate::UnstructuredNodeList| {
+        let tree = UnstructuredNodeRoot { root: uns_list!(UnstructuredNode::Parentheses(inner)) };
+        AsciiRenderer::default().layout(&tree, None, LayoutComputationProperties::default()).glyphs
+            .into_iter()
+            .filter_map(|(g, _)| match g.glyph {
+                Glyph::LeftParenthesis { inner_height, inner_baseline }
+                | Glyph::RightParenthesis { inner_height, inner_baseline } => Some((inner_height, inner_baseline)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Content which is mostly below the baseline...
+    assert_eq!(
+        paren_glyphs(uns_list!(uns_frac!(tokens!(1), uns_list!(uns_frac!(tokens!(2), tokens!(3)))))),
+        vec![(5, 1), (5, 1)],
+    );
+
+    // ...can be told apart from content which is mostly above it
+    assert_eq!(
+        paren_glyphs(uns_list!(uns_frac!(uns_list!(uns_frac!(tokens!(1), tokens!(2))), tokens!(3)))),
+        vec![(5, 3), (5, 3)],
+    );
+
+    // Both are converted when drawing with sub-pixel units
+    assert_eq!(
+        Glyph::LeftParenthesis { inner_height: 10, inner_baseline: 3 }.to_pixels(2),
+        Glyph::LeftParenthesis { inner_height: 5, inner_baseline: 2 },
+    );
+}
+
+#[test]
+fn test_decomposed_radicals() {
+    // An ASCII renderer which draws radicals from their components
+    #[derive(Default)]
+    struct DecomposingRenderer(AsciiRenderer);
+    impl Renderer for DecomposingRenderer {
+        fn size(&mut self, glyph: Glyph, size_reduction_level: u32) -> Area { self.0.size(glyph, size_reduction_level) }
+        fn init(&mut self, size: Area) { self.0.init(size) }
+        fn draw(&mut self, glyph: ViewportGlyph) { self.0.draw(glyph) }
+        fn square_root_padding(&self) -> Dimension { self.0.square_root_padding() }
+        fn decompose_radicals(&self) -> bool { true }
+    }
+
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::Sqrt(uns_list!(uns_frac!(tokens!(2), tokens!(3)))),
+        token!(+),
+        UnstructuredNode::Root(tokens!(3), tokens!(8)),
+    ) };
+    let mut renderer = DecomposingRenderer::default();
+    renderer.draw_all(&tree, None, None);
+    assert_eq!(
+        renderer.0.lines,
+        vec![
+            "   .-- 3   ",
+            "   |2   .--",
+            "1+ |- +\\|8 ",
+            "  \\|3      ",
+        ],
+    );
+
+    // The layout is the same shape as an undecomposed radical
+    let layout = renderer.layout(&tree, None, LayoutComputationProperties::default());
+    assert!(layout.glyphs.iter().all(|(g, _)| !matches!(g.glyph, Glyph::Sqrt { .. })));
+    assert_eq!(layout.area, AsciiRenderer::default().layout(&tree, None, LayoutComputationProperties::default()).area);
+
+    // The extra glyphs are accounted for by the bound used to reject large layouts
+    assert!(layout.glyphs.len() <= tree.glyph_count_bound());
+    let structured = tree.upgrade().unwrap();
+    assert!(renderer.layout(&structured, None, LayoutComputationProperties::default()).glyphs.len() <= structured.glyph_count_bound());
+}
+
+#[test]
+fn test_bounds_of() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        uns_frac!(
+            tokens!(2 3),
+            uns_list!(token!(4), UnstructuredNode::Power(uns_list!(UnstructuredNode::Styled(5, tokens!(5))))),
+        ),
+    ) };
+    assert_eq!(
+        render!(tree),
+        vec![
+            "  23",
+            "1+--",
+            "   5",
+            "  4 ",
+        ],
+    );
+
+    let mut renderer = AsciiRenderer::default();
+    let mut bounds_of = |path: Vec<usize>, cursor: Option<Vec<usize>>| renderer.bounds_of(
+        &tree,
+        &NavPath::new(path),
+        cursor.map(NavPath::new).as_ref(),
+        LayoutComputationProperties::default(),
+    ).map(|b| (b.point.x, b.point.y, b.area.width, b.area.height));
+
+    // Nodes at the top level and within slots, including powers and styled nodes
+    assert_eq!(bounds_of(vec![1], None), Some((1, 1, 1, 1)));
+    assert_eq!(bounds_of(vec![2], None), Some((2, 0, 2, 4)));
+    assert_eq!(bounds_of(vec![2, 0, 1], None), Some((3, 0, 1, 1)));
+    assert_eq!(bounds_of(vec![2, 1, 1], None), Some((3, 2, 1, 1)));
+    assert_eq!(bounds_of(vec![2, 1, 1, 0, 0], None), Some((3, 2, 1, 1)));
+
+    // Paths to the end of a list don't point at a node
+    assert_eq!(bounds_of(vec![3], None), None);
+    assert_eq!(bounds_of(vec![2, 1, 2], None), None);
+    assert_eq!(bounds_of(vec![2, 2, 0], None), None);
+
+    // Neither does an empty path
+    assert_eq!(bounds_of(vec![], None), None);
+
+    // The cursor is taken into account, including when it's inside the node
+    assert_eq!(bounds_of(vec![2], Some(vec![1])), Some((3, 0, 2, 4)));
+    assert_eq!(bounds_of(vec![2], Some(vec![2, 1, 1, 0, 0, 0, 1])), Some((2, 0, 3, 4)));
+
+    // Finding a node doesn't change the layout, so elided parentheses stay elided
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        UnstructuredNode::FunctionCall(Function::Sine, vec![tokens!(2)]),
+        token!(+),
+        token!(3),
+    ) };
+    let properties = LayoutComputationProperties { elide_function_parentheses: true, ..LayoutComputationProperties::default() };
+    let layout = renderer.layout(&tree, None, properties);
+    assert_eq!(layout.bounds_of(&NavPath::new(vec![1])).map(|b| b.point.x), Some(5));
+    assert_eq!(renderer.bounds_of(&tree, &NavPath::new(vec![1]), None, properties), layout.bounds_of(&NavPath::new(vec![1])));
+
+    // Regions can be used to find what was tapped
+    let bounds = LayoutBounds { point: CalculatedPoint { x: 2, y: 0 }, area: Area::new(2, 4) };
+    assert!(bounds.contains(CalculatedPoint { x: 3, y: 3 }));
+    assert!(!bounds.contains(CalculatedPoint { x: 4, y: 3 }));
+}
+
+#[test]
+fn test_decorations() {
+    let tree = UnstructuredNodeRoot { root: uns_list!(
+        token!(1),
+        token!(+),
+        UnstructuredNode::FunctionCall(Function::GreatestCommonDenominator, vec![tokens!(2), tokens!(0)]),
+        token!(+),
+        UnstructuredNode::Root(tokens!(3), tokens!(8)),
+    ) };
+    let mut renderer = AsciiRenderer::default();
+    let mut draw_decorated = |cursor: Option<NavPath>, decorations: &[(NavPath, Decoration)]| {
+        let layout = renderer.layout_decorated(&tree, cursor.as_ref(), decorations, LayoutComputationProperties::default());
+        renderer.draw_all_by_layout(&layout, None);
+        renderer.lines.clone()
+    };
+
+    // Underlines make the layout taller, and strike-throughs are drawn in the gaps between
+    // characters
+    assert_eq!(
+        draw_decorated(None, &[
+            (NavPath::new(vec![2]), Decoration::WavyUnderline),
+            (NavPath::new(vec![4]), Decoration::Strikethrough),
+        ]),
+        vec![
+            "           3   ",
+            "           -.-.",
+            "1+gcd(2,0)+\\|8'",
+            "  ~~~~~~~~     ",
+        ],
+    );
+
+    // Decorations follow the cursor, and paths without a node are ignored
+    assert_eq!(
+        draw_decorated(Some(NavPath::new(vec![2, 1, 0])), &[
+            (NavPath::new(vec![2, 1, 0]), Decoration::WavyUnderline),
+            (NavPath::new(vec![5]), Decoration::WavyUnderline),
+        ]),
+        vec![
+            "            3   ",
+            "             .-.",
+            "1+gcd(2,|0)+\\|8'",
+            "         ~      ",
+        ],
+    );
+}
+
+#[test]
+fn test_power_function_rendering() {
+    let call = |func, arg| UnstructuredNodeRoot { root: uns_list!(
+        token!(1), token!(+), UnstructuredNode::FunctionCall(func, vec![arg]),
+    ) };
+
+    // Reciprocals are written with a superscript -1, and complex arguments are parenthesised
+    assert_eq!(
+        render!(call(Function::Reciprocal, tokens!(2))),
+        vec![
+            "   -1",
+            "1+2  ",
+        ],
+    );
+    assert_eq!(
+        render!(call(Function::Reciprocal, tokens!(2 + 3))),
+        vec![
+            "       -1",
+            "1+(2+3)  ",
+        ],
+    );
+
+    // Powers of ten and e have the argument as the exponent, which the cursor can be placed in
+    assert_eq!(
+        render!(call(Function::PowerOfTen, tokens!(2 3)), Some(&mut NavPath::new(vec![2, 0, 1]).to_navigator())),
+        vec![
+            "    2|3",
+            "1+10   ",
+        ],
+    );
+    assert_eq!(
+        render!(call(Function::Exp, uns_list!(uns_frac!(tokens!(1), tokens!(2))))),
+        vec![
+            "   1",
+            "   -",
+            "   2",
+            "1+e ",
+        ],
+    );
+}
@@ -1,6 +1,8 @@
 use alloc::vec;
 
-use crate::{node::simplified::SimplifiedNode, UnstructuredNode};
+use alloc::boxed::Box;
+
+use crate::{node::{simplified::{SimplifiedNode, ReductionSettings}, assumptions::{Assumptions, Assumption}}, UnstructuredNode, serialize::Serializable, number::ZeroPowerZero, error::MathsError};
 
 #[test]
 fn test_simplify_structured() {
@@ -260,3 +262,81 @@ fn test_reduction() {
         ])
     );
 }
+
+#[test]
+fn test_reduction_with_assumptions() {
+    // √(x²)
+    let sqrt_x_squared = simplify!(uns_list!(
+        UnstructuredNode::Sqrt(uns_list!(
+            token!(var x),
+            UnstructuredNode::Power(tokens!(2)),
+        )),
+    ));
+
+    // With no assumptions, this can't be reduced, since x could be negative
+    let mut node = sqrt_x_squared.clone();
+    node.reduce().unwrap();
+    assert_eq!(
+        node,
+        SimplifiedNode::Power(
+            Box::new(SimplifiedNode::Power(
+                Box::new(SimplifiedNode::Variable('x')),
+                Box::new(SimplifiedNode::Number(rat!(2))),
+            )),
+            Box::new(SimplifiedNode::Number(rat!(1, 2))),
+        )
+    );
+
+    // Assuming x is positive implies it's non-negative, so this reduces to x
+    let mut assumptions = Assumptions::new();
+    assumptions.assume('x', Assumption::Positive);
+    let mut node = sqrt_x_squared.clone();
+    node.reduce_with_assumptions(&assumptions).unwrap();
+    assert_eq!(node, SimplifiedNode::Variable('x'));
+
+    // Assumptions can filter candidate values
+    assumptions.assume('n', Assumption::Integer);
+    assert!(assumptions.satisfied_by('x', &rat!(2)));
+    assert!(!assumptions.satisfied_by('x', &rat!(-2)));
+    assert!(!assumptions.satisfied_by('n', &rat!(1, 2)));
+    assert!(assumptions.satisfied_by('y', &rat!(-2)));
+
+    // Assumptions can be serialized
+    assert_eq!(Assumptions::deserialize(&mut assumptions.serialize().into_iter()), Some(assumptions));
+}
+
+#[test]
+fn test_reduction_zero_power_zero() {
+    let x_power_zero = SimplifiedNode::Power(
+        Box::new(SimplifiedNode::Variable('x')),
+        Box::new(SimplifiedNode::Number(rat!(0))),
+    );
+
+    // By default, x^0 = 1
+    let mut node = x_power_zero.clone();
+    node.reduce().unwrap();
+    assert_eq!(node, SimplifiedNode::Number(rat!(1)));
+
+    // If 0^0 is an error, then x^0 is left alone, since x could be 0...
+    let mut settings = ReductionSettings { zero_power_zero: ZeroPowerZero::Error, ..Default::default() };
+    let mut node = x_power_zero.clone();
+    node.reduce_with_settings(&settings).unwrap();
+    assert_eq!(node, x_power_zero);
+
+    // ...unless we know it isn't
+    settings.assumptions.assume('x', Assumption::Positive);
+    let mut node = x_power_zero.clone();
+    node.reduce_with_settings(&settings).unwrap();
+    assert_eq!(node, SimplifiedNode::Number(rat!(1)));
+
+    // 0^0 itself is an error
+    let mut node = SimplifiedNode::Power(
+        Box::new(SimplifiedNode::Number(rat!(0))),
+        Box::new(SimplifiedNode::Number(rat!(0))),
+    );
+    assert_eq!(node.reduce_with_settings(&settings), Err(MathsError::Indeterminate));
+
+    // Or undefined, if configured that way
+    settings.zero_power_zero = ZeroPowerZero::Undefined;
+    assert_eq!(node.reduce_with_settings(&settings), Err(MathsError::Undefined));
+}
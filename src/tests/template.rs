@@ -0,0 +1,36 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{template::Template, node::{structured::EvaluationSettings, unstructured::Upgradable}, serialize::Serializable, UnstructuredNodeRoot};
+
+#[test]
+fn test_templates() {
+    for template in Template::all() {
+        // Every built-in template is valid, and uses exactly the variables it describes
+        let mut reader = template.expression;
+        assert_eq!(UnstructuredNodeRoot::validate(&mut reader), Ok(()));
+        assert!(reader.is_empty());
+        let mut expected_variables = template.variables.iter().map(|v| v.name).collect::<Vec<_>>();
+        expected_variables.sort_unstable();
+        let mut variables = template.instantiate().upgrade().unwrap().variables();
+        variables.sort_unstable();
+        assert_eq!(variables, expected_variables);
+    }
+
+    let settings = EvaluationSettings::default();
+    let evaluate_filled = |name, values: &[_]| {
+        Template::find(name).unwrap().fill(values).upgrade().unwrap().evaluate(&settings).unwrap()
+    };
+
+    assert_eq!(evaluate_filled("Pythagoras", &[('a', rat!(3)), ('b', rat!(4))]), dec!(5));
+    assert_eq!(evaluate_filled("Quadratic formula", &[('a', rat!(1)), ('b', rat!(-3)), ('c', rat!(2))]), dec!(2));
+    assert_eq!(
+        evaluate_filled("Compound interest", &[('P', rat!(1000)), ('r', dec!(0.05)), ('n', rat!(1)), ('t', rat!(2))]),
+        dec_approx!(1102.5),
+    );
+
+    // Unfilled variables are left alone
+    let partial = Template::find("Pythagoras").unwrap().fill(&[('a', rat!(3))]);
+    assert_eq!(partial.upgrade().unwrap().variables(), vec!['b']);
+
+    assert_eq!(Template::find("Nonexistent"), None);
+}
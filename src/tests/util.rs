@@ -8,6 +8,7 @@ macro_rules! token {
     (*)             => { crate::UnstructuredNode::Token(crate::Token::Multiply) };
     (/)             => { crate::UnstructuredNode::Token(crate::Token::Divide) };
     (.)             => { crate::UnstructuredNode::Token(crate::Token::Point) };
+    (pi)            => { crate::UnstructuredNode::Token(crate::Token::Pi) };
     (var $v:ident)  => { crate::UnstructuredNode::Token(crate::Token::Variable(stringify!($v).chars().nth(0).unwrap())) };
     ($x:literal)    => { crate::UnstructuredNode::Token(crate::Token::Digit($x)) };
 }
@@ -115,3 +116,36 @@ pub fn complex_unstructured_expression() -> crate::UnstructuredNodeRoot {
         token!(2),
     ) }
 }
+
+/// The largest difference allowed between results calculated with and without
+/// [use_floats](crate::node::structured::EvaluationSettings::use_floats), relative to the size of
+/// the result (or absolute, for results smaller than 1). Floats are single-precision, so only agree
+/// with decimals to around 6 significant figures.
+pub const FLOAT_TOLERANCE: rust_decimal::Decimal = rust_decimal::Decimal::from_parts(1, 0, 0, false, 5);
+
+/// Calls `f` with the given settings, once evaluating with decimals and once with floats, so that a
+/// test covers both evaluation paths.
+pub fn with_each_backend(settings: crate::node::structured::EvaluationSettings, mut f: impl FnMut(&crate::node::structured::EvaluationSettings)) {
+    for use_floats in [false, true] {
+        f(&crate::node::structured::EvaluationSettings { use_floats, ..settings.clone() });
+    }
+}
+
+/// Asserts that two evaluation results are equal. If either is an approximation, they may differ by
+/// up to [FLOAT_TOLERANCE].
+#[track_caller]
+pub fn assert_approx_eq(
+    actual: Result<crate::Number, crate::error::MathsError>,
+    expected: Result<crate::Number, crate::error::MathsError>,
+) {
+    use crate::{Number, number::DecimalAccuracy::Approximation};
+
+    match (actual, expected) {
+        (Ok(a @ Number::Decimal(_, Approximation)), Ok(b)) | (Ok(a), Ok(b @ Number::Decimal(_, Approximation))) => {
+            let (a, b) = (a.to_decimal(), b.to_decimal());
+            let allowed = FLOAT_TOLERANCE * b.abs().max(rust_decimal::Decimal::ONE);
+            assert!((a - b).abs() <= allowed, "{} and {} differ by more than {}", a, b, allowed);
+        },
+        (actual, expected) => assert_eq!(actual, expected),
+    }
+}
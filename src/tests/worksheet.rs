@@ -0,0 +1,36 @@
+use alloc::{vec, vec::Vec};
+
+use crate::{worksheet::Worksheet, UnstructuredNodeRoot, node::structured::EvaluationSettings, error::{EvaluationError, MathsError}};
+
+#[test]
+fn test_worksheet_recalculation() {
+    let settings = EvaluationSettings::default();
+    let mut worksheet = Worksheet::new();
+
+    // a = 2, b = a*3, c = 10, b+c
+    worksheet.push_line(Some('a'), UnstructuredNodeRoot { root: tokens!(2) });
+    worksheet.push_line(Some('b'), UnstructuredNodeRoot { root: uns_list!(token!(var a), token!(*), token!(3)) });
+    worksheet.push_line(Some('c'), UnstructuredNodeRoot { root: tokens!(1 0) });
+    worksheet.push_line(None, UnstructuredNodeRoot { root: uns_list!(token!(var b), token!(+), token!(var c)) });
+
+    assert_eq!(worksheet.dependencies(1), vec![0]);
+    assert_eq!(worksheet.dependencies(3), vec![1, 2]);
+
+    // Everything is calculated initially
+    assert_eq!(worksheet.recalculate(&settings), vec![0, 1, 2, 3]);
+    let results = || worksheet.lines().iter().map(|l| l.result().cloned()).collect::<Vec<_>>();
+    assert_eq!(results(), vec![Some(Ok(rat!(2))), Some(Ok(rat!(6))), Some(Ok(rat!(10))), Some(Ok(rat!(16)))]);
+
+    // Nothing is dirty, so nothing changes
+    assert_eq!(worksheet.recalculate(&settings), vec![]);
+
+    // Changing `a` changes its dependents, but not `c`
+    worksheet.set_expression(0, UnstructuredNodeRoot { root: tokens!(4) });
+    assert_eq!(worksheet.recalculate(&settings), vec![0, 1, 3]);
+    assert_eq!(worksheet.lines()[3].result(), Some(&Ok(rat!(22))));
+
+    // Removing a definition causes a missing variable error in the lines which use it
+    worksheet.set_variable(0, None);
+    assert_eq!(worksheet.recalculate(&settings), vec![1, 3]);
+    assert_eq!(worksheet.lines()[1].result(), Some(&Err(EvaluationError::Maths(MathsError::MissingVariable))));
+}
@@ -0,0 +1,160 @@
+//! A worksheet of calculations, where each line can define a variable for use by later lines.
+//! 
+//! rbop does not yet have syntax for assignments, so the variable defined by a line is stored
+//! alongside its expression, rather than being part of the expression.
+//! 
+//! When a line is modified, it is marked dirty. Calling [recalculate](Worksheet::recalculate) then
+//! evaluates the dirty lines, plus any lines which (directly or indirectly) use variables defined by
+//! them. Lines may only use variables defined by earlier lines, so the worksheet is always
+//! recalculated from top to bottom.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{UnstructuredNodeRoot, Number, StructuredNode, error::EvaluationError, node::{structured::EvaluationSettings, unstructured::Upgradable}};
+
+/// One line of a [Worksheet].
+#[derive(Debug, Clone)]
+pub struct WorksheetLine {
+    variable: Option<char>,
+    expression: UnstructuredNodeRoot,
+    result: Option<Result<Number, EvaluationError>>,
+    dirty: bool,
+}
+
+impl WorksheetLine {
+    /// The variable defined by this line, if any.
+    pub fn variable(&self) -> Option<char> {
+        self.variable
+    }
+
+    /// The expression on this line.
+    pub fn expression(&self) -> &UnstructuredNodeRoot {
+        &self.expression
+    }
+
+    /// The result of this line as of the last recalculation, or `None` if the line has never been
+    /// calculated.
+    pub fn result(&self) -> Option<&Result<Number, EvaluationError>> {
+        self.result.as_ref()
+    }
+}
+
+/// A list of lines which are recalculated when the lines they depend upon change. See the
+/// [module-level documentation](crate::worksheet) for more information.
+#[derive(Debug, Clone, Default)]
+pub struct Worksheet {
+    lines: Vec<WorksheetLine>,
+}
+
+impl Worksheet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The lines in this worksheet.
+    pub fn lines(&self) -> &[WorksheetLine] {
+        &self.lines
+    }
+
+    /// Adds a new line to the end of the worksheet, optionally defining a variable, and returns
+    /// its index. The line is dirty until the next recalculation.
+    pub fn push_line(&mut self, variable: Option<char>, expression: UnstructuredNodeRoot) -> usize {
+        self.lines.push(WorksheetLine { variable, expression, result: None, dirty: true });
+        self.lines.len() - 1
+    }
+
+    /// Replaces the expression on a line, marking it dirty.
+    pub fn set_expression(&mut self, index: usize, expression: UnstructuredNodeRoot) {
+        self.lines[index].expression = expression;
+        self.lines[index].dirty = true;
+    }
+
+    /// Changes the variable defined by a line, marking it dirty.
+    /// 
+    /// Later lines which use either the old or new variable are also marked dirty, since the line
+    /// which they depend on may have changed.
+    pub fn set_variable(&mut self, index: usize, variable: Option<char>) {
+        let old_variable = self.lines[index].variable;
+        self.lines[index].variable = variable;
+        self.lines[index].dirty = true;
+
+        for line in &mut self.lines[(index + 1)..] {
+            if let Ok(node) = line.expression.upgrade() {
+                let used = node.variables();
+                if [old_variable, variable].iter().flatten().any(|v| used.contains(v)) {
+                    line.dirty = true;
+                }
+            }
+        }
+    }
+
+    /// Returns the indexes of the lines which the given line depends on. For each variable used on
+    /// the line, this is the nearest earlier line which defines that variable.
+    pub fn dependencies(&self, index: usize) -> Vec<usize> {
+        match self.lines[index].expression.upgrade() {
+            Ok(node) => self.dependencies_of_node(index, &node)
+                .into_iter()
+                .map(|(_, line)| line)
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Evaluates every dirty line, and every line which depends on a line evaluated during this
+    /// call, in order.
+    /// 
+    /// Returns the indexes of the lines whose result changed.
+    pub fn recalculate(&mut self, settings: &EvaluationSettings) -> Vec<usize> {
+        let mut recalculated = vec![false; self.lines.len()];
+        let mut changed = vec![];
+
+        for i in 0..self.lines.len() {
+            let upgraded = self.lines[i].expression.upgrade();
+            let dependencies = match &upgraded {
+                Ok(node) => self.dependencies_of_node(i, node),
+                Err(_) => vec![],
+            };
+
+            if !self.lines[i].dirty && !dependencies.iter().any(|(_, line)| recalculated[*line]) {
+                continue;
+            }
+
+            let result = upgraded
+                .map_err(EvaluationError::from)
+                .and_then(|mut node| {
+                    // Substitute the results of any dependencies which were successful - any
+                    // which weren't are left as variables, causing a missing variable error
+                    for (variable, line) in dependencies {
+                        if let Some(Ok(value)) = self.lines[line].result {
+                            node = node.substitute_variable(variable, &StructuredNode::Number(value));
+                        }
+                    }
+                    Ok(node.evaluate(settings)?)
+                });
+
+            let line = &mut self.lines[i];
+            if line.result.as_ref() != Some(&result) {
+                changed.push(i);
+            }
+            line.result = Some(result);
+            line.dirty = false;
+            recalculated[i] = true;
+        }
+
+        changed
+    }
+
+    /// Returns pairs of (variable, defining line index) for each variable used by a node, which is
+    /// assumed to be on the line `index`. Variables which are not defined are omitted.
+    fn dependencies_of_node(&self, index: usize, node: &StructuredNode) -> Vec<(char, usize)> {
+        node.variables()
+            .into_iter()
+            .filter_map(|variable|
+                self.lines[..index]
+                    .iter()
+                    .rposition(|line| line.variable == Some(variable))
+                    .map(|line| (variable, line))
+            )
+            .collect()
+    }
+}